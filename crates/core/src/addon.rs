@@ -45,6 +45,10 @@ pub struct AddonFolder {
     pub version: Option<String>,
     pub repository_identifiers: RepositoryIdentifiers,
     pub dependencies: Vec<String>,
+    /// Account wide SavedVariables declared in the `.toc` file
+    pub saved_variables: Vec<String>,
+    /// Per character SavedVariables declared in the `.toc` file
+    pub saved_variables_per_character: Vec<String>,
     pub fingerprint: Option<u32>,
 }
 
@@ -80,6 +84,8 @@ impl AddonFolder {
         version: Option<String>,
         repository_identifiers: RepositoryIdentifiers,
         dependencies: Vec<String>,
+        saved_variables: Vec<String>,
+        saved_variables_per_character: Vec<String>,
     ) -> Self {
         AddonFolder {
             id,
@@ -91,6 +97,8 @@ impl AddonFolder {
             version,
             repository_identifiers,
             dependencies,
+            saved_variables,
+            saved_variables_per_character,
             fingerprint: None,
         }
     }
@@ -139,6 +147,8 @@ pub struct Addon {
     pub pick_release_channel_state: iced_native::pick_list::State<ReleaseChannel>,
     #[cfg(feature = "gui")]
     pub changelog_btn_state: iced_native::button::State,
+    #[cfg(feature = "gui")]
+    pub revert_btn_state: iced_native::button::State,
 }
 
 impl Addon {
@@ -168,6 +178,8 @@ impl Addon {
             pick_release_channel_state: Default::default(),
             #[cfg(feature = "gui")]
             changelog_btn_state: Default::default(),
+            #[cfg(feature = "gui")]
+            revert_btn_state: Default::default(),
         }
     }
 
@@ -270,6 +282,13 @@ impl Addon {
         }
     }
 
+    /// Sets the file id of the installed release.
+    pub(crate) fn set_file_id(&mut self, file_id: i64) {
+        if let Some(metadata) = self.repository.as_mut().map(|r| &mut r.metadata) {
+            metadata.file_id = Some(file_id);
+        }
+    }
+
     /// Returns the title of the addon.
     pub fn title(&self) -> &str {
         let meta_title = self.metadata().map(|m| m.title.as_deref()).flatten();
@@ -380,6 +399,13 @@ impl Addon {
         self.metadata().map(|f| f.file_id).flatten()
     }
 
+    /// Returns the fingerprint Curse has for `folder` in the installed release.
+    pub fn expected_fingerprint(&self, folder: &str) -> Option<u32> {
+        self.metadata()
+            .map(|m| m.module_fingerprints.get(folder).copied())
+            .flatten()
+    }
+
     fn primary_addon_folder(&self) -> Option<&AddonFolder> {
         self.folders.iter().find(|f| f.id == self.primary_folder_id)
     }
@@ -389,6 +415,12 @@ impl Addon {
         self.repository().map(|r| r.id.as_str())
     }
 
+    /// Returns `true` if the remote info for this addon couldn't be fetched
+    /// and was built from cached / local data.
+    pub fn is_remote_stale(&self) -> bool {
+        self.metadata().map(|m| m.stale).unwrap_or_default()
+    }
+
     /// Function returns a `bool` indicating if the user has manually ignored the addon.
     pub fn is_ignored(&self, ignored: Option<&Vec<String>>) -> bool {
         match ignored {
@@ -0,0 +1,254 @@
+//! Snapshots of an addon's folders and SavedVariables, taken before the addon is
+//! updated so the update can be reverted.
+
+use super::{
+    backup_folders, create_staging_folder, delete_backup, list_backups, section_of, swap_in,
+    BackupArchive, BackupFolder, BackupKind, BackupOptions,
+};
+use crate::addon::Addon;
+use crate::config::{BackupFormat, BackupRetention, Config, Flavor};
+use crate::error::FilesystemError;
+use crate::fs::backup::{read_manifest, snapshot_extract};
+
+use chrono::NaiveDateTime;
+use glob::MatchOptions;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where snapshots of the addons of a flavor are saved, and how many to keep.
+#[derive(Debug, Clone)]
+pub struct SnapshotSettings {
+    pub wow_dir: PathBuf,
+    pub wtf_dir: PathBuf,
+    pub snapshot_dir: PathBuf,
+    pub keep: usize,
+}
+
+impl SnapshotSettings {
+    /// Returns `None` if snapshots are turned off or no WoW directory is set.
+    pub fn from_config(config: &Config, flavor: Flavor) -> Option<SnapshotSettings> {
+        let keep = config.addon_snapshots_to_keep();
+
+        if keep == 0 {
+            return None;
+        }
+
+        Some(SnapshotSettings {
+            wow_dir: config.wow.directory.clone()?,
+            wtf_dir: config.get_wtf_directory_for_flavor(&flavor)?,
+            snapshot_dir: config.get_addon_snapshot_directory(),
+            keep,
+        })
+    }
+
+    /// Returns the folder the snapshots of the addon with `addon_id` are saved
+    /// to.
+    fn addon_dir(&self, flavor: Flavor, addon_id: &str) -> PathBuf {
+        self.snapshot_dir.join(flavor.folder_name()).join(addon_id)
+    }
+}
+
+/// Snapshots the folders of `addon` and its SavedVariables files. Only the
+/// newest `settings.keep` snapshots of the addon are kept.
+pub async fn snapshot_addon(
+    addon: Addon,
+    flavor: Flavor,
+    settings: SnapshotSettings,
+) -> Result<NaiveDateTime, FilesystemError> {
+    let wow_dir = &settings.wow_dir;
+
+    let mut src_folders: Vec<_> = addon
+        .folders
+        .iter()
+        .filter(|f| f.path.exists())
+        .map(|f| BackupFolder::new(&f.path, wow_dir))
+        .collect();
+
+    src_folders.extend(
+        saved_variables_files(&addon, &settings.wtf_dir)
+            .into_iter()
+            .map(|path| BackupFolder::new(path, wow_dir)),
+    );
+
+    let dest = settings.addon_dir(flavor, &addon.primary_folder_id);
+    fs::create_dir_all(&dest)?;
+
+    let options = BackupOptions {
+        format: BackupFormat::Incremental,
+        retention: BackupRetention {
+            keep_last: Some(settings.keep),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let as_of = backup_folders(src_folders, dest, options).await?;
+
+    log::debug!(
+        "snapshot of {} ({}) saved",
+        addon.title(),
+        &addon.primary_folder_id
+    );
+
+    Ok(as_of)
+}
+
+/// Returns the snapshots of the addon with `addon_id`, newest first.
+pub async fn addon_snapshots(
+    addon_id: String,
+    flavor: Flavor,
+    settings: SnapshotSettings,
+) -> Vec<BackupArchive> {
+    list_backups(settings.addon_dir(flavor, &addon_id)).await
+}
+
+/// Restores the folders and SavedVariables files of `addon` from its newest
+/// snapshot, and returns when the snapshot was taken.
+///
+/// Folders of `addon` which aren't in the snapshot, such as folders added by
+/// the update, are deleted. The snapshot is deleted once restored, so reverting
+/// again goes back another version.
+pub async fn revert_addon(
+    addon: Addon,
+    flavor: Flavor,
+    settings: SnapshotSettings,
+) -> Result<NaiveDateTime, FilesystemError> {
+    let wow_dir = &settings.wow_dir;
+
+    let dir = settings.addon_dir(flavor, &addon.primary_folder_id);
+    let snapshot = list_backups(dir.clone())
+        .await
+        .into_iter()
+        .next()
+        .ok_or(FilesystemError::NothingToRestore { path: dir })?;
+
+    let entries = read_manifest(&snapshot.path)?.entries;
+
+    let folders: Vec<_> = entries
+        .iter()
+        .filter(|e| e.hash.is_none() && is_addon_folder(&e.name))
+        .map(|e| e.name.as_str())
+        .collect();
+
+    // SavedVariables files, which are copied over the current files
+    let files: Vec<_> = entries
+        .iter()
+        .filter(|e| e.hash.is_some())
+        .map(|e| e.name.as_str())
+        .filter(|name| !folders.iter().any(|f| Path::new(name).starts_with(f)))
+        .collect();
+
+    let staging = create_staging_folder(wow_dir)?;
+
+    let result = snapshot_extract(&snapshot.path, &staging.join("new"), |_| true).and_then(|_| {
+        for folder in folders.iter() {
+            swap_in(
+                &staging.join("new").join(folder),
+                &wow_dir.join(folder),
+                &staging.join("old").join(folder),
+            )?;
+        }
+
+        for file in files.iter() {
+            let target = wow_dir.join(file);
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::copy(staging.join("new").join(file), target)?;
+        }
+
+        Ok(())
+    });
+
+    fs::remove_dir_all(&staging)?;
+
+    result?;
+
+    for folder in addon.folders.iter() {
+        let in_snapshot = folders
+            .iter()
+            .any(|f| Path::new(f).file_name() == Some(OsStr::new(&folder.id)));
+
+        if !in_snapshot && folder.path.exists() {
+            fs::remove_dir_all(&folder.path)?;
+        }
+    }
+
+    delete_backup(snapshot.path).await?;
+
+    log::debug!(
+        "reverted {} ({}) to snapshot of {}",
+        addon.title(),
+        &addon.primary_folder_id,
+        snapshot.as_of
+    );
+
+    Ok(snapshot.as_of)
+}
+
+/// Returns `true` if `name` is a folder in AddOns, eg.
+/// `_retail_/Interface/AddOns/Bagnon`.
+fn is_addon_folder(name: &str) -> bool {
+    matches!(section_of(name), Some((_, BackupKind::AddOns, _))) && name.split('/').count() == 4
+}
+
+/// Returns the SavedVariables files of `addon` for every account and
+/// character in `wtf_dir`. Files are named after the folder of the addon which
+/// declares them in its `.toc` file.
+fn saved_variables_files(addon: &Addon, wtf_dir: &Path) -> Vec<PathBuf> {
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+
+    let wtf_dir = glob::Pattern::escape(&wtf_dir.display().to_string());
+
+    let mut patterns = vec![];
+
+    for folder in addon.folders.iter() {
+        let name = glob::Pattern::escape(&folder.id);
+
+        // `.lua*` includes the `.lua.bak` file WoW keeps of the previous session
+        if !folder.saved_variables.is_empty() {
+            patterns.push(format!(
+                "{}/Account/*/SavedVariables/{}.lua*",
+                wtf_dir, name
+            ));
+        }
+
+        if !folder.saved_variables_per_character.is_empty() {
+            patterns.push(format!(
+                "{}/Account/*/*/*/SavedVariables/{}.lua*",
+                wtf_dir, name
+            ));
+        }
+    }
+
+    patterns
+        .iter()
+        .filter_map(|pattern| glob::glob_with(pattern, options).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_addon_folder() {
+        assert!(is_addon_folder("_retail_/Interface/AddOns/Bagnon"));
+        assert!(!is_addon_folder(
+            "_retail_/Interface/AddOns/Bagnon/Bagnon.toc"
+        ));
+        assert!(!is_addon_folder("_retail_/Interface/AddOns"));
+        assert!(!is_addon_folder(
+            "_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua"
+        ));
+    }
+}
@@ -0,0 +1,755 @@
+use crate::config::{
+    BackupCompression, BackupFilter, BackupFormat, BackupRetention, Config, Flavor,
+};
+use crate::error::FilesystemError;
+use crate::fs::backup::{
+    read_manifest, remove_unused_objects, snapshot_entries, snapshot_extract, snapshot_to_zip,
+    zip_entries, zip_extract, Backup, IncrementalBackup, ZipBackup, SNAPSHOT_FOLDER,
+};
+
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub mod addon;
+
+/// Prefix of the folders inside the WoW directory archives are extracted to
+/// before being moved into place. Each restore gets its own folder.
+const STAGING_FOLDER: &str = ".ajour_restore";
+
+/// Counts the staging folders created by this process, so concurrent restores
+/// started in the same instant still get different folders.
+static STAGING_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How a backup is saved and which files it includes.
+#[derive(Debug, Clone, Default)]
+pub struct BackupOptions {
+    pub format: BackupFormat,
+    pub retention: BackupRetention,
+    pub filter: BackupFilter,
+    pub compression: BackupCompression,
+}
+
+impl BackupOptions {
+    /// Returns the backup options set in `config`.
+    pub fn from_config(config: &Config) -> BackupOptions {
+        BackupOptions {
+            format: config.backup_format,
+            retention: config.backup_retention,
+            filter: config.backup_filter.clone(),
+            compression: config.backup_compression,
+        }
+    }
+}
+
+/// Backs up the list of source folders to the dest folder, either as a .zip
+/// archive or as a snapshot in the incremental store. Older backups in the
+/// dest folder are then deleted according to the retention in `options`.
+pub async fn backup_folders(
+    src_folders: Vec<BackupFolder>,
+    dest: PathBuf,
+    options: BackupOptions,
+) -> Result<NaiveDateTime, FilesystemError> {
+    let now = Local::now().naive_local();
+
+    let archive = backup_path(&dest, options.format, now);
+
+    match options.format {
+        BackupFormat::Zip => {
+            ZipBackup::new(src_folders, &archive, &options.filter, options.compression)?.backup()?
+        }
+        BackupFormat::Incremental => {
+            let store = dest.join(SNAPSHOT_FOLDER);
+            fs::create_dir_all(&store)?;
+
+            IncrementalBackup::new(src_folders, &store, &archive, &options.filter)?.backup()?
+        }
+    }
+
+    // Won't fail since we pass it the correct format
+    let as_of = Archive::try_from(archive).unwrap().as_of;
+
+    // The backup itself succeeded, so failing to clean up is only logged
+    if let Err(e) = apply_retention(dest, options.retention).await {
+        log::error!("failed to apply backup retention: {}", e);
+    }
+
+    Ok(as_of)
+}
+
+/// Finds the latest archive in the supplied backup folder and returns
+/// the datetime it was saved
+pub async fn latest_backup(backup_dir: PathBuf) -> Option<NaiveDateTime> {
+    list_backups(backup_dir)
+        .await
+        .first()
+        .map(|archive| archive.as_of)
+}
+
+/// Returns all archives and snapshots in the supplied backup folder, newest
+/// first.
+pub async fn list_backups(backup_dir: PathBuf) -> Vec<BackupArchive> {
+    let name =
+        "ajour_backup_[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]_[0-9][0-9]-[0-9][0-9]-[0-9][0-9]";
+    let patterns = [
+        format!("{}/{}.zip", backup_dir.display(), name),
+        format!(
+            "{}/{}.json",
+            backup_dir.join(SNAPSHOT_FOLDER).display(),
+            name
+        ),
+    ];
+
+    let mut backups = vec![];
+
+    for pattern in patterns.iter() {
+        for entry in glob::glob(pattern).unwrap() {
+            if let Ok(path) = entry {
+                if let (Ok(archive), Some(format), Ok(metadata)) = (
+                    Archive::try_from(path.clone()),
+                    format_of(&path),
+                    fs::metadata(&path),
+                ) {
+                    backups.push(BackupArchive {
+                        path,
+                        as_of: archive.as_of,
+                        format,
+                        size: metadata.len(),
+                    });
+                }
+            }
+        }
+    }
+
+    backups.sort_by(|a, b| b.as_of.cmp(&a.as_of));
+
+    // A snapshot takes up the size of its manifest plus the files which no
+    // newer snapshot also contains.
+    let mut stored = HashSet::new();
+
+    for backup in backups.iter_mut() {
+        if backup.format == BackupFormat::Incremental {
+            if let Ok(manifest) = read_manifest(&backup.path) {
+                for entry in manifest.entries {
+                    if let Some(hash) = entry.hash {
+                        if stored.insert(hash) {
+                            backup.size += entry.size;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    backups
+}
+
+/// Deletes the archive or snapshot at `archive`. Only files named like an Ajour
+/// backup can be deleted.
+pub async fn delete_backup(archive: PathBuf) -> Result<(), FilesystemError> {
+    let format = remove_backup_file(&archive)?;
+
+    if format == BackupFormat::Incremental {
+        if let Some(store) = archive.parent() {
+            remove_unused_objects(store)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the file of the archive or snapshot at `archive` and returns its
+/// format. Files only used by a deleted snapshot are left in the store.
+fn remove_backup_file(archive: &Path) -> Result<BackupFormat, FilesystemError> {
+    let format = match format_of(archive) {
+        Some(format) if Archive::try_from(archive.to_owned()).is_ok() => format,
+        _ => {
+            return Err(FilesystemError::NotABackup {
+                path: archive.to_owned(),
+            })
+        }
+    };
+
+    fs::remove_file(archive)?;
+
+    log::debug!("deleted backup {:?}", archive);
+
+    Ok(format)
+}
+
+/// Saves the archive or snapshot at `archive` as a .zip archive in `dest` and
+/// returns its path. Snapshots are compressed with `compression`, .zip
+/// archives are copied as is.
+pub async fn export_backup(
+    archive: PathBuf,
+    dest: PathBuf,
+    compression: BackupCompression,
+) -> Result<PathBuf, FilesystemError> {
+    let (as_of, format) = match (Archive::try_from(archive.clone()), format_of(&archive)) {
+        (Ok(Archive { as_of }), Some(format)) => (as_of, format),
+        _ => return Err(FilesystemError::NotABackup { path: archive }),
+    };
+
+    let exported = backup_path(&dest, BackupFormat::Zip, as_of);
+
+    // Exporting a .zip archive to its own folder would copy it onto itself,
+    // truncating it
+    if is_same_file(&archive, &exported) {
+        return Ok(exported);
+    }
+
+    match format {
+        BackupFormat::Zip => {
+            fs::copy(&archive, &exported)?;
+        }
+        BackupFormat::Incremental => snapshot_to_zip(&archive, &exported, compression)?,
+    }
+
+    Ok(exported)
+}
+
+/// Returns `true` if `a` and `b` both exist and are the same file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Returns the path a backup made at `as_of` is saved to in `backup_dir`.
+fn backup_path(backup_dir: &Path, format: BackupFormat, as_of: NaiveDateTime) -> PathBuf {
+    let name = format!("ajour_backup_{}", as_of.format("%Y-%m-%d_%H-%M-%S"));
+
+    match format {
+        BackupFormat::Zip => backup_dir.join(name + ".zip"),
+        BackupFormat::Incremental => backup_dir.join(SNAPSHOT_FOLDER).join(name + ".json"),
+    }
+}
+
+/// Returns the format of the backup at `path` by its extension.
+fn format_of(path: &Path) -> Option<BackupFormat> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("zip") => Some(BackupFormat::Zip),
+        Some("json") => Some(BackupFormat::Incremental),
+        _ => None,
+    }
+}
+
+/// Deletes the archives in `backup_dir` which `retention` doesn't keep and
+/// returns them.
+pub async fn apply_retention(
+    backup_dir: PathBuf,
+    retention: BackupRetention,
+) -> Result<Vec<BackupArchive>, FilesystemError> {
+    if !retention.is_set() {
+        return Ok(vec![]);
+    }
+
+    let archives = list_backups(backup_dir.clone()).await;
+    let expired = expired_backups(&archives, &retention, Local::now().naive_local());
+
+    let mut deleted = vec![];
+
+    for index in expired {
+        let archive = archives[index].clone();
+        remove_backup_file(&archive.path)?;
+        deleted.push(archive);
+    }
+
+    // Files of the deleted snapshots are removed once all of them are deleted
+    if deleted
+        .iter()
+        .any(|a| a.format == BackupFormat::Incremental)
+    {
+        remove_unused_objects(&backup_dir.join(SNAPSHOT_FOLDER))?;
+    }
+
+    Ok(deleted)
+}
+
+/// Returns the indices of the archives `retention` doesn't keep. `archives`
+/// must be sorted newest first.
+fn expired_backups(
+    archives: &[BackupArchive],
+    retention: &BackupRetention,
+    now: NaiveDateTime,
+) -> Vec<usize> {
+    let keep_all = retention.keep_last.is_none()
+        && retention.keep_daily.is_none()
+        && retention.keep_weekly.is_none();
+
+    let today = now.date();
+    let this_week = week_start(today);
+
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    let mut kept = vec![];
+    let mut expired = vec![];
+
+    for (index, archive) in archives.iter().enumerate() {
+        // Since archives are sorted newest first, the first archive seen for a
+        // day or week is the newest one.
+        let day = archive.as_of.date();
+        let week = week_start(day);
+        let is_newest_of_day = days.insert(day);
+        let is_newest_of_week = weeks.insert(week);
+
+        let keep = keep_all
+            || index == 0
+            || retention.keep_last.map_or(false, |n| index < n)
+            || retention.keep_daily.map_or(false, |n| {
+                is_newest_of_day && day > today - Duration::days(i64::from(n))
+            })
+            || retention.keep_weekly.map_or(false, |n| {
+                is_newest_of_week && week > this_week - Duration::weeks(i64::from(n))
+            });
+
+        if keep {
+            kept.push(index);
+        } else {
+            expired.push(index);
+        }
+    }
+
+    if let Some(max_total_size) = retention.max_total_size {
+        let max_total_size = max_total_size * 1024 * 1024;
+        let mut total_size = 0;
+
+        for index in kept {
+            total_size += archives[index].size;
+
+            if index > 0 && total_size > max_total_size {
+                expired.push(index);
+            }
+        }
+
+        expired.sort_unstable();
+    }
+
+    expired
+}
+
+/// Returns the AddOns and WTF folders stored in the archive or snapshot at
+/// `archive`.
+pub async fn archive_contents(archive: PathBuf) -> Result<Vec<ArchiveSection>, FilesystemError> {
+    let mut sections: Vec<ArchiveSection> = vec![];
+
+    let entries = match format_of(&archive) {
+        Some(BackupFormat::Incremental) => snapshot_entries(&archive)?,
+        _ => zip_entries(&archive)?,
+    };
+
+    for entry in entries {
+        if let Some((flavor, kind, path)) = section_of(&entry.name) {
+            let section = match sections.iter_mut().find(|s| s.path == path) {
+                Some(section) => section,
+                None => {
+                    sections.push(ArchiveSection {
+                        flavor,
+                        kind,
+                        path,
+                        files: 0,
+                        size: 0,
+                    });
+                    sections.last_mut().unwrap()
+                }
+            };
+
+            if !entry.is_dir {
+                section.files += 1;
+                section.size += entry.size;
+            }
+        }
+    }
+
+    sections.sort_by_key(|s| (s.flavor, s.kind));
+
+    Ok(sections)
+}
+
+/// Restores the folders in `selection` from the archive or snapshot at
+/// `archive` into `wow_dir`.
+///
+/// The current folders are backed up to `snapshot_dir` first. The archive is
+/// then extracted to a staging folder, and each folder is only swapped in once
+/// it's fully extracted.
+pub async fn restore_backup(
+    archive: PathBuf,
+    wow_dir: PathBuf,
+    selection: RestoreSelection,
+    snapshot_dir: PathBuf,
+) -> Result<RestoreResult, FilesystemError> {
+    let sections: Vec<_> = archive_contents(archive.clone())
+        .await?
+        .into_iter()
+        .filter(|s| selection.includes(s))
+        .collect();
+
+    if sections.is_empty() {
+        return Err(FilesystemError::NothingToRestore { path: archive });
+    }
+
+    // Safety snapshot of the folders we are about to replace
+    let current_folders: Vec<_> = sections
+        .iter()
+        .map(|s| wow_dir.join(&s.path))
+        .filter(|path| path.exists())
+        .map(|path| BackupFolder::new(path, &wow_dir))
+        .collect();
+
+    let snapshot = if current_folders.is_empty() {
+        None
+    } else {
+        fs::create_dir_all(&snapshot_dir)?;
+
+        // Every file is saved, regardless of the configured filter
+        let as_of = backup_folders(
+            current_folders,
+            snapshot_dir.clone(),
+            BackupOptions::default(),
+        )
+        .await?;
+
+        Some(backup_path(&snapshot_dir, BackupFormat::Zip, as_of))
+    };
+
+    let staging = create_staging_folder(&wow_dir)?;
+
+    let filter = |name: &str| {
+        sections
+            .iter()
+            .any(|s| Path::new(name).starts_with(&s.path))
+    };
+
+    let extracted = match format_of(&archive) {
+        Some(BackupFormat::Incremental) => snapshot_extract(&archive, &staging.join("new"), filter),
+        _ => zip_extract(&archive, &staging.join("new"), filter),
+    };
+
+    let result = extracted.and_then(|_| {
+        for section in sections.iter() {
+            swap_in(
+                &staging.join("new").join(&section.path),
+                &wow_dir.join(&section.path),
+                &staging.join("old").join(&section.path),
+            )?;
+        }
+
+        Ok(())
+    });
+
+    fs::remove_dir_all(&staging)?;
+
+    result?;
+
+    Ok(RestoreResult {
+        restored: sections,
+        snapshot,
+    })
+}
+
+/// Creates a new, empty staging folder in `wow_dir`, named
+/// `.ajour_restore-<timestamp>-<counter>`.
+fn create_staging_folder(wow_dir: &Path) -> Result<PathBuf, FilesystemError> {
+    loop {
+        let staging = wow_dir.join(format!(
+            "{}-{}-{}",
+            STAGING_FOLDER,
+            Local::now().timestamp_millis(),
+            STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // Fails if the folder exists, so a folder in use is never shared
+        match fs::create_dir(&staging) {
+            Ok(_) => return Ok(staging),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Moves `staged` to `target`. An existing `target` is moved to `old` first, and
+/// moved back if `staged` can't be moved into place.
+fn swap_in(staged: &Path, target: &Path, old: &Path) -> Result<(), FilesystemError> {
+    fs::create_dir_all(staged)?;
+
+    if let Some(parent) = old.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let had_target = target.exists();
+    if had_target {
+        fs::rename(target, old)?;
+    }
+
+    if let Err(e) = fs::rename(staged, target) {
+        if had_target {
+            fs::rename(old, target)?;
+        }
+
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Returns the flavor, kind and root path of the archive section `name` is in,
+/// eg. `_retail_/Interface/AddOns/Bagnon/Bagnon.toc` is in the Retail AddOns
+/// section with root `_retail_/Interface/AddOns`.
+fn section_of(name: &str) -> Option<(Flavor, BackupKind, PathBuf)> {
+    let components: Vec<_> = name.split('/').collect();
+
+    let root = *components.first()?;
+    let flavor = Flavor::ALL
+        .iter()
+        .copied()
+        .find(|f| f.folder_name() == root)?;
+
+    match components.get(1..3) {
+        Some([interface, addons])
+            if interface.eq_ignore_ascii_case("interface")
+                && addons.eq_ignore_ascii_case("addons") =>
+        {
+            Some((flavor, BackupKind::AddOns, components[..3].iter().collect()))
+        }
+        _ if components
+            .get(1)
+            .map_or(false, |c| c.eq_ignore_ascii_case("wtf")) =>
+        {
+            Some((flavor, BackupKind::WTF, components[..2].iter().collect()))
+        }
+        _ => None,
+    }
+}
+
+/// A folder which can be backed up and restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BackupKind {
+    AddOns,
+    WTF,
+}
+
+impl std::fmt::Display for BackupKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BackupKind::AddOns => "AddOns",
+            BackupKind::WTF => "WTF",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The AddOns or WTF folder of a flavor stored in an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSection {
+    pub flavor: Flavor,
+    pub kind: BackupKind,
+    /// Path of the folder relative to the WoW directory
+    pub path: PathBuf,
+    pub files: usize,
+    pub size: u64,
+}
+
+/// What to restore from an archive. Empty `flavors` or `kinds` restores all
+/// of them.
+#[derive(Debug, Clone, Default)]
+pub struct RestoreSelection {
+    pub flavors: Vec<Flavor>,
+    pub kinds: Vec<BackupKind>,
+}
+
+impl RestoreSelection {
+    fn includes(&self, section: &ArchiveSection) -> bool {
+        (self.flavors.is_empty() || self.flavors.contains(&section.flavor))
+            && (self.kinds.is_empty() || self.kinds.contains(&section.kind))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestoreResult {
+    /// Folders which were restored
+    pub restored: Vec<ArchiveSection>,
+    /// Archive of the folders as they were before restoring
+    pub snapshot: Option<PathBuf>,
+}
+
+/// Returns the monday of the week `date` is in.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// An archive or snapshot in the backup directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupArchive {
+    /// Path of the .zip archive or snapshot manifest
+    pub path: PathBuf,
+    pub as_of: NaiveDateTime,
+    pub format: BackupFormat,
+    /// Size in bytes. For snapshots, files shared with newer snapshots aren't
+    /// counted, so the sizes of all backups add up to the space they take up.
+    pub size: u64,
+}
+
+/// Specifies a folder that we want backed up. `prefix` will get stripped out of
+/// the path of each entry in the archive.
+pub struct BackupFolder {
+    pub path: PathBuf,
+    pub prefix: PathBuf,
+}
+
+impl BackupFolder {
+    pub fn new(path: impl AsRef<Path>, prefix: impl AsRef<Path>) -> BackupFolder {
+        BackupFolder {
+            path: path.as_ref().to_owned(),
+            prefix: prefix.as_ref().to_owned(),
+        }
+    }
+}
+
+/// Metadata for our archive saved on the filesystem. Converted from a `PathBuf` with
+/// the correct naming convention
+struct Archive {
+    pub as_of: NaiveDateTime,
+}
+
+impl TryFrom<PathBuf> for Archive {
+    type Error = chrono::ParseError;
+
+    fn try_from(path: PathBuf) -> Result<Archive, chrono::ParseError> {
+        let file_stem = path.file_stem().unwrap().to_str().unwrap();
+
+        let date_str = format!(
+            "{} {}",
+            file_stem.split('_').nth(2).unwrap_or_default(),
+            file_stem.split('_').nth(3).unwrap_or_default()
+        );
+
+        let as_of = NaiveDateTime::parse_from_str(&date_str, "%Y-%m-%d %H-%M-%S")?;
+
+        Ok(Archive { as_of })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::test_dir;
+
+    #[test]
+    fn test_section_of() {
+        assert_eq!(
+            section_of("_retail_/Interface/AddOns/Bagnon/Bagnon.toc"),
+            Some((
+                Flavor::Retail,
+                BackupKind::AddOns,
+                PathBuf::from("_retail_/Interface/AddOns")
+            ))
+        );
+        assert_eq!(
+            section_of("_classic_/WTF/Config.wtf"),
+            Some((
+                Flavor::Classic,
+                BackupKind::WTF,
+                PathBuf::from("_classic_/WTF")
+            ))
+        );
+        assert_eq!(section_of("_retail_/Logs/FrameXML.log"), None);
+    }
+
+    #[test]
+    fn test_expired_backups() {
+        let now =
+            NaiveDateTime::parse_from_str("2020-11-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        // Two archives a day for the last 30 days, newest first
+        let archives: Vec<_> = (0..60)
+            .map(|i| BackupArchive {
+                path: PathBuf::new(),
+                as_of: now - Duration::hours(i * 12 + 1),
+                format: BackupFormat::Zip,
+                size: 10 * 1024 * 1024,
+            })
+            .collect();
+
+        let expired = |retention| expired_backups(&archives, &retention, now);
+
+        assert!(expired(BackupRetention::default()).is_empty());
+
+        let retention = BackupRetention {
+            keep_last: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(expired(retention), (5..60).collect::<Vec<_>>());
+
+        // One archive for each of the last 7 days, including today
+        let retention = BackupRetention {
+            keep_daily: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(60 - expired(retention).len(), 7);
+
+        // 2020-11-20 is a friday, so this week has 5 days of archives
+        let retention = BackupRetention {
+            keep_weekly: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(retention),
+            [(1..9).collect::<Vec<_>>(), (10..60).collect()].concat()
+        );
+
+        // The newest archive is kept even if it's larger than the limit
+        let retention = BackupRetention {
+            max_total_size: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(expired(retention), (1..60).collect::<Vec<_>>());
+
+        let retention = BackupRetention {
+            keep_last: Some(5),
+            max_total_size: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(expired(retention), (3..60).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_export_backup_to_own_folder() {
+        let dir = test_dir("export_backup_to_own_folder");
+        let archive = dir.join("ajour_backup_2020-11-20_12-00-00.zip");
+        fs::write(&archive, b"archive").unwrap();
+
+        let exported = async_std::task::block_on(export_backup(
+            archive.clone(),
+            dir.clone(),
+            BackupCompression::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(exported, archive);
+        assert_eq!(fs::read(&archive).unwrap(), b"archive");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_staging_folder() {
+        let dir = test_dir("create_staging_folder");
+
+        let first = create_staging_folder(&dir).unwrap();
+        let second = create_staging_folder(&dir).unwrap();
+
+        assert_ne!(first, second);
+        assert!(first.is_dir() && second.is_dir());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
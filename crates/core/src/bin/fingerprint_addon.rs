@@ -1,4 +1,4 @@
-use ajour_core::parse::fingerprint_addon_dir;
+use ajour_core::parse::diagnose_addon_dir;
 use async_std::task;
 use std::env;
 use std::path::PathBuf;
@@ -7,15 +7,34 @@ fn main() {
     let mut args = env::args();
     args.next();
 
-    if args.len() != 1 {
-        panic!("Usage: parse_addon <PATH>");
+    if args.len() != 1 && args.len() != 2 {
+        panic!("Usage: fingerprint_addon <PATH> [EXPECTED FINGERPRINT]");
     }
 
     let path = PathBuf::from(args.next().unwrap());
+    let expected = args.next().map(|expected| {
+        expected
+            .parse::<u32>()
+            .expect("Invalid expected fingerprint")
+    });
 
     task::block_on(async move {
-        let fingerprint = fingerprint_addon_dir(&path).unwrap();
+        let diagnostics = diagnose_addon_dir(&path, expected, &[]).unwrap();
+
+        for file in diagnostics.files.iter() {
+            if let Some(hash) = file.hash {
+                println!("{:>10}  {}", hash, file.path);
+            }
+        }
+
+        println!("Fingerprint is {}", diagnostics.fingerprint);
 
-        println!("Fingerprint is {}", fingerprint);
+        if let Some(expected) = diagnostics.expected {
+            if expected == diagnostics.fingerprint {
+                println!("Fingerprint matches {}", expected);
+            } else {
+                println!("Fingerprint doesn't match {}", expected);
+            }
+        }
     });
 }
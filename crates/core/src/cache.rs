@@ -1,18 +1,19 @@
-use crate::addon::Addon;
+use crate::addon::{Addon, AddonFolder};
+use crate::catalog::{self, Catalog};
 use crate::config::Flavor;
 use crate::error::{CacheError, FilesystemError};
 use crate::fs::{config_dir, PersistentData};
-use crate::parse::Fingerprint;
-use crate::repository::RepositoryKind;
+use crate::parse::{fingerprint_folders, Fingerprint};
+use crate::repository::{curse, ReleaseChannel, RepositoryKind};
 
 use async_std::fs::rename;
 use async_std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct FingerprintCache(HashMap<Flavor, Vec<Fingerprint>>);
@@ -49,18 +50,38 @@ pub async fn load_fingerprint_cache() -> Result<FingerprintCache, CacheError> {
 #[derive(Serialize, Deserialize, Debug)]
 pub enum AddonCache {
     V1(HashMap<Flavor, Vec<AddonCacheEntry>>),
+    /// Entries also record the release the addon was installed from.
+    V2(HashMap<Flavor, Vec<AddonCacheEntry>>),
 }
 
 impl Default for AddonCache {
     fn default() -> Self {
-        AddonCache::V1(Default::default())
+        AddonCache::V2(Default::default())
     }
 }
 
 impl AddonCache {
     pub(crate) fn get_mut_for_flavor(&mut self, flavor: Flavor) -> &mut Vec<AddonCacheEntry> {
         match self {
-            AddonCache::V1(cache) => cache.entry(flavor).or_default(),
+            AddonCache::V1(cache) | AddonCache::V2(cache) => cache.entry(flavor).or_default(),
+        }
+    }
+
+    /// Returns the entries for `flavor`.
+    pub fn get_for_flavor(&self, flavor: Flavor) -> &[AddonCacheEntry] {
+        match self {
+            AddonCache::V1(cache) | AddonCache::V2(cache) => {
+                cache.get(&flavor).map(Vec::as_slice).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Migrates a `V1` cache to `V2`. Migrated entries have no installed
+    /// release until the addon is installed or updated again.
+    fn migrate(self) -> (Self, bool) {
+        match self {
+            AddonCache::V1(cache) => (AddonCache::V2(cache), true),
+            cache @ AddonCache::V2(_) => (cache, false),
         }
     }
 }
@@ -72,7 +93,67 @@ impl PersistentData for AddonCache {
 }
 
 pub async fn load_addon_cache() -> Result<AddonCache, CacheError> {
-    Ok(AddonCache::load_or_default()?)
+    let addon_cache: AddonCache = AddonCache::load_or_default()?;
+    let (addon_cache, migrated) = addon_cache.migrate();
+
+    if migrated {
+        log::debug!("migrated addon cache to V2");
+
+        addon_cache.save()?;
+    }
+
+    Ok(addon_cache)
+}
+
+/// The Curse game info from the last successful download. Its parsing rules
+/// are used to fingerprint addons, unless overridden by a local file.
+impl PersistentData for curse::GameInfo {
+    fn relative_path() -> PathBuf {
+        PathBuf::from("cache/game_info.yml")
+    }
+}
+
+/// Downloads the Curse game info and saves it. The parsing rules it holds
+/// are used to fingerprint addons from the next launch on.
+pub async fn update_game_info_cache() -> Result<(), CacheError> {
+    let game_info = curse::fetch_game_info().await?;
+
+    Ok(game_info.save()?)
+}
+
+/// The merged catalog from the last successful download.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogCache {
+    pub fetched_at: DateTime<Utc>,
+    pub catalog: Catalog,
+}
+
+impl CatalogCache {
+    pub fn new(catalog: Catalog) -> Self {
+        CatalogCache {
+            fetched_at: Utc::now(),
+            catalog,
+        }
+    }
+
+    /// Returns `true` if a newer catalog has been published since this was fetched.
+    pub fn is_stale(&self) -> bool {
+        catalog::is_stale(&self.fetched_at)
+    }
+}
+
+impl PersistentData for CatalogCache {
+    fn relative_path() -> PathBuf {
+        PathBuf::from("cache/catalog.yml")
+    }
+}
+
+pub async fn load_catalog_cache() -> Result<CatalogCache, CacheError> {
+    Ok(CatalogCache::load()?)
+}
+
+pub async fn save_catalog_cache(catalog_cache: CatalogCache) -> Result<(), CacheError> {
+    Ok(catalog_cache.save()?)
 }
 
 /// Update the cache with input entry. If an entry already exists in the cache,
@@ -101,6 +182,73 @@ pub async fn update_addon_cache(
     Ok(entry)
 }
 
+/// Update the cache with an entry for `addon`, which was just installed into
+/// `addon_dir`, recording the release it was installed from.
+pub async fn cache_installed_addon(
+    addon_cache: Arc<Mutex<AddonCache>>,
+    addon: Addon,
+    addon_dir: PathBuf,
+    flavor: Flavor,
+) -> Result<AddonCacheEntry, CacheError> {
+    let entry = AddonCacheEntry::installed(&addon, &addon_dir)?;
+
+    update_addon_cache(addon_cache, entry, flavor).await
+}
+
+/// Replaces the Curse entries for `flavor` with entries for the Curse addons
+/// in `addons`, which were just matched by fingerprint.
+pub(crate) async fn update_curse_cache_entries(
+    addon_cache: Arc<Mutex<AddonCache>>,
+    flavor: Flavor,
+    addons: &[Addon],
+) -> Result<(), CacheError> {
+    // Lock mutex to get mutable access and block other tasks from trying to update
+    let mut addon_cache = addon_cache.lock().await;
+
+    // Persist changes to filesystem
+    if replace_curse_entries(addon_cache.get_mut_for_flavor(flavor), addons) {
+        addon_cache.save()?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the Curse entries in `entries` with entries for the Curse addons in
+/// `addons`. Existing entries for the same addon and folders are kept, along
+/// with the release they record. Returns `true` if any entry changed.
+fn replace_curse_entries(entries: &mut Vec<AddonCacheEntry>, addons: &[Addon]) -> bool {
+    let (old_entries, other_entries): (Vec<_>, Vec<_>) = entries
+        .drain(..)
+        .partition(|e| e.repository == RepositoryKind::Curse);
+
+    let is_same = |a: &AddonCacheEntry, b: &AddonCacheEntry| {
+        a.repository_id == b.repository_id && a.folder_names == b.folder_names
+    };
+
+    let new_entries: Vec<_> = addons
+        .iter()
+        .filter(|a| a.repository_kind() == Some(RepositoryKind::Curse))
+        .filter_map(|a| AddonCacheEntry::try_from(a).ok())
+        .map(|entry| {
+            old_entries
+                .iter()
+                .find(|e| is_same(e, &entry))
+                .cloned()
+                .unwrap_or(entry)
+        })
+        .collect();
+
+    let changed = old_entries.len() != new_entries.len()
+        || new_entries
+            .iter()
+            .any(|entry| !old_entries.iter().any(|e| is_same(e, entry)));
+
+    entries.extend(other_entries);
+    entries.extend(new_entries);
+
+    changed
+}
+
 /// Remove the cache entry that has the same folder names
 /// as the input entry. Will return the removed entry, if applicable.
 pub async fn remove_addon_cache_entry(
@@ -138,6 +286,62 @@ pub struct AddonCacheEntry {
     pub primary_folder_id: String,
     pub folder_names: Vec<String>,
     pub modified: DateTime<Utc>,
+    /// Version of the installed release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// File id of the installed release, if the repository has them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<i64>,
+    #[serde(default)]
+    pub release_channel: ReleaseChannel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<DateTime<Utc>>,
+    /// Fingerprint of each folder right after the release was installed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fingerprints: BTreeMap<String, u32>,
+}
+
+impl AddonCacheEntry {
+    /// Creates the entry for `addon`, which was just installed into
+    /// `addon_dir`, recording the release it was installed from.
+    pub fn installed(addon: &Addon, addon_dir: &Path) -> Result<Self, CacheError> {
+        let mut entry = AddonCacheEntry::try_from(addon)?;
+
+        // Same release `download_addon` installs
+        if let Some(package) = addon
+            .relevant_release_package()
+            .or_else(|| addon.fallback_release_package())
+        {
+            entry.version = Some(package.version);
+            entry.file_id = package.file_id;
+            entry.download_url = Some(package.download_url);
+        }
+
+        entry.release_channel = addon.release_channel;
+        entry.installed_at = Some(entry.modified);
+
+        // Without fingerprints the installed release is never trusted, but the
+        // entry is still needed to match the addon to its repository
+        match fingerprint_folders(addon_dir, &entry.folder_names) {
+            Ok(fingerprints) => entry.fingerprints = fingerprints,
+            Err(e) => log::warn!("failed to fingerprint {}: {}", &entry.title, e),
+        }
+
+        Ok(entry)
+    }
+
+    /// Returns `true` if the installed release is known and every folder in
+    /// `folders` still has the fingerprint it had right after installing it.
+    pub(crate) fn is_unmodified(&self, folders: &[AddonFolder]) -> bool {
+        self.version.is_some()
+            && !self.fingerprints.is_empty()
+            && self.fingerprints.len() == folders.len()
+            && folders.iter().all(|f| {
+                f.fingerprint.is_some() && self.fingerprints.get(&f.id) == f.fingerprint.as_ref()
+            })
+    }
 }
 
 impl TryFrom<&Addon> for AddonCacheEntry {
@@ -157,6 +361,12 @@ impl TryFrom<&Addon> for AddonCacheEntry {
                 primary_folder_id: addon.primary_folder_id.clone(),
                 folder_names,
                 modified: Utc::now(),
+                version: None,
+                file_id: None,
+                release_channel: Default::default(),
+                download_url: None,
+                installed_at: None,
+                fingerprints: Default::default(),
             })
         } else {
             Err(CacheError::AddonMissingRepo {
@@ -165,3 +375,144 @@ impl TryFrom<&Addon> for AddonCacheEntry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::RepositoryPackage;
+
+    fn addon_folder(id: &str, fingerprint: u32) -> AddonFolder {
+        let mut folder = AddonFolder::new(
+            id.to_owned(),
+            id.to_owned(),
+            None,
+            PathBuf::from(id),
+            None,
+            None,
+            None,
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        );
+        folder.fingerprint = Some(fingerprint);
+        folder
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        let yaml = r#"---
+V1:
+  Retail:
+    - title: ElvUI
+      repository: Tukui
+      repository_id: "-1"
+      primary_folder_id: ElvUI
+      folder_names:
+        - ElvUI
+        - ElvUI_OptionsUI
+      modified: "2020-11-01T12:00:00Z"
+"#;
+
+        let cache: AddonCache = serde_yaml::from_str(yaml).unwrap();
+
+        let (mut cache, migrated) = cache.migrate();
+        assert!(migrated);
+        assert!(matches!(cache, AddonCache::V2(_)));
+
+        let entry = &cache.get_mut_for_flavor(Flavor::Retail)[0];
+        assert_eq!(entry.title, "ElvUI");
+        assert_eq!(entry.version, None);
+        assert!(entry.fingerprints.is_empty());
+
+        let (_, migrated) = cache.migrate();
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn test_is_unmodified() {
+        let folders = vec![addon_folder("ElvUI", 1), addon_folder("ElvUI_OptionsUI", 2)];
+
+        let mut entry = AddonCacheEntry {
+            title: "ElvUI".to_owned(),
+            repository: RepositoryKind::Tukui,
+            repository_id: "-1".to_owned(),
+            primary_folder_id: "ElvUI".to_owned(),
+            folder_names: vec!["ElvUI".to_owned(), "ElvUI_OptionsUI".to_owned()],
+            modified: Utc::now(),
+            version: Some("12.0".to_owned()),
+            file_id: None,
+            release_channel: ReleaseChannel::Stable,
+            download_url: None,
+            installed_at: None,
+            fingerprints: vec![("ElvUI".to_owned(), 1), ("ElvUI_OptionsUI".to_owned(), 2)]
+                .into_iter()
+                .collect(),
+        };
+
+        assert!(entry.is_unmodified(&folders));
+
+        let changed = vec![addon_folder("ElvUI", 1), addon_folder("ElvUI_OptionsUI", 3)];
+        assert!(!entry.is_unmodified(&changed));
+        assert!(!entry.is_unmodified(&folders[..1]));
+
+        entry.version = None;
+        assert!(!entry.is_unmodified(&folders));
+    }
+
+    #[test]
+    fn test_replace_curse_entries() {
+        let entry = |repository, id: &str, folder: &str| AddonCacheEntry {
+            title: folder.to_owned(),
+            repository,
+            repository_id: id.to_owned(),
+            primary_folder_id: folder.to_owned(),
+            folder_names: vec![folder.to_owned()],
+            modified: Utc::now(),
+            version: None,
+            file_id: None,
+            release_channel: ReleaseChannel::Stable,
+            download_url: None,
+            installed_at: None,
+            fingerprints: Default::default(),
+        };
+
+        let curse_addon = |id: &str, folder: &str| {
+            let package = RepositoryPackage::from_repo_id(
+                Flavor::Retail,
+                RepositoryKind::Curse,
+                id.to_owned(),
+            )
+            .unwrap();
+
+            Addon::build_with_repo_and_folders(package, vec![addon_folder(folder, 1)]).unwrap()
+        };
+
+        let mut installed = entry(RepositoryKind::Curse, "61284", "Details");
+        installed.version = Some("1.0".to_owned());
+
+        let mut entries = vec![
+            entry(RepositoryKind::Tukui, "-1", "ElvUI"),
+            installed,
+            entry(RepositoryKind::Curse, "3358", "DBM-Core"),
+        ];
+
+        // Removed addons lose their entry, matched addons keep theirs
+        let addons = vec![curse_addon("61284", "Details")];
+        assert!(replace_curse_entries(&mut entries, &addons));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].repository, RepositoryKind::Tukui);
+        assert_eq!(entries[1].repository_id, "61284");
+        assert_eq!(entries[1].version.as_deref(), Some("1.0"));
+
+        assert!(!replace_curse_entries(&mut entries, &addons));
+
+        let addons = vec![
+            curse_addon("61284", "Details"),
+            curse_addon("3358", "DBM-Core"),
+        ];
+        assert!(replace_curse_entries(&mut entries, &addons));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].repository_id, "3358");
+    }
+}
@@ -0,0 +1,368 @@
+use crate::config::Flavor;
+use crate::error::DownloadError;
+use crate::network::request_async;
+
+use async_std::task;
+use chrono::prelude::*;
+use futures::future::join_all;
+use isahc::ResponseExt;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub mod search;
+
+const CURSE_CATALOG_URL: &str =
+    "https://github.com/casperstorm/ajour-catalog/releases/latest/download/curse.json";
+const TUKUI_CATALOG_URL: &str =
+    "https://github.com/casperstorm/ajour-catalog/releases/latest/download/tukui.json";
+const WOWI_CATALOG_URL: &str =
+    "https://github.com/casperstorm/ajour-catalog/releases/latest/download/wowi.json";
+
+const CATALOG_URLS: [&str; 3] = [WOWI_CATALOG_URL, CURSE_CATALOG_URL, TUKUI_CATALOG_URL];
+
+pub async fn get_catalog_addons_from(url: &str) -> Vec<CatalogAddon> {
+    let mut addons = vec![];
+
+    let request = request_async(url, vec![], None);
+    if let Ok(mut response) = request.await {
+        if let Ok(json) = task::spawn_blocking(move || response.json::<Vec<CatalogAddon>>()).await {
+            log::debug!("Successfully fetched and parsed {}", url);
+            addons.extend(json);
+        } else {
+            log::debug!("Could not parse {}", url);
+        }
+    } else {
+        log::debug!("Could not fetch {}", url);
+    }
+
+    addons
+}
+
+async fn get_catalog_addons_from_file(path: &Path) -> Vec<CatalogAddon> {
+    let mut addons = vec![];
+
+    if let Ok(json) = async_std::fs::read_to_string(path).await {
+        if let Ok(json) = serde_json::from_str::<Vec<CatalogAddon>>(&json) {
+            log::debug!("Successfully read and parsed {:?}", path);
+            addons.extend(json);
+        } else {
+            log::debug!("Could not parse {:?}", path);
+        }
+    } else {
+        log::debug!("Could not read {:?}", path);
+    }
+
+    addons
+}
+
+async fn get_custom_catalog_addons(source: &CustomSource) -> Vec<CatalogAddon> {
+    let mut addons = match &source.location {
+        CustomSourceLocation::Url(url) => get_catalog_addons_from(url).await,
+        CustomSourceLocation::File(path) => get_catalog_addons_from_file(path).await,
+    };
+
+    for addon in addons.iter_mut() {
+        addon.origin = CatalogOrigin::Custom(source.name.clone());
+    }
+
+    addons
+}
+
+/// Fetches and merges the official catalogs with the `custom_sources`.
+///
+/// Custom sources are merged first, in the order given, so if the same addon
+/// is found in multiple catalogs the one with the highest priority is kept.
+pub async fn get_catalog(custom_sources: Vec<CustomSource>) -> Result<Catalog, DownloadError> {
+    let custom_futures = join_all(custom_sources.iter().map(get_custom_catalog_addons));
+    let official_futures = join_all(CATALOG_URLS.iter().map(|url| get_catalog_addons_from(url)));
+
+    let (custom_results, official_results) = futures::join!(custom_futures, official_futures);
+
+    let mut seen = HashSet::new();
+    let mut addons = vec![];
+    for _addons in custom_results.into_iter().chain(official_results) {
+        addons.extend(
+            _addons
+                .into_iter()
+                .filter(|a| seen.insert((a.source, a.id))),
+        );
+    }
+
+    if !addons.is_empty() {
+        Ok(Catalog { addons })
+    } else {
+        Err(DownloadError::CatalogFailed)
+    }
+}
+
+/// Returns `true` if a catalog fetched at `fetched_at` is older than the
+/// latest published catalog. The catalog is republished daily at 00:40 UTC.
+pub fn is_stale(fetched_at: &DateTime<Utc>) -> bool {
+    let now = Utc::now();
+    let refresh_time = NaiveTime::from_hms(0, 40, 0);
+
+    (fetched_at.date() < now.date() && now.time() > refresh_time)
+        || now.signed_duration_since(*fetched_at) > chrono::Duration::days(1)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Source {
+    #[serde(alias = "curse")]
+    Curse,
+    #[serde(alias = "tukui")]
+    Tukui,
+    #[serde(alias = "wowi")]
+    WowI,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Source::Curse => "Curse",
+            Source::Tukui => "Tukui",
+            Source::WowI => "WowInterface",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Where a `CatalogAddon` was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CatalogOrigin {
+    Official,
+    Custom(String),
+}
+
+impl Default for CatalogOrigin {
+    fn default() -> Self {
+        CatalogOrigin::Official
+    }
+}
+
+/// A user defined catalog. It must use the same format as the official catalogs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CustomSource {
+    pub name: String,
+    pub location: CustomSourceLocation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CustomSourceLocation {
+    Url(String),
+    File(PathBuf),
+}
+
+impl std::str::FromStr for CustomSourceLocation {
+    type Err = std::convert::Infallible;
+
+    /// Anything starting with `http://` or `https://` is treated as an url,
+    /// everything else as a path to a local file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(CustomSourceLocation::Url(s.to_owned()))
+        } else {
+            Ok(CustomSourceLocation::File(PathBuf::from(s)))
+        }
+    }
+}
+
+impl std::fmt::Display for CustomSourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CustomSourceLocation::Url(url) => write!(f, "{}", url),
+            CustomSourceLocation::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[serde(transparent)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub addons: Vec<CatalogAddon>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub struct GameVersion {
+    #[serde(with = "null_to_default")]
+    pub game_version: String,
+    pub flavor: Flavor,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogAddon {
+    #[serde(with = "null_to_default")]
+    pub id: i32,
+    #[serde(with = "null_to_default")]
+    pub website_url: String,
+    #[serde(with = "date_parser")]
+    pub date_released: Option<DateTime<Utc>>,
+    #[serde(with = "null_to_default")]
+    pub name: String,
+    #[serde(with = "null_to_default")]
+    pub categories: Vec<String>,
+    #[serde(with = "null_to_default")]
+    pub summary: String,
+    #[serde(default, with = "null_to_default")]
+    pub author: String,
+    #[serde(with = "null_to_default")]
+    pub number_of_downloads: u64,
+    pub source: Source,
+    #[serde(with = "null_to_default")]
+    #[deprecated(since = "0.4.4", note = "Please use game_versions instead")]
+    pub flavors: Vec<Flavor>,
+    #[serde(with = "null_to_default")]
+    pub game_versions: Vec<GameVersion>,
+    #[serde(default)]
+    pub origin: CatalogOrigin,
+}
+
+mod null_to_default {
+    use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Default + Deserialize<'de>,
+    {
+        let opt = Option::deserialize(deserializer)?;
+        Ok(opt.unwrap_or_default())
+    }
+}
+
+mod date_parser {
+    use chrono::prelude::*;
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    /// Serializes to rfc3339 so the date can be parsed back by `deserialize`.
+    /// A missing date is written as an empty string.
+    pub(crate) fn serialize<S>(
+        date: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => serializer.serialize_str(&date.to_rfc3339()),
+            None => serializer.serialize_str(""),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+
+        // Curse format
+        let date = DateTime::parse_from_rfc3339(&s)
+            .map(|d| d.with_timezone(&Utc))
+            .ok();
+
+        if date.is_some() {
+            return Ok(date);
+        }
+
+        // Tukui format
+        let date = NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %T")
+            .map(|d| Utc.from_utc_datetime(&d))
+            .ok();
+
+        if date.is_some() {
+            return Ok(date);
+        }
+
+        // Handles Elvui and Tukui addons which runs in a format without HH:mm:ss.
+        let s_modified = format!("{} 00:00:00", &s);
+        let date = NaiveDateTime::parse_from_str(&s_modified, "%Y-%m-%d %T")
+            .map(|d| Utc.from_utc_datetime(&d))
+            .ok();
+
+        if date.is_some() {
+            return Ok(date);
+        }
+
+        // Handles WowI.
+        if let Ok(ts) = &s.parse::<i64>() {
+            let date = Utc.timestamp(ts / 1000, 0);
+            return Ok(Some(date));
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_download() {
+        async_std::task::block_on(async {
+            let catalog = get_catalog(vec![]).await;
+
+            if let Err(e) = catalog {
+                panic!("{}", e);
+            }
+        });
+    }
+
+    #[test]
+    fn test_null_fields() {
+        let tests = [
+            r"[]",
+            r#"[{"id": null,"websiteUrl": null,"dateReleased":"2020-11-20T02:29:43.46Z","name": null,"summary": null,"numberOfDownloads": null,"categories": null,"flavors": null,"gameVersions": null,"source":"curse"}]"#,
+        ];
+
+        for test in tests.iter() {
+            serde_json::from_str::<Vec<CatalogAddon>>(test).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_custom_source_location() {
+        assert_eq!(
+            "https://example.com/catalog.json"
+                .parse::<CustomSourceLocation>()
+                .unwrap(),
+            CustomSourceLocation::Url("https://example.com/catalog.json".to_owned())
+        );
+        assert_eq!(
+            " /home/user/catalog.json "
+                .parse::<CustomSourceLocation>()
+                .unwrap(),
+            CustomSourceLocation::File(PathBuf::from("/home/user/catalog.json"))
+        );
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let json = r#"[{"id": 1,"websiteUrl": "","dateReleased":"2020-11-20T02:29:43.46Z","name": "Test","summary": null,"numberOfDownloads": 5,"categories": null,"flavors": null,"gameVersions": [{"gameVersion": "9.0.2","flavor": "wow_retail"}],"source":"curse"},{"id": 2,"websiteUrl": "","dateReleased":"","name": "Test","summary": null,"numberOfDownloads": null,"categories": null,"flavors": null,"gameVersions": null,"source":"wowi"}]"#;
+
+        let catalog = serde_json::from_str::<Catalog>(json).unwrap();
+        let yaml = serde_yaml::to_string(&catalog).unwrap();
+        let roundtrip = serde_yaml::from_str::<Catalog>(&yaml).unwrap();
+
+        assert_eq!(roundtrip.addons.len(), 2);
+        assert_eq!(
+            roundtrip.addons[0].date_released,
+            catalog.addons[0].date_released
+        );
+        assert_eq!(roundtrip.addons[1].date_released, None);
+        assert_eq!(roundtrip.addons[1].source, Source::WowI);
+    }
+}
@@ -0,0 +1,434 @@
+use super::{CatalogAddon, CatalogOrigin, Source};
+
+use chrono::{DateTime, Utc};
+
+// How much a match in each field counts towards the score.
+const NAME_WEIGHT: f64 = 3.0;
+const CATEGORY_WEIGHT: f64 = 1.5;
+const AUTHOR_WEIGHT: f64 = 1.5;
+const SUMMARY_WEIGHT: f64 = 1.0;
+
+// How much popularity and recency can boost the match score.
+const DOWNLOADS_WEIGHT: f64 = 0.25;
+const RECENCY_WEIGHT: f64 = 0.15;
+
+/// A parsed search query.
+///
+/// Words are matched against the name, summary, categories and author of each
+/// addon. `source:<source>`, `category:<category>` and `author:<author>` can be
+/// used to filter the results. Values with spaces can be wrapped in quotes,
+/// eg. `category:"bags & inventory"`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<String>,
+    pub sources: Vec<String>,
+    pub categories: Vec<String>,
+    pub authors: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut search_query = SearchQuery::default();
+
+        for token in tokenize(query) {
+            let (field, value) = match token.find(':') {
+                Some(idx) => (&token[..idx], token[idx + 1..].trim_matches('"')),
+                None => ("", token.as_str()),
+            };
+
+            if value.is_empty() {
+                continue;
+            }
+
+            let value = value.to_lowercase();
+
+            match field {
+                "source" => search_query.sources.push(value),
+                "category" => search_query.categories.push(value),
+                "author" => search_query.authors.push(value),
+                _ => search_query
+                    .terms
+                    .extend(words(&token.to_lowercase()).map(str::to_owned)),
+            }
+        }
+
+        search_query
+    }
+
+    /// Returns `true` if the query has no words to match on, only filters.
+    pub fn has_terms(&self) -> bool {
+        !self.terms.is_empty()
+    }
+}
+
+/// A search result, pointing to the addon at `index` in the indexed addons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchResult {
+    pub index: usize,
+    pub score: f64,
+}
+
+/// Lowercased and tokenized fields of every addon in a catalog.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    entries: Vec<IndexEntry>,
+    max_downloads: u64,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    name: String,
+    name_words: Vec<String>,
+    summary_words: Vec<String>,
+    categories: Vec<String>,
+    category_words: Vec<String>,
+    author: String,
+    author_words: Vec<String>,
+    source: Source,
+    origin: Option<String>,
+    number_of_downloads: u64,
+    date_released: Option<DateTime<Utc>>,
+}
+
+impl SearchIndex {
+    pub fn new(addons: &[CatalogAddon]) -> Self {
+        let entries: Vec<_> = addons.iter().map(IndexEntry::from).collect();
+        let max_downloads = entries
+            .iter()
+            .map(|e| e.number_of_downloads)
+            .max()
+            .unwrap_or_default();
+
+        SearchIndex {
+            entries,
+            max_downloads,
+        }
+    }
+
+    /// Returns all addons matching `query`, best match first.
+    ///
+    /// Every term has to match at least one field. Results are ranked by
+    /// how well they match, weighted by number of downloads and how recently
+    /// the addon was released.
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        let now = Utc::now();
+
+        let mut results: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.matches_filters(query))
+            .filter_map(|(index, entry)| {
+                let match_score = if query.has_terms() {
+                    let mut total = 0.0;
+
+                    for term in query.terms.iter() {
+                        total += entry.term_score(term)?;
+                    }
+
+                    // Bonus if the whole query matches the name.
+                    if entry.name == query.terms.join(" ") {
+                        total += NAME_WEIGHT;
+                    }
+
+                    total
+                } else {
+                    1.0
+                };
+
+                let score = match_score
+                    * (1.0
+                        + DOWNLOADS_WEIGHT * entry.popularity(self.max_downloads)
+                        + RECENCY_WEIGHT * entry.recency(now));
+
+                Some(SearchResult { index, score })
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        results
+    }
+}
+
+impl IndexEntry {
+    fn matches_filters(&self, query: &SearchQuery) -> bool {
+        query.sources.iter().all(|s| self.matches_source(s))
+            && query
+                .categories
+                .iter()
+                .all(|c| self.categories.iter().any(|category| category.contains(c)))
+            && query.authors.iter().all(|a| self.author.contains(a))
+    }
+
+    fn matches_source(&self, source: &str) -> bool {
+        let aliases: &[&str] = match self.source {
+            Source::Curse => &["curse", "curseforge"],
+            Source::Tukui => &["tukui"],
+            Source::WowI => &["wowi", "wowinterface"],
+        };
+
+        aliases.contains(&source) || self.origin.as_deref() == Some(source)
+    }
+
+    /// Returns the best weighted score of `term` across all fields, or `None`
+    /// if it doesn't match any field.
+    fn term_score(&self, term: &str) -> Option<f64> {
+        // Each field with its weight, and whether it's the name.
+        let fields = [
+            (&self.name_words, NAME_WEIGHT, true),
+            (&self.category_words, CATEGORY_WEIGHT, false),
+            (&self.author_words, AUTHOR_WEIGHT, false),
+            (&self.summary_words, SUMMARY_WEIGHT, false),
+        ];
+
+        let mut best: Option<f64> = None;
+
+        for (words, weight, is_name) in fields.iter() {
+            // Terms can be part of a longer name, eg. "bag" in "adibags".
+            let quality = if *is_name && self.name.contains(term) {
+                Some(words_score(words, term).unwrap_or(0.7))
+            } else {
+                words_score(words, term)
+            };
+
+            if let Some(quality) = quality {
+                let score = quality * weight;
+                best = Some(best.map_or(score, |b: f64| b.max(score)));
+            }
+        }
+
+        best
+    }
+
+    /// Number of downloads, on a log scale from 0 to 1.
+    fn popularity(&self, max_downloads: u64) -> f64 {
+        if max_downloads == 0 {
+            return 0.0;
+        }
+
+        (1.0 + self.number_of_downloads as f64).ln() / (1.0 + max_downloads as f64).ln()
+    }
+
+    /// 1 if released today, halving for every year since.
+    fn recency(&self, now: DateTime<Utc>) -> f64 {
+        match self.date_released {
+            Some(date) => {
+                let days = now.signed_duration_since(date).num_days().max(0) as f64;
+                0.5_f64.powf(days / 365.0)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl From<&CatalogAddon> for IndexEntry {
+    fn from(addon: &CatalogAddon) -> Self {
+        let name = addon.name.to_lowercase();
+        let summary = addon.summary.to_lowercase();
+        let author = addon.author.to_lowercase();
+        let categories: Vec<_> = addon.categories.iter().map(|c| c.to_lowercase()).collect();
+
+        IndexEntry {
+            name_words: words(&name).map(str::to_owned).collect(),
+            summary_words: words(&summary).map(str::to_owned).collect(),
+            category_words: categories
+                .iter()
+                .flat_map(|c| words(c))
+                .map(str::to_owned)
+                .collect(),
+            author_words: words(&author).map(str::to_owned).collect(),
+            name,
+            categories,
+            author,
+            source: addon.source,
+            origin: match &addon.origin {
+                CatalogOrigin::Custom(name) => Some(name.to_lowercase()),
+                CatalogOrigin::Official => None,
+            },
+            number_of_downloads: addon.number_of_downloads,
+            date_released: addon.date_released,
+        }
+    }
+}
+
+/// Splits a query into whitespace separated tokens, keeping quoted
+/// values together.
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+}
+
+/// Returns how well `term` matches the best of `words`, from 0 to 1.
+fn words_score(words: &[String], term: &str) -> Option<f64> {
+    words
+        .iter()
+        .filter_map(|word| word_score(word, term))
+        .fold(None, |best, score| {
+            Some(best.map_or(score, |b: f64| b.max(score)))
+        })
+}
+
+fn word_score(word: &str, term: &str) -> Option<f64> {
+    if word == term {
+        return Some(1.0);
+    }
+
+    if word.starts_with(term) {
+        return Some(0.9);
+    }
+
+    if term.len() >= 3 && word.contains(term) {
+        return Some(0.7);
+    }
+
+    // Allow typos in longer terms.
+    let max_distance = match term.chars().count() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    let word_len = word.chars().count();
+    let term_len = term.chars().count();
+    if (word_len as isize - term_len as isize).abs() > max_distance as isize {
+        return None;
+    }
+
+    match edit_distance(word, term) {
+        d if d <= max_distance => Some(0.6 - 0.1 * d as f64),
+        _ => None,
+    }
+}
+
+/// Optimal string alignment distance, ie. Levenshtein distance where swapping
+/// two adjacent characters counts as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        rows[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addon(
+        id: i32,
+        name: &str,
+        summary: &str,
+        categories: &[&str],
+        downloads: u64,
+    ) -> CatalogAddon {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "websiteUrl": "",
+            "dateReleased": "",
+            "name": name,
+            "categories": categories,
+            "summary": summary,
+            "numberOfDownloads": downloads,
+            "flavors": null,
+            "gameVersions": null,
+            "source": "curse",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = SearchQuery::parse(r#"bag source:WowI category:"bags & inventory""#);
+
+        assert_eq!(query.terms, vec!["bag".to_owned()]);
+        assert_eq!(query.sources, vec!["wowi".to_owned()]);
+        assert_eq!(query.categories, vec!["bags & inventory".to_owned()]);
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let addons = vec![
+            addon(
+                1,
+                "Bagnon",
+                "Single window bags",
+                &["Bags & Inventory"],
+                1_000,
+            ),
+            addon(2, "AdiBags", "Smart bags", &["Bags & Inventory"], 100_000),
+            addon(3, "Details", "Damage meter", &["Combat"], 1_000_000),
+        ];
+        let index = SearchIndex::new(&addons);
+
+        // Exact name match ranks first, despite fewer downloads.
+        let results = index.search(&SearchQuery::parse("bagnon"));
+        assert_eq!(results[0].index, 0);
+
+        // Typos are tolerated.
+        let results = index.search(&SearchQuery::parse("detials"));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].index, 2);
+
+        // Filters without terms rank by downloads.
+        let results = index.search(&SearchQuery::parse("category:bags"));
+        assert_eq!(
+            results.iter().map(|r| r.index).collect::<Vec<_>>(),
+            vec![1, 0]
+        );
+
+        assert!(index.search(&SearchQuery::parse("source:tukui")).is_empty());
+    }
+}
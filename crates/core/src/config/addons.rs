@@ -2,7 +2,7 @@ use super::Flavor;
 use crate::repository::ReleaseChannel;
 use de::de_ignored;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Struct for addons specific settings.
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
@@ -12,6 +12,15 @@ pub struct Addons {
 
     #[serde(default)]
     pub release_channels: HashMap<Flavor, HashMap<String, ReleaseChannel>>,
+
+    /// Named sets of addon folders per flavor.
+    #[serde(default)]
+    pub profiles: HashMap<Flavor, BTreeMap<String, Vec<String>>>,
+
+    /// Profile switched to per flavor. Without one, all addons are in
+    /// `AddOns`.
+    #[serde(default)]
+    pub active_profile: HashMap<Flavor, String>,
 }
 
 impl Default for Addons {
@@ -19,6 +28,8 @@ impl Default for Addons {
         Addons {
             ignored: HashMap::new(),
             release_channels: HashMap::new(),
+            profiles: HashMap::new(),
+            active_profile: HashMap::new(),
         }
     }
 }
@@ -0,0 +1,507 @@
+//! Get and set config values by key, eg. `wow.directory`, so the config can be
+//! changed without editing `ajour.yml` by hand.
+
+use super::{BackupCompression, BackupFormat, Config, Flavor, SelfUpdateChannel};
+use crate::error::ConfigError;
+use crate::repository::ReleaseChannel;
+use crate::utility::wow_path_resolution;
+
+use std::path::{Path, PathBuf};
+
+/// Keys which hold a single value. Per addon settings use
+/// `addons.<flavor>.<folder>.ignored` and `addons.<flavor>.<folder>.channel`.
+pub const KEYS: [&str; 18] = [
+    "wow.directory",
+    "wow.flavor",
+    "backup_directory",
+    "backup_addons",
+    "backup_wtf",
+    "backup_format",
+    "backup_retention.keep_last",
+    "backup_retention.keep_daily",
+    "backup_retention.keep_weekly",
+    "backup_retention.max_total_size",
+    "backup_filter.include",
+    "backup_filter.exclude",
+    "backup_compression",
+    "addon_snapshots",
+    "hide_ignored_addons",
+    "theme",
+    "scale",
+    "self_update_channel",
+];
+
+/// A per addon setting.
+enum AddonKey<'a> {
+    Ignored { flavor: Flavor, folder: &'a str },
+    Channel { flavor: Flavor, folder: &'a str },
+}
+
+impl Config {
+    /// Returns the value of `key`, or `None` if it isn't set.
+    pub fn get_key(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        if let Some(addon_key) = parse_addon_key(key)? {
+            return Ok(match addon_key {
+                AddonKey::Ignored { flavor, folder } => Some(
+                    self.addons
+                        .ignored
+                        .get(&flavor)
+                        .map(|ids| ids.iter().any(|i| i == folder))
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+                AddonKey::Channel { flavor, folder } => self
+                    .addons
+                    .release_channels
+                    .get(&flavor)
+                    .and_then(|channels| channels.get(folder))
+                    .map(|c| release_channel_to_str(*c).to_owned()),
+            });
+        }
+
+        let value = match key {
+            "wow.directory" => self.wow.directory.as_deref().map(path_to_string),
+            "wow.flavor" => Some(self.wow.flavor.as_str().to_owned()),
+            "backup_directory" => self.backup_directory.as_deref().map(path_to_string),
+            "backup_addons" => Some(self.backup_addons.to_string()),
+            "backup_wtf" => Some(self.backup_wtf.to_string()),
+            "backup_format" => Some(
+                match self.backup_format {
+                    BackupFormat::Zip => "zip",
+                    BackupFormat::Incremental => "incremental",
+                }
+                .to_owned(),
+            ),
+            "backup_retention.keep_last" => self.backup_retention.keep_last.map(|n| n.to_string()),
+            "backup_retention.keep_daily" => {
+                self.backup_retention.keep_daily.map(|n| n.to_string())
+            }
+            "backup_retention.keep_weekly" => {
+                self.backup_retention.keep_weekly.map(|n| n.to_string())
+            }
+            "backup_retention.max_total_size" => {
+                self.backup_retention.max_total_size.map(|n| n.to_string())
+            }
+            "backup_filter.include" => patterns_to_string(&self.backup_filter.include),
+            "backup_filter.exclude" => patterns_to_string(&self.backup_filter.exclude),
+            "backup_compression" => Some(
+                match self.backup_compression {
+                    BackupCompression::Stored => "stored",
+                    BackupCompression::Deflated => "deflated",
+                    BackupCompression::Bzip2 => "bzip2",
+                }
+                .to_owned(),
+            ),
+            "addon_snapshots" => self.addon_snapshots.map(|n| n.to_string()),
+            "hide_ignored_addons" => Some(self.hide_ignored_addons.to_string()),
+            "theme" => self.theme.clone(),
+            "scale" => self.scale.map(|s| s.to_string()),
+            "self_update_channel" => Some(
+                match self.self_update_channel {
+                    SelfUpdateChannel::Stable => "stable",
+                    SelfUpdateChannel::Beta => "beta",
+                }
+                .to_owned(),
+            ),
+            _ => return Err(unknown_key(key)),
+        };
+
+        Ok(value)
+    }
+
+    /// Validates `value` and sets it for `key`.
+    pub fn set_key(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        if let Some(addon_key) = parse_addon_key(key)? {
+            match addon_key {
+                AddonKey::Ignored { flavor, folder } => {
+                    let ignored = self.addons.ignored.entry(flavor).or_default();
+                    ignored.retain(|i| i != folder);
+
+                    if parse_bool(key, value)? {
+                        ignored.push(folder.to_owned());
+                    }
+                }
+                AddonKey::Channel { flavor, folder } => {
+                    let channel = match value {
+                        "stable" => ReleaseChannel::Stable,
+                        "beta" => ReleaseChannel::Beta,
+                        "alpha" => ReleaseChannel::Alpha,
+                        _ => {
+                            return Err(invalid_value(key, value, "expected stable, beta or alpha"))
+                        }
+                    };
+
+                    self.addons
+                        .release_channels
+                        .entry(flavor)
+                        .or_default()
+                        .insert(folder.to_owned(), channel);
+                }
+            }
+
+            return Ok(());
+        }
+
+        match key {
+            "wow.directory" => self.wow.directory = Some(parse_wow_directory(key, value)?),
+            "wow.flavor" => {
+                self.wow.flavor = str_to_flavor(value).ok_or_else(|| {
+                    invalid_value(
+                        key,
+                        value,
+                        "expected retail, ptr, beta, classic or classic_ptr",
+                    )
+                })?
+            }
+            "backup_directory" => self.backup_directory = Some(parse_directory(key, value)?),
+            "backup_addons" => self.backup_addons = parse_bool(key, value)?,
+            "backup_wtf" => self.backup_wtf = parse_bool(key, value)?,
+            "backup_format" => {
+                self.backup_format = match value {
+                    "zip" => BackupFormat::Zip,
+                    "incremental" => BackupFormat::Incremental,
+                    _ => return Err(invalid_value(key, value, "expected zip or incremental")),
+                }
+            }
+            "backup_retention.keep_last" => {
+                self.backup_retention.keep_last = Some(parse_count(key, value)?)
+            }
+            "backup_retention.keep_daily" => {
+                self.backup_retention.keep_daily = Some(parse_count(key, value)?)
+            }
+            "backup_retention.keep_weekly" => {
+                self.backup_retention.keep_weekly = Some(parse_count(key, value)?)
+            }
+            "backup_retention.max_total_size" => {
+                self.backup_retention.max_total_size = Some(parse_count(key, value)?)
+            }
+            "backup_filter.include" => self.backup_filter.include = parse_patterns(key, value)?,
+            "backup_filter.exclude" => self.backup_filter.exclude = parse_patterns(key, value)?,
+            "backup_compression" => {
+                self.backup_compression = match value {
+                    "stored" => BackupCompression::Stored,
+                    "deflated" => BackupCompression::Deflated,
+                    "bzip2" => BackupCompression::Bzip2,
+                    _ => {
+                        return Err(invalid_value(
+                            key,
+                            value,
+                            "expected stored, deflated or bzip2",
+                        ))
+                    }
+                }
+            }
+            "addon_snapshots" => {
+                self.addon_snapshots = Some(value.parse().map_err(|_| {
+                    invalid_value(key, value, "expected a whole number, 0 turns snapshots off")
+                })?)
+            }
+            "hide_ignored_addons" => self.hide_ignored_addons = parse_bool(key, value)?,
+            "theme" => {
+                if value.trim().is_empty() {
+                    return Err(invalid_value(key, value, "expected a theme name"));
+                }
+
+                self.theme = Some(value.to_owned());
+            }
+            "scale" => {
+                let scale = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| (0.5..=2.0).contains(s))
+                    .ok_or_else(|| {
+                        invalid_value(key, value, "expected a number from 0.5 to 2.0")
+                    })?;
+
+                // Same steps as the scale buttons in Settings
+                self.scale = Some((scale * 10.0).round() / 10.0);
+            }
+            "self_update_channel" => {
+                self.self_update_channel = match value {
+                    "stable" => SelfUpdateChannel::Stable,
+                    "beta" => SelfUpdateChannel::Beta,
+                    _ => return Err(invalid_value(key, value, "expected stable or beta")),
+                }
+            }
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
+    /// Resets `key` to its default value.
+    pub fn unset_key(&mut self, key: &str) -> Result<(), ConfigError> {
+        if let Some(addon_key) = parse_addon_key(key)? {
+            match addon_key {
+                AddonKey::Ignored { flavor, folder } => {
+                    if let Some(ignored) = self.addons.ignored.get_mut(&flavor) {
+                        ignored.retain(|i| i != folder);
+                    }
+                }
+                AddonKey::Channel { flavor, folder } => {
+                    if let Some(channels) = self.addons.release_channels.get_mut(&flavor) {
+                        channels.remove(folder);
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        let default = Config::default();
+
+        match key {
+            "wow.directory" => self.wow.directory = None,
+            "wow.flavor" => self.wow.flavor = default.wow.flavor,
+            "backup_directory" => self.backup_directory = None,
+            "backup_addons" => self.backup_addons = default.backup_addons,
+            "backup_wtf" => self.backup_wtf = default.backup_wtf,
+            "backup_format" => self.backup_format = default.backup_format,
+            "backup_retention.keep_last" => self.backup_retention.keep_last = None,
+            "backup_retention.keep_daily" => self.backup_retention.keep_daily = None,
+            "backup_retention.keep_weekly" => self.backup_retention.keep_weekly = None,
+            "backup_retention.max_total_size" => self.backup_retention.max_total_size = None,
+            "backup_filter.include" => self.backup_filter.include.clear(),
+            "backup_filter.exclude" => self.backup_filter.exclude.clear(),
+            "backup_compression" => self.backup_compression = default.backup_compression,
+            "addon_snapshots" => self.addon_snapshots = None,
+            "hide_ignored_addons" => self.hide_ignored_addons = default.hide_ignored_addons,
+            "theme" => self.theme = None,
+            "scale" => self.scale = None,
+            "self_update_channel" => self.self_update_channel = default.self_update_channel,
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
+    /// Returns all keys which have a value, including per addon settings.
+    pub fn list_keys(&self) -> Vec<(String, String)> {
+        let mut values: Vec<_> = KEYS
+            .iter()
+            .filter_map(|key| {
+                self.get_key(key)
+                    .ok()
+                    .flatten()
+                    .map(|value| (key.to_string(), value))
+            })
+            .collect();
+
+        for flavor in Flavor::ALL.iter() {
+            if let Some(ignored) = self.addons.ignored.get(flavor) {
+                let mut ignored = ignored.clone();
+                ignored.sort();
+
+                values.extend(ignored.into_iter().map(|folder| {
+                    (
+                        format!("addons.{}.{}.ignored", flavor.as_str(), folder),
+                        true.to_string(),
+                    )
+                }));
+            }
+
+            if let Some(channels) = self.addons.release_channels.get(flavor) {
+                let mut channels: Vec<_> = channels.iter().collect();
+                channels.sort();
+
+                values.extend(channels.into_iter().map(|(folder, channel)| {
+                    (
+                        format!("addons.{}.{}.channel", flavor.as_str(), folder),
+                        release_channel_to_str(*channel).to_owned(),
+                    )
+                }));
+            }
+        }
+
+        values
+    }
+}
+
+/// Parses `addons.<flavor>.<folder>.<setting>`. Returns `None` for keys which
+/// aren't per addon settings.
+fn parse_addon_key(key: &str) -> Result<Option<AddonKey>, ConfigError> {
+    let rest = match key.strip_prefix("addons.") {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+
+    let (flavor, rest) = rest.split_at(rest.find('.').ok_or_else(|| unknown_key(key))?);
+    let flavor = str_to_flavor(flavor).ok_or_else(|| unknown_key(key))?;
+
+    // Folder names can contain dots, so the setting is split off the end
+    let (folder, setting) =
+        rest[1..].split_at(rest[1..].rfind('.').ok_or_else(|| unknown_key(key))?);
+    let setting = &setting[1..];
+
+    if folder.is_empty() {
+        return Err(unknown_key(key));
+    }
+
+    match setting {
+        "ignored" => Ok(Some(AddonKey::Ignored { flavor, folder })),
+        "channel" => Ok(Some(AddonKey::Channel { flavor, folder })),
+        _ => Err(unknown_key(key)),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid_value(key, value, "expected true or false")),
+    }
+}
+
+/// Parses a whole number of at least 1.
+fn parse_count<T: std::str::FromStr + PartialOrd + From<u8>>(
+    key: &str,
+    value: &str,
+) -> Result<T, ConfigError> {
+    value
+        .parse::<T>()
+        .ok()
+        .filter(|n| *n >= T::from(1))
+        .ok_or_else(|| invalid_value(key, value, "expected a whole number of at least 1"))
+}
+
+/// Parses a comma separated list of glob patterns.
+fn parse_patterns(key: &str, value: &str) -> Result<Vec<String>, ConfigError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match glob::Pattern::new(p) {
+            Ok(_) => Ok(p.to_owned()),
+            Err(e) => Err(invalid_value(key, value, &format!("{:?} {}", p, e.msg))),
+        })
+        .collect()
+}
+
+fn patterns_to_string(patterns: &[String]) -> Option<String> {
+    if patterns.is_empty() {
+        None
+    } else {
+        Some(patterns.join(","))
+    }
+}
+
+fn parse_directory(key: &str, value: &str) -> Result<PathBuf, ConfigError> {
+    let path = PathBuf::from(value);
+
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(invalid_value(key, value, "directory doesn't exist"))
+    }
+}
+
+/// Resolves the World of Warcraft folder the same way as choosing it in the
+/// GUI, so a path inside it such as `_retail_/Interface/AddOns` also works.
+fn parse_wow_directory(key: &str, value: &str) -> Result<PathBuf, ConfigError> {
+    let path = parse_directory(key, value)?;
+
+    wow_path_resolution(Some(path)).ok_or_else(|| {
+        invalid_value(
+            key,
+            value,
+            "not a World of Warcraft folder, expected it to contain _retail_, _classic_ or _ptr_",
+        )
+    })
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.display().to_string()
+}
+
+fn str_to_flavor(s: &str) -> Option<Flavor> {
+    Flavor::ALL.iter().copied().find(|f| f.as_str() == s)
+}
+
+fn release_channel_to_str(channel: ReleaseChannel) -> &'static str {
+    match channel {
+        ReleaseChannel::Stable => "stable",
+        ReleaseChannel::Beta => "beta",
+        ReleaseChannel::Alpha => "alpha",
+    }
+}
+
+fn unknown_key(key: &str) -> ConfigError {
+    ConfigError::UnknownKey {
+        key: key.to_owned(),
+    }
+}
+
+fn invalid_value(key: &str, value: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        key: key.to_owned(),
+        value: value.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get_keys() {
+        let mut config = Config::default();
+
+        config.set_key("wow.flavor", "classic").unwrap();
+        assert_eq!(config.wow.flavor, Flavor::Classic);
+
+        config.set_key("scale", "1.25").unwrap();
+        assert_eq!(config.get_key("scale").unwrap(), Some("1.3".to_owned()));
+        assert!(config.set_key("scale", "3").is_err());
+
+        config
+            .set_key("addons.retail.DBM-Core.channel", "beta")
+            .unwrap();
+        config
+            .set_key("addons.retail.Foo.Bar.ignored", "true")
+            .unwrap();
+        assert_eq!(
+            config.get_key("addons.retail.DBM-Core.channel").unwrap(),
+            Some("beta".to_owned())
+        );
+        assert_eq!(
+            config.addons.ignored.get(&Flavor::Retail),
+            Some(&vec!["Foo.Bar".to_owned()])
+        );
+
+        config
+            .set_key("backup_filter.exclude", "*.bak, Cache,Logs")
+            .unwrap();
+        assert_eq!(config.backup_filter.exclude, vec!["*.bak", "Cache", "Logs"]);
+        assert!(config.set_key("backup_filter.include", "[abc").is_err());
+
+        assert!(config.get_key("unknown").is_err());
+        assert!(config.set_key("addons.retail.Foo.version", "1").is_err());
+    }
+
+    #[test]
+    fn test_set_wow_directory() {
+        let dir = crate::fs::test_dir("set_wow_directory");
+        let addons_dir = dir.join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(&addons_dir).unwrap();
+
+        let mut config = Config::default();
+
+        config
+            .set_key("wow.directory", addons_dir.to_str().unwrap())
+            .unwrap();
+        assert_eq!(config.wow.directory, Some(dir.clone()));
+
+        config
+            .set_key("wow.directory", dir.to_str().unwrap())
+            .unwrap();
+        assert_eq!(config.wow.directory, Some(dir.clone()));
+
+        let other_dir = dir.join("Other");
+        std::fs::create_dir_all(&other_dir).unwrap();
+        assert!(config
+            .set_key("wow.directory", other_dir.to_str().unwrap())
+            .is_err());
+        assert_eq!(config.wow.directory, Some(dir));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::catalog::CustomSource;
 use crate::error::FilesystemError;
 use glob::MatchOptions;
 use serde::{Deserialize, Serialize};
@@ -5,11 +6,13 @@ use std::fmt::{self, Display, Formatter};
 use std::path::PathBuf;
 
 mod addons;
+mod keys;
 mod wow;
 
 use crate::fs::PersistentData;
 
 pub use crate::config::addons::Addons;
+pub use crate::config::keys::KEYS;
 pub use crate::config::wow::{Flavor, Wow};
 
 /// Config struct.
@@ -38,11 +41,31 @@ pub struct Config {
     #[serde(default)]
     pub backup_wtf: bool,
 
+    #[serde(default)]
+    pub backup_format: BackupFormat,
+
+    #[serde(default)]
+    pub backup_retention: BackupRetention,
+
+    #[serde(default)]
+    pub backup_filter: BackupFilter,
+
+    #[serde(default)]
+    pub backup_compression: BackupCompression,
+
+    /// Number of snapshots to keep per addon. Snapshots are taken before an
+    /// addon is updated. `0` turns them off.
+    pub addon_snapshots: Option<usize>,
+
     #[serde(default)]
     pub hide_ignored_addons: bool,
 
     #[serde(default)]
     pub self_update_channel: SelfUpdateChannel,
+
+    /// User defined catalogs, ordered by priority.
+    #[serde(default)]
+    pub catalog_sources: Vec<CustomSource>,
 }
 
 impl Config {
@@ -96,6 +119,15 @@ impl Config {
         }
     }
 
+    /// Returns a `Option<PathBuf>` to the directory addons are parked in when
+    /// they aren't part of the active profile. It's next to the AddOns
+    /// directory, so parking an addon is a rename.
+    /// This will return `None` if no `wow_directory` is set in the config.
+    pub fn get_parked_addon_directory_for_flavor(&self, flavor: &Flavor) -> Option<PathBuf> {
+        self.get_addon_directory_for_flavor(flavor)
+            .and_then(|dir| dir.parent().map(|dir| dir.join("ParkedAddOns")))
+    }
+
     /// Returns a `Option<PathBuf>` to the WTF directory.
     /// This will return `None` if no `wow_directory` is set in the config.
     pub fn get_wtf_directory_for_flavor(&self, flavor: &Flavor) -> Option<PathBuf> {
@@ -128,6 +160,33 @@ impl Config {
             None => None,
         }
     }
+
+    /// Returns the directory to save a snapshot to before restoring a backup.
+    /// This is the backup directory if set, otherwise `backups` in the config
+    /// directory.
+    pub fn get_snapshot_directory(&self) -> PathBuf {
+        self.backup_directory
+            .clone()
+            .unwrap_or_else(|| crate::fs::config_dir().join("backups"))
+    }
+
+    /// Returns the directory snapshots of addons are saved to before they are
+    /// updated.
+    pub fn get_addon_snapshot_directory(&self) -> PathBuf {
+        crate::fs::config_dir().join("addon_snapshots")
+    }
+
+    /// Returns the directory orphaned SavedVariables are backed up to before
+    /// they are deleted.
+    pub fn get_saved_variables_backup_directory(&self) -> PathBuf {
+        crate::fs::config_dir().join("saved_variables_backups")
+    }
+
+    /// Returns the number of snapshots to keep per addon, which is 3 unless
+    /// set.
+    pub fn addon_snapshots_to_keep(&self) -> usize {
+        self.addon_snapshots.unwrap_or(3)
+    }
 }
 
 impl PersistentData for Config {
@@ -169,6 +228,100 @@ impl Default for ColumnConfig {
     }
 }
 
+/// How backups are saved.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BackupFormat {
+    /// Each backup is a complete zip archive.
+    Zip,
+    /// Each backup is a snapshot which only saves files that changed since
+    /// earlier snapshots.
+    Incremental,
+}
+
+impl Default for BackupFormat {
+    fn default() -> Self {
+        BackupFormat::Zip
+    }
+}
+
+impl Display for BackupFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BackupFormat::Zip => "Zip",
+            BackupFormat::Incremental => "Incremental",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Which archives to keep in the backup directory. An archive is kept if any of
+/// `keep_last`, `keep_daily` or `keep_weekly` keeps it. If none of them are set,
+/// all archives are kept. The newest archive is always kept.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub struct BackupRetention {
+    /// Keep this many of the newest archives.
+    pub keep_last: Option<usize>,
+    /// Keep the newest archive of each day for this many days.
+    pub keep_daily: Option<u32>,
+    /// Keep the newest archive of each week for this many weeks.
+    pub keep_weekly: Option<u32>,
+    /// Delete the oldest archives once all archives together are larger than
+    /// this many megabytes.
+    pub max_total_size: Option<u64>,
+}
+
+impl BackupRetention {
+    /// Returns `true` if any setting is set.
+    pub fn is_set(&self) -> bool {
+        self.keep_last.is_some()
+            || self.keep_daily.is_some()
+            || self.keep_weekly.is_some()
+            || self.max_total_size.is_some()
+    }
+}
+
+/// Which files are backed up. Patterns without a `/` match any file or folder
+/// with that name, eg. `*.bak` or `Cache`. Patterns with a `/` match the path
+/// inside the WoW directory, eg. `_retail_/WTF/Account/*/SavedVariables`.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Default, Clone)]
+pub struct BackupFilter {
+    /// If not empty, only files matching a pattern, or in a folder matching
+    /// one, are backed up.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Files and folders matching a pattern are skipped.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// How files are compressed in .zip archives. The zip crate doesn't let us set
+/// a compression level, so each method uses its default level.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BackupCompression {
+    Stored,
+    Deflated,
+    Bzip2,
+}
+
+impl Default for BackupCompression {
+    fn default() -> Self {
+        BackupCompression::Deflated
+    }
+}
+
+impl Display for BackupCompression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BackupCompression::Stored => "Stored",
+            BackupCompression::Deflated => "Deflated",
+            BackupCompression::Bzip2 => "Bzip2",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SelfUpdateChannel {
     Stable,
@@ -49,6 +49,17 @@ impl Flavor {
         }
     }
 
+    /// Returns the name of the flavor in config keys and on the command line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flavor::Retail => "retail",
+            Flavor::RetailPTR => "ptr",
+            Flavor::RetailBeta => "beta",
+            Flavor::Classic => "classic",
+            Flavor::ClassicPTR => "classic_ptr",
+        }
+    }
+
     /// Returns `Flavor` which self relates to.
     pub fn base_flavor(self) -> Flavor {
         match self {
@@ -9,9 +9,13 @@ pub enum FilesystemError {
     #[error(transparent)]
     SerdeYaml(#[from] serde_yaml::Error),
     #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
     Zip(#[from] zip::result::ZipError),
     #[error(transparent)]
     WalkDir(#[from] walkdir::Error),
+    #[error(transparent)]
+    Pattern(#[from] glob::PatternError),
     #[error("File doesn't exist: {path:?}")]
     FileDoesntExist { path: PathBuf },
     #[cfg(target_os = "macos")]
@@ -21,6 +25,20 @@ pub enum FilesystemError {
     NormalizingPathSlash { path: PathBuf },
     #[error("Could not strip prefix {prefix:?} from {from:?}")]
     StripPrefix { prefix: String, from: String },
+    #[error("Nothing to restore from {path:?}")]
+    NothingToRestore { path: PathBuf },
+    #[error("Not an Ajour backup: {path:?}")]
+    NotABackup { path: PathBuf },
+    #[error("Manifest version {version} is newer than this version of Ajour supports")]
+    UnsupportedManifestVersion { version: u32 },
+    #[error("Lockfile version {version} is newer than this version of Ajour supports")]
+    UnsupportedLockfileVersion { version: u32 },
+    #[error("No profile named {name}")]
+    UnknownProfile { name: String },
+    #[error("No account or character named {name}")]
+    UnknownAccountOrCharacter { name: String },
+    #[error("More than one account has a character named {name}")]
+    AmbiguousCharacter { name: String },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -28,6 +46,8 @@ pub enum CacheError {
     #[error("No repository information to create cache entry from addon {title}")]
     AddonMissingRepo { title: String },
     #[error(transparent)]
+    Download(#[from] DownloadError),
+    #[error(transparent)]
     Filesystem(#[from] FilesystemError),
 }
 
@@ -143,6 +163,10 @@ pub enum ParseError {
         pos: usize,
         line: String,
     },
+    #[error("Invalid SavedVariables on line {line}: {reason}")]
+    InvalidLua { line: usize, reason: String },
+    #[error("{name} isn't a SavedVariable of {folder} in this scope")]
+    UndeclaredSavedVariable { name: String, folder: String },
     #[error(transparent)]
     StripPrefix(#[from] std::path::StripPrefixError),
     #[error(transparent)]
@@ -152,6 +176,8 @@ pub enum ParseError {
     #[error(transparent)]
     FancyRegex(#[from] fancy_regex::Error),
     #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error(transparent)]
     Download(#[from] DownloadError),
     #[error(transparent)]
     Filesystem(#[from] FilesystemError),
@@ -162,3 +188,15 @@ impl From<std::io::Error> for ParseError {
         ParseError::Filesystem(FilesystemError::IO(e))
     }
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Unknown config key {key}")]
+    UnknownKey { key: String },
+    #[error("Invalid value {value:?} for {key}: {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
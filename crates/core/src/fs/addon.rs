@@ -5,7 +5,7 @@ use crate::{
 };
 use std::collections::HashSet;
 use std::fs::remove_dir_all;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Deletes an Addon and all dependencies from disk.
 pub fn delete_addons(addon_folders: &[AddonFolder]) -> Result<()> {
@@ -19,6 +19,29 @@ pub fn delete_addons(addon_folders: &[AddonFolder]) -> Result<()> {
     Ok(())
 }
 
+/// Returns the names of the addon folders in `dir`, sorted.
+pub fn addon_folders(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut folders = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            if let Some(name) = entry.file_name().to_str() {
+                folders.push(name.to_owned());
+            }
+        }
+    }
+
+    folders.sort();
+
+    Ok(folders)
+}
+
 /// Unzips an `Addon` archive, and once that is done, it moves the content
 /// to the `to_directory`.
 /// At the end it will cleanup and remove the archive.
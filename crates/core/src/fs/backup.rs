@@ -1,13 +1,27 @@
 use super::Result;
 use crate::backup::BackupFolder;
+use crate::config::{BackupCompression, BackupFilter};
 use crate::error::FilesystemError;
 
+use glob::{MatchOptions, Pattern};
 use path_slash::PathExt;
-use std::fs::File;
-use std::io::{BufWriter, Read, Write};
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
-use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// Folder in the backup directory incremental backups are saved to.
+pub(crate) const SNAPSHOT_FOLDER: &str = "ajour_snapshots";
+
+/// Folder in `SNAPSHOT_FOLDER` file contents are saved to, named by their hash.
+const OBJECTS_FOLDER: &str = "objects";
+
+/// Version of the snapshot manifest format.
+const MANIFEST_VERSION: u32 = 1;
 
 /// A trait defining a way to back things up to the fs
 pub trait Backup {
@@ -18,14 +32,23 @@ pub trait Backup {
 pub struct ZipBackup {
     src: Vec<BackupFolder>,
     dest: PathBuf,
+    filter: EntryFilter,
+    compression: CompressionMethod,
 }
 
 impl ZipBackup {
-    pub(crate) fn new(src: Vec<BackupFolder>, dest: impl AsRef<Path>) -> ZipBackup {
-        ZipBackup {
+    pub(crate) fn new(
+        src: Vec<BackupFolder>,
+        dest: impl AsRef<Path>,
+        filter: &BackupFilter,
+        compression: BackupCompression,
+    ) -> Result<ZipBackup> {
+        Ok(ZipBackup {
             src,
             dest: dest.as_ref().to_owned(),
-        }
+            filter: EntryFilter::new(filter)?,
+            compression: compression_method(compression),
+        })
     }
 }
 
@@ -34,48 +57,388 @@ impl Backup for ZipBackup {
         let output = BufWriter::new(File::create(&self.dest)?);
 
         let mut zip_writer = ZipWriter::new(output);
-        let options = FileOptions::default()
-            .compression_method(CompressionMethod::Deflated)
-            .unix_permissions(0o755);
 
         let mut buffer = vec![];
 
         for folder in &self.src {
-            let prefix = &folder.prefix;
-            let path = &folder.path;
+            for (entry, name) in self.filter.walk(folder)? {
+                let options = entry_options(&entry, self.compression)?;
+
+                zip_write(entry.path(), &name, &mut buffer, &mut zip_writer, options)?;
+            }
+        }
 
-            zip_write(path, prefix, &mut buffer, &mut zip_writer, options)?;
+        zip_writer.finish()?;
 
-            for entry in WalkDir::new(path)
-                .into_iter()
-                .filter_map(std::result::Result::ok)
-            {
+        Ok(())
+    }
+}
+
+/// Back up folders as a snapshot of a content-addressed store. The contents of
+/// each file are saved once by their hash and shared by every snapshot that
+/// contains them, and the snapshot itself is a manifest of the files.
+pub struct IncrementalBackup {
+    src: Vec<BackupFolder>,
+    store: PathBuf,
+    manifest: PathBuf,
+    filter: EntryFilter,
+}
+
+impl IncrementalBackup {
+    pub(crate) fn new(
+        src: Vec<BackupFolder>,
+        store: impl AsRef<Path>,
+        manifest: impl AsRef<Path>,
+        filter: &BackupFilter,
+    ) -> Result<IncrementalBackup> {
+        Ok(IncrementalBackup {
+            src,
+            store: store.as_ref().to_owned(),
+            manifest: manifest.as_ref().to_owned(),
+            filter: EntryFilter::new(filter)?,
+        })
+    }
+}
+
+impl Backup for IncrementalBackup {
+    fn backup(&self) -> Result<()> {
+        let mut entries = vec![];
+        let mut buffer = vec![];
+
+        for folder in &self.src {
+            for (entry, name) in self.filter.walk(folder)? {
                 let path = entry.path();
 
-                zip_write(path, prefix, &mut buffer, &mut zip_writer, options)?;
+                if path.is_dir() {
+                    entries.push(SnapshotEntry {
+                        name,
+                        hash: None,
+                        size: 0,
+                    });
+                    continue;
+                }
+
+                File::open(path)?.read_to_end(&mut buffer)?;
+
+                let hash = format!("{:x}", Sha256::digest(&buffer));
+                let object = object_path(&self.store, &hash);
+
+                // Unchanged files are already in the store
+                if !object.exists() {
+                    if let Some(parent) = object.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    let temp = object.with_extension("tmp");
+                    fs::write(&temp, &buffer)?;
+                    fs::rename(&temp, &object)?;
+                }
+
+                entries.push(SnapshotEntry {
+                    name,
+                    hash: Some(hash),
+                    size: buffer.len() as u64,
+                });
+                buffer.clear();
             }
         }
 
-        zip_writer.finish()?;
+        let manifest = SnapshotManifest {
+            version: MANIFEST_VERSION,
+            entries,
+        };
+
+        // The manifest is written last, so a snapshot only exists once all of
+        // its files are in the store.
+        let temp = self.manifest.with_extension("tmp");
+        serde_json::to_writer(BufWriter::new(File::create(&temp)?), &manifest)?;
+        fs::rename(&temp, &self.manifest)?;
 
         Ok(())
     }
 }
 
-/// Write each path to the zip archive
+/// The files in a snapshot. Directories have no hash.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SnapshotManifest {
+    pub version: u32,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SnapshotEntry {
+    /// Name of the entry, with `/` separators
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Reads the manifest of the snapshot at `path`.
+pub(crate) fn read_manifest(path: &Path) -> Result<SnapshotManifest> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Returns the path of the object with `hash` in `store`. Objects are spread
+/// over folders by the first two characters of their hash.
+fn object_path(store: &Path, hash: &str) -> PathBuf {
+    store.join(OBJECTS_FOLDER).join(&hash[..2]).join(hash)
+}
+
+/// Returns all entries in the snapshot at `path`.
+pub(crate) fn snapshot_entries(path: &Path) -> Result<Vec<ZipEntry>> {
+    Ok(read_manifest(path)?
+        .entries
+        .into_iter()
+        .map(|entry| ZipEntry {
+            is_dir: entry.hash.is_none(),
+            name: entry.name,
+            size: entry.size,
+        })
+        .collect())
+}
+
+/// Extracts the entries of the snapshot at `path` for which `filter` returns
+/// `true` into `dest`.
+pub(crate) fn snapshot_extract(
+    path: &Path,
+    dest: &Path,
+    filter: impl Fn(&str) -> bool,
+) -> Result<()> {
+    let store = path.parent().unwrap_or_else(|| Path::new(""));
+
+    for entry in read_manifest(path)?.entries {
+        if !filter(&entry.name) {
+            continue;
+        }
+
+        let out_path = dest.join(sanitized_name(&entry.name));
+
+        match &entry.hash {
+            None => fs::create_dir_all(&out_path)?,
+            Some(hash) => {
+                if let Some(parent) = out_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                fs::copy(object_path(store, hash), &out_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the snapshot at `path` to a zip archive at `dest`.
+pub(crate) fn snapshot_to_zip(
+    path: &Path,
+    dest: &Path,
+    compression: BackupCompression,
+) -> Result<()> {
+    let store = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let output = BufWriter::new(File::create(dest)?);
+
+    let mut zip_writer = ZipWriter::new(output);
+    let options = FileOptions::default().compression_method(compression_method(compression));
+
+    for entry in read_manifest(path)?.entries {
+        match &entry.hash {
+            None => zip_writer.add_directory(&entry.name, options)?,
+            Some(hash) => {
+                zip_writer.start_file(&entry.name, options)?;
+
+                let mut object = File::open(object_path(store, hash))?;
+                std::io::copy(&mut object, &mut zip_writer)?;
+            }
+        }
+    }
+
+    zip_writer.finish()?;
+
+    Ok(())
+}
+
+/// Deletes the objects in `store` which none of the snapshots in `store`
+/// contain.
+pub(crate) fn remove_unused_objects(store: &Path) -> Result<()> {
+    let mut used = HashSet::new();
+
+    for entry in fs::read_dir(store)? {
+        let path = entry?.path();
+
+        if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            used.extend(
+                read_manifest(&path)?
+                    .entries
+                    .into_iter()
+                    .filter_map(|entry| entry.hash),
+            );
+        }
+    }
+
+    let objects = store.join(OBJECTS_FOLDER);
+    if !objects.exists() {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(&objects)
+        .min_depth(2)
+        .into_iter()
+        .filter_map(std::result::Result::ok)
+    {
+        let is_used = entry
+            .file_name()
+            .to_str()
+            .map_or(false, |hash| used.contains(hash));
+
+        if entry.file_type().is_file() && !is_used {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `name` with any components removed which could point outside of the
+/// folder it's joined to.
+fn sanitized_name(name: &str) -> PathBuf {
+    Path::new(name)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// Write each path to the zip archive as `name`
 fn zip_write(
     path: &Path,
-    prefix: &Path,
+    name: &str,
     buffer: &mut Vec<u8>,
     writer: &mut ZipWriter<BufWriter<File>>,
     options: FileOptions,
 ) -> Result<()> {
-    if !path.exists() {
-        return Err(FilesystemError::FileDoesntExist {
-            path: path.to_owned(),
-        });
+    if path.is_dir() {
+        writer.add_directory(name, options)?;
+    } else {
+        writer.start_file(name, options)?;
+
+        let mut file = File::open(path)?;
+        file.read_to_end(buffer)?;
+
+        writer.write_all(buffer)?;
+        buffer.clear();
     }
 
+    Ok(())
+}
+
+/// Returns the zip options for `entry`. On unix, the permissions of the file
+/// are kept.
+fn entry_options(entry: &DirEntry, compression: CompressionMethod) -> Result<FileOptions> {
+    let options = FileOptions::default().compression_method(compression);
+
+    #[cfg(unix)]
+    let options = {
+        use std::os::unix::fs::PermissionsExt;
+
+        options.unix_permissions(entry.metadata()?.permissions().mode())
+    };
+
+    #[cfg(not(unix))]
+    let _ = entry;
+
+    Ok(options)
+}
+
+fn compression_method(compression: BackupCompression) -> CompressionMethod {
+    match compression {
+        BackupCompression::Stored => CompressionMethod::Stored,
+        BackupCompression::Deflated => CompressionMethod::Deflated,
+        BackupCompression::Bzip2 => CompressionMethod::Bzip2,
+    }
+}
+
+/// The include and exclude patterns of a `BackupFilter`, matched against the
+/// name of each entry in a backup.
+struct EntryFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl EntryFilter {
+    fn new(filter: &BackupFilter) -> Result<EntryFilter> {
+        let compile = |patterns: &[String]| -> Result<Vec<Pattern>> {
+            Ok(patterns
+                .iter()
+                .map(|p| Pattern::new(p))
+                .collect::<std::result::Result<Vec<_>, glob::PatternError>>()?)
+        };
+
+        Ok(EntryFilter {
+            include: compile(&filter.include)?,
+            exclude: compile(&filter.exclude)?,
+        })
+    }
+
+    /// Returns the entries in `folder` to back up along with their names.
+    /// Excluded folders aren't walked into.
+    fn walk(&self, folder: &BackupFolder) -> Result<Vec<(DirEntry, String)>> {
+        if !folder.path.exists() {
+            return Err(FilesystemError::FileDoesntExist {
+                path: folder.path.clone(),
+            });
+        }
+
+        let mut entries = vec![];
+
+        let walker = WalkDir::new(&folder.path)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry_name(entry.path(), &folder.prefix)
+                    .map_or(true, |name| !matches_any(&self.exclude, &name))
+            })
+            .filter_map(std::result::Result::ok);
+
+        for entry in walker {
+            let name = entry_name(entry.path(), &folder.prefix)?;
+
+            if self.include.is_empty() || matches_any(&self.include, &name) {
+                entries.push((entry, name));
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Returns `true` if `name`, or a folder it's in, matches one of `patterns`.
+/// Patterns without a `/` are matched against a single file or folder name.
+fn matches_any(patterns: &[Pattern], name: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let components: Vec<_> = name.split('/').collect();
+
+    (1..=components.len()).any(|n| {
+        let path = components[..n].join("/");
+
+        patterns.iter().any(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches_with(&path, options)
+            } else {
+                pattern.matches_with(components[n - 1], options)
+            }
+        })
+    })
+}
+
+/// Returns the name of `path` in a backup, which is `path` without `prefix`
+/// and with `/` separators.
+fn entry_name(path: &Path, prefix: &Path) -> Result<String> {
     // On windows, convers `\` to `/`
     let normalized_path = path
         .to_slash()
@@ -97,17 +460,237 @@ fn zip_write(
         })?
         .trim_start_matches('/');
 
-    if path.is_dir() {
-        writer.add_directory(name, options)?;
-    } else {
-        writer.start_file(name, options)?;
+    Ok(name.to_owned())
+}
 
-        let mut file = File::open(path)?;
-        file.read_to_end(buffer)?;
+/// An entry in a zip archive or snapshot.
+pub(crate) struct ZipEntry {
+    /// Name of the entry, with `/` separators
+    pub name: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
 
-        writer.write_all(buffer)?;
-        buffer.clear();
+/// Returns all entries in the zip archive at `path`.
+pub(crate) fn zip_entries(path: &Path) -> Result<Vec<ZipEntry>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+
+        entries.push(ZipEntry {
+            name: file.name().trim_end_matches('/').to_owned(),
+            size: file.size(),
+            is_dir: file.is_dir(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Extracts the entries of the zip archive at `path` for which `filter` returns
+/// `true` into `dest`.
+pub(crate) fn zip_extract(path: &Path, dest: &Path, filter: impl Fn(&str) -> bool) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+
+        if !filter(file.name().trim_end_matches('/')) {
+            continue;
+        }
+
+        // Sanitized so entries can't be written outside of `dest`
+        #[allow(deprecated)]
+        let out_path = dest.join(file.sanitized_name());
+
+        if file.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut out_file = BufWriter::new(File::create(&out_path)?);
+            std::io::copy(&mut file, &mut out_file)?;
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::test_dir;
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn incremental_backup(wow_dir: &Path, store: &Path, name: &str) -> PathBuf {
+        let manifest = store.join(name);
+        let src = vec![BackupFolder::new(wow_dir.join("_retail_/WTF"), wow_dir)];
+
+        IncrementalBackup::new(src, store, &manifest, &BackupFilter::default())
+            .unwrap()
+            .backup()
+            .unwrap();
+
+        manifest
+    }
+
+    fn object_count(store: &Path) -> usize {
+        WalkDir::new(store.join(OBJECTS_FOLDER))
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .count()
+    }
+
+    #[test]
+    fn test_matches_any() {
+        let patterns = |patterns: &[&str]| -> Vec<Pattern> {
+            patterns.iter().map(|p| Pattern::new(p).unwrap()).collect()
+        };
+
+        let exclude = patterns(&["*.bak", "cache"]);
+        assert!(matches_any(
+            &exclude,
+            "_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua.bak"
+        ));
+        assert!(matches_any(
+            &exclude,
+            "_retail_/Cache/WDB/enUS/creaturecache.wdb"
+        ));
+        assert!(!matches_any(&exclude, "_retail_/WTF/Config.wtf"));
+
+        let include = patterns(&["_retail_/WTF/Account/*/SavedVariables"]);
+        assert!(matches_any(
+            &include,
+            "_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua"
+        ));
+        assert!(!matches_any(
+            &include,
+            "_retail_/WTF/Account/FOO/Realm/Char/SavedVariables/Bagnon.lua"
+        ));
+        assert!(!matches_any(&include, "_retail_/WTF/Account/FOO"));
+    }
+
+    #[test]
+    fn test_incremental_backup_round_trip() {
+        let dir = test_dir("incremental_backup_round_trip");
+        let wow_dir = dir.join("wow");
+        let store = dir.join("store");
+
+        write_file(&wow_dir.join("_retail_/WTF/Config.wtf"), "SET foo 1");
+        write_file(
+            &wow_dir.join("_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua"),
+            "Bagnon = {}",
+        );
+
+        let manifest = incremental_backup(&wow_dir, &store, "1.json");
+
+        let restored = dir.join("restored");
+        snapshot_extract(&manifest, &restored, |_| true).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(restored.join("_retail_/WTF/Config.wtf")).unwrap(),
+            "SET foo 1"
+        );
+        assert_eq!(
+            fs::read_to_string(restored.join("_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua"))
+                .unwrap(),
+            "Bagnon = {}"
+        );
+
+        let zip = dir.join("snapshot.zip");
+        snapshot_to_zip(&manifest, &zip, BackupCompression::Deflated).unwrap();
+
+        let mut names: Vec<_> = zip_entries(&zip)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "_retail_/WTF/Account/FOO/SavedVariables/Bagnon.lua",
+                "_retail_/WTF/Config.wtf",
+            ]
+        );
+
+        let unzipped = dir.join("unzipped");
+        zip_extract(&zip, &unzipped, |_| true).unwrap();
+        assert_eq!(
+            fs::read_to_string(unzipped.join("_retail_/WTF/Config.wtf")).unwrap(),
+            "SET foo 1"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_backup_shares_objects() {
+        let dir = test_dir("incremental_backup_shares_objects");
+        let wow_dir = dir.join("wow");
+        let store = dir.join("store");
+
+        write_file(&wow_dir.join("_retail_/WTF/Config.wtf"), "SET foo 1");
+        write_file(&wow_dir.join("_retail_/WTF/Macros.txt"), "first");
+        let first = incremental_backup(&wow_dir, &store, "1.json");
+
+        write_file(&wow_dir.join("_retail_/WTF/Macros.txt"), "second");
+        let second = incremental_backup(&wow_dir, &store, "2.json");
+
+        let hash = |manifest: &Path, name: &str| -> String {
+            read_manifest(manifest)
+                .unwrap()
+                .entries
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .and_then(|entry| entry.hash)
+                .unwrap()
+        };
+
+        // The unchanged file is stored once and shared by both snapshots
+        assert_eq!(
+            hash(&first, "_retail_/WTF/Config.wtf"),
+            hash(&second, "_retail_/WTF/Config.wtf")
+        );
+        assert_ne!(
+            hash(&first, "_retail_/WTF/Macros.txt"),
+            hash(&second, "_retail_/WTF/Macros.txt")
+        );
+        assert_eq!(object_count(&store), 3);
+
+        // Nothing is removed while both snapshots exist
+        remove_unused_objects(&store).unwrap();
+        assert_eq!(object_count(&store), 3);
+
+        fs::remove_file(&first).unwrap();
+        remove_unused_objects(&store).unwrap();
+
+        // Only the object of the first snapshot's `Macros.txt` is removed
+        assert_eq!(object_count(&store), 2);
+        assert!(!object_path(&store, &format!("{:x}", Sha256::digest(b"first"))).exists());
+
+        let restored = dir.join("restored");
+        snapshot_extract(&second, &restored, |_| true).unwrap();
+        assert_eq!(
+            fs::read_to_string(restored.join("_retail_/WTF/Config.wtf")).unwrap(),
+            "SET foo 1"
+        );
+        assert_eq!(
+            fs::read_to_string(restored.join("_retail_/WTF/Macros.txt")).unwrap(),
+            "second"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -12,7 +12,7 @@ mod save;
 #[cfg(feature = "gui")]
 mod theme;
 
-pub use addon::{delete_addons, install_addon};
+pub use addon::{addon_folders, delete_addons, install_addon};
 pub use save::PersistentData;
 #[cfg(feature = "gui")]
 pub use theme::load_user_themes;
@@ -62,4 +62,15 @@ pub fn config_dir() -> PathBuf {
     CONFIG_DIR.lock().unwrap().clone()
 }
 
+/// Returns an empty directory in the temp directory for the test `name`.
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ajour_test_{}_{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
 type Result<T, E = FilesystemError> = std::result::Result<T, E>;
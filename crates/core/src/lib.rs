@@ -5,10 +5,15 @@ pub mod catalog;
 pub mod config;
 pub mod error;
 pub mod fs;
+pub mod lockfile;
+pub mod manifest;
 pub mod murmur2;
 pub mod network;
 pub mod parse;
+pub mod profile;
 pub mod repository;
+pub mod saved_variables;
 #[cfg(feature = "gui")]
 pub mod theme;
 pub mod utility;
+pub mod wtf;
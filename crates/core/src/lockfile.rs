@@ -0,0 +1,368 @@
+//! Lockfiles which record the exact release each addon of a flavor was
+//! installed from, so the same builds can be installed again.
+
+use crate::addon::Addon;
+use crate::config::Flavor;
+use crate::error::{FilesystemError, ParseError, RepositoryError};
+use crate::fs::config_dir;
+use crate::parse::fingerprint_folders;
+use crate::repository::{
+    ReleaseChannel, RemotePackage, RepositoryKind, RepositoryMetadata, RepositoryPackage,
+};
+
+use async_std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the lockfile format. Lockfiles of a newer version can't be
+/// loaded.
+pub const LOCKFILE_VERSION: u32 = 1;
+
+lazy_static::lazy_static! {
+    /// Serializes updates of the lockfiles, since addons are installed
+    /// concurrently.
+    static ref LOCKFILE_MUTEX: Mutex<()> = Mutex::new(());
+}
+
+/// The releases the addons of a flavor were installed from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Lockfile {
+    pub version: u32,
+    pub flavor: Flavor,
+    pub addons: Vec<LockedAddon>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockedAddon {
+    pub title: String,
+    pub repository: RepositoryKind,
+    /// Id of the addon in the repository. For GitHub and GitLab, this is the
+    /// url of the repository.
+    pub id: String,
+    /// Folders of the addon, primary folder first.
+    pub folders: Vec<String>,
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    /// Version of the release. For GitHub and GitLab, this is the tag.
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<i64>,
+    pub download_url: String,
+    /// Fingerprint of each folder after installing the release.
+    pub fingerprints: BTreeMap<String, u32>,
+}
+
+impl Lockfile {
+    pub fn new(flavor: Flavor) -> Lockfile {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            flavor,
+            addons: vec![],
+        }
+    }
+
+    /// Returns the path of the lockfile of `flavor` in the config folder.
+    /// Will create the lockfile folder if it doesn't exist.
+    pub fn path(flavor: Flavor) -> Result<PathBuf, FilesystemError> {
+        let dir = config_dir().join("lockfiles");
+
+        fs::create_dir_all(&dir)?;
+
+        Ok(dir.join(format!("{}.yml", flavor.folder_name().trim_matches('_'))))
+    }
+
+    /// Returns the locked addon for `addon`, if any. They match if they are
+    /// from the same repository, or have the same primary folder.
+    pub fn get(&self, addon: &Addon) -> Option<&LockedAddon> {
+        self.addons.iter().find(|locked| locked.matches(addon))
+    }
+
+    /// Adds `locked` to the lockfile, replacing the entry of the same addon.
+    pub fn insert(&mut self, locked: LockedAddon) {
+        self.addons.retain(|a| {
+            !((a.repository == locked.repository && a.id == locked.id)
+                || a.folders.first() == locked.folders.first())
+        });
+
+        self.addons.push(locked);
+        self.addons
+            .sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+    }
+
+    /// Removes the locked addon for `addon`, if any, and returns it.
+    pub fn remove(&mut self, addon: &Addon) -> Option<LockedAddon> {
+        let idx = self
+            .addons
+            .iter()
+            .position(|locked| locked.matches(addon))?;
+
+        Some(self.addons.remove(idx))
+    }
+}
+
+impl LockedAddon {
+    /// Locks `addon` to `package`, the release it's installed from.
+    /// Returns `None` if `addon` isn't linked to a repository.
+    pub fn new(
+        addon: &Addon,
+        package: RemotePackage,
+        fingerprints: BTreeMap<String, u32>,
+    ) -> Option<LockedAddon> {
+        let repository = addon.repository_kind()?;
+        let id = addon.repository_id()?.to_owned();
+
+        let mut folders = vec![addon.primary_folder_id.clone()];
+        folders.extend(
+            addon
+                .folders
+                .iter()
+                .map(|f| f.id.clone())
+                .filter(|id| id != &addon.primary_folder_id),
+        );
+
+        Some(LockedAddon {
+            title: addon.title().to_owned(),
+            repository,
+            id,
+            folders,
+            channel: addon.release_channel,
+            version: package.version,
+            file_id: package.file_id,
+            download_url: package.download_url,
+            fingerprints,
+        })
+    }
+
+    /// Returns `true` if `addon` is from the same repository, or has the same
+    /// primary folder.
+    pub fn matches(&self, addon: &Addon) -> bool {
+        (addon.repository_kind() == Some(self.repository)
+            && addon.repository_id() == Some(&self.id))
+            || self.folders.first() == Some(&addon.primary_folder_id)
+    }
+
+    /// Returns `true` if every folder of the addon is in `addon_dir` with the
+    /// locked fingerprint.
+    pub fn is_installed(&self, addon_dir: &Path) -> bool {
+        match fingerprint_folders(addon_dir, &self.folders) {
+            Ok(fingerprints) => self.mismatches(&fingerprints).is_empty(),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the folders whose fingerprint in `fingerprints` differs from
+    /// the locked one.
+    pub fn mismatches(&self, fingerprints: &BTreeMap<String, u32>) -> Vec<&str> {
+        self.fingerprints
+            .iter()
+            .filter(|(folder, hash)| fingerprints.get(*folder) != Some(hash))
+            .map(|(folder, _)| folder.as_str())
+            .collect()
+    }
+
+    /// Returns an addon which downloads exactly the locked release.
+    pub fn to_addon(&self, flavor: Flavor) -> Result<Addon, RepositoryError> {
+        let repo_package =
+            RepositoryPackage::from_kind_and_id(flavor, self.repository, self.id.clone())?;
+
+        let package = RemotePackage {
+            version: self.version.clone(),
+            download_url: self.download_url.clone(),
+            file_id: self.file_id,
+            date_time: None,
+            modules: vec![],
+        };
+
+        let mut remote_packages = HashMap::new();
+        remote_packages.insert(self.channel, package);
+
+        let mut metadata = RepositoryMetadata::empty();
+        metadata.title = Some(self.title.clone());
+        metadata.version = Some(self.version.clone());
+        metadata.file_id = self.file_id;
+        metadata.remote_packages = remote_packages;
+
+        let primary_folder_id = self.folders.first().cloned().unwrap_or_default();
+
+        let mut addon = Addon::empty(&primary_folder_id);
+        addon.set_repository(repo_package.with_metadata(metadata));
+        addon.release_channel = self.channel;
+
+        Ok(addon)
+    }
+}
+
+/// Returns the release `addon` is installed from, if it's still available
+/// from the repository. Curse releases are matched by file id, others by
+/// version.
+pub fn installed_release(addon: &Addon) -> Option<RemotePackage> {
+    let file_id = addon.file_id();
+    let version = addon.version();
+
+    addon
+        .remote_packages()
+        .into_iter()
+        .map(|(_, p)| p)
+        .find(|p| {
+            if addon.repository_kind() == Some(RepositoryKind::Curse) {
+                file_id.is_some() && p.file_id == file_id
+            } else {
+                version.is_some() && Some(p.version.as_str()) == version
+            }
+        })
+}
+
+/// Records the release `addon` was just installed from in the lockfile of
+/// `flavor`, along with the fingerprints of its folders in `addon_dir`.
+pub async fn lock_installed_addon(
+    flavor: Flavor,
+    addon: Addon,
+    addon_dir: PathBuf,
+) -> Result<(), ParseError> {
+    // Same release `download_addon` installs
+    let package = match addon
+        .relevant_release_package()
+        .or_else(|| addon.fallback_release_package())
+    {
+        Some(package) => package,
+        None => return Ok(()),
+    };
+
+    let folders: Vec<_> = addon.folders.iter().map(|f| f.id.clone()).collect();
+    let fingerprints = fingerprint_folders(&addon_dir, &folders)?;
+
+    let locked = match LockedAddon::new(&addon, package, fingerprints) {
+        Some(locked) => locked,
+        None => return Ok(()),
+    };
+
+    let _guard = LOCKFILE_MUTEX.lock().await;
+
+    let path = Lockfile::path(flavor)?;
+    let mut lockfile = if path.exists() {
+        load_lockfile(path.clone()).await?
+    } else {
+        Lockfile::new(flavor)
+    };
+
+    log::debug!("locking {} to {}", &locked.title, &locked.version);
+
+    lockfile.insert(locked);
+    save_lockfile(&lockfile, path).await?;
+
+    Ok(())
+}
+
+/// Removes `addon` from the lockfile of `flavor`, after it was deleted, so it
+/// isn't installed again when syncing.
+pub async fn unlock_addon(flavor: Flavor, addon: Addon) -> Result<(), FilesystemError> {
+    let _guard = LOCKFILE_MUTEX.lock().await;
+
+    let path = Lockfile::path(flavor)?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut lockfile = load_lockfile(path.clone()).await?;
+
+    if let Some(locked) = lockfile.remove(&addon) {
+        log::debug!("unlocking {}", &locked.title);
+
+        save_lockfile(&lockfile, path).await?;
+    }
+
+    Ok(())
+}
+
+/// Saves `lockfile` to `path` as YAML.
+pub async fn save_lockfile(lockfile: &Lockfile, path: PathBuf) -> Result<(), FilesystemError> {
+    fs::write(&path, serde_yaml::to_string(lockfile)?)?;
+
+    log::debug!(
+        "saved lockfile of {} addons to {:?}",
+        lockfile.addons.len(),
+        &path
+    );
+
+    Ok(())
+}
+
+/// Loads the lockfile at `path`.
+pub async fn load_lockfile(path: PathBuf) -> Result<Lockfile, FilesystemError> {
+    let file = fs::File::open(&path)?;
+    let lockfile: Lockfile = serde_yaml::from_reader(file)?;
+
+    if lockfile.version > LOCKFILE_VERSION {
+        return Err(FilesystemError::UnsupportedLockfileVersion {
+            version: lockfile.version,
+        });
+    }
+
+    Ok(lockfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked_addon(folder: &str, id: &str, version: &str) -> LockedAddon {
+        LockedAddon {
+            title: folder.to_owned(),
+            repository: RepositoryKind::Curse,
+            id: id.to_owned(),
+            folders: vec![folder.to_owned()],
+            channel: ReleaseChannel::Stable,
+            version: version.to_owned(),
+            file_id: None,
+            download_url: String::new(),
+            fingerprints: vec![(folder.to_owned(), 1)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_insert_replaces_addon() {
+        let mut lockfile = Lockfile::new(Flavor::Retail);
+
+        lockfile.insert(locked_addon("Details", "61284", "1.0"));
+        lockfile.insert(locked_addon("Bagnon", "1592", "1.0"));
+        lockfile.insert(locked_addon("Details", "61284", "1.1"));
+
+        let addons: Vec<_> = lockfile
+            .addons
+            .iter()
+            .map(|a| (a.title.as_str(), a.version.as_str()))
+            .collect();
+
+        assert_eq!(addons, vec![("Bagnon", "1.0"), ("Details", "1.1")]);
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let locked = locked_addon("Details", "61284", "1.0");
+
+        let same = vec![("Details".to_owned(), 1)].into_iter().collect();
+        let changed = vec![("Details".to_owned(), 2)].into_iter().collect();
+
+        assert!(locked.mismatches(&same).is_empty());
+        assert_eq!(locked.mismatches(&changed), vec!["Details"]);
+        assert_eq!(locked.mismatches(&BTreeMap::new()), vec!["Details"]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut lockfile = Lockfile::new(Flavor::Retail);
+
+        lockfile.insert(locked_addon("Details", "61284", "1.0"));
+        lockfile.insert(locked_addon("Bagnon", "1592", "1.0"));
+
+        // Addons without a repository are matched by their primary folder
+        let removed = lockfile.remove(&Addon::empty("Details"));
+        assert_eq!(removed.map(|a| a.title), Some("Details".to_owned()));
+        assert!(lockfile.remove(&Addon::empty("Details")).is_none());
+
+        assert_eq!(lockfile.addons.len(), 1);
+        assert_eq!(lockfile.addons[0].title, "Bagnon");
+    }
+}
@@ -0,0 +1,239 @@
+//! Manifests of the installed addons of a flavor, so the same addons can be
+//! installed on another machine.
+
+use crate::addon::Addon;
+use crate::config::{Config, Flavor};
+use crate::error::FilesystemError;
+use crate::repository::{ReleaseChannel, RepositoryKind};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the manifest format. Manifests of a newer version can't be
+/// imported.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The addons of a flavor which are linked to a repository.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AddonManifest {
+    pub version: u32,
+    pub flavor: Flavor,
+    pub exported_at: DateTime<Utc>,
+    pub addons: Vec<ManifestAddon>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestAddon {
+    pub title: String,
+    pub repository: RepositoryKind,
+    /// Id of the addon in the repository. For GitHub and GitLab, this is the
+    /// url of the repository.
+    pub id: String,
+    /// Folders of the addon, primary folder first.
+    pub folders: Vec<String>,
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    #[serde(default)]
+    pub ignored: bool,
+    /// Version which was installed when the manifest was exported. Imports
+    /// install the latest version of `channel`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+}
+
+impl AddonManifest {
+    /// Returns the manifest of the installed `addons` of `flavor`, with their
+    /// release channel and ignored state from `config`. Addons which aren't
+    /// linked to a repository are left out, since they can't be installed
+    /// from the manifest.
+    pub fn new(flavor: Flavor, addons: &[Addon], config: &Config) -> AddonManifest {
+        let release_channels = config.addons.release_channels.get(&flavor);
+        let ignored = config.addons.ignored.get(&flavor);
+
+        let mut addons: Vec<_> = addons
+            .iter()
+            .filter_map(|addon| {
+                let repository = addon.repository_kind()?;
+                let id = addon.repository_id()?.to_owned();
+
+                let mut folders = vec![addon.primary_folder_id.clone()];
+                folders.extend(
+                    addon
+                        .folders
+                        .iter()
+                        .map(|f| f.id.clone())
+                        .filter(|id| id != &addon.primary_folder_id),
+                );
+
+                Some(ManifestAddon {
+                    title: addon.title().to_owned(),
+                    repository,
+                    id,
+                    folders,
+                    channel: release_channels
+                        .and_then(|c| c.get(&addon.primary_folder_id))
+                        .copied()
+                        .unwrap_or(addon.release_channel),
+                    ignored: ignored
+                        .map(|i| i.contains(&addon.primary_folder_id))
+                        .unwrap_or_default(),
+                    installed_version: addon.version().map(str::to_owned),
+                })
+            })
+            .collect();
+
+        addons.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+
+        AddonManifest {
+            version: MANIFEST_VERSION,
+            flavor,
+            exported_at: Utc::now(),
+            addons,
+        }
+    }
+
+    /// Returns the addons in the manifest which aren't in `installed`. An
+    /// addon counts as installed if an installed addon is from the same
+    /// repository, or has the same primary folder.
+    pub fn missing(&self, installed: &[Addon]) -> Vec<&ManifestAddon> {
+        self.addons
+            .iter()
+            .filter(|addon| {
+                !installed.iter().any(|a| {
+                    (a.repository_kind() == Some(addon.repository)
+                        && a.repository_id() == Some(&addon.id))
+                        || addon.folders.first() == Some(&a.primary_folder_id)
+                })
+            })
+            .collect()
+    }
+}
+
+impl ManifestAddon {
+    /// Saves the release channel and ignored state of the addon to `config`,
+    /// so they apply once the addon is installed.
+    pub fn apply_to_config(&self, flavor: Flavor, config: &mut Config) {
+        let primary_folder_id = match self.folders.first() {
+            Some(id) => id,
+            None => return,
+        };
+
+        if self.channel != ReleaseChannel::default() {
+            config
+                .addons
+                .release_channels
+                .entry(flavor)
+                .or_default()
+                .insert(primary_folder_id.clone(), self.channel);
+        }
+
+        if self.ignored {
+            let ignored = config.addons.ignored.entry(flavor).or_default();
+
+            if !ignored.contains(primary_folder_id) {
+                ignored.push(primary_folder_id.clone());
+            }
+        }
+    }
+}
+
+/// Saves `manifest` to `path`, as JSON if `path` ends in `.json` and as YAML
+/// otherwise.
+pub async fn save_manifest(manifest: AddonManifest, path: PathBuf) -> Result<(), FilesystemError> {
+    let contents = if is_json(&path) {
+        serde_json::to_string_pretty(&manifest)?
+    } else {
+        serde_yaml::to_string(&manifest)?
+    };
+
+    fs::write(&path, contents)?;
+
+    log::debug!(
+        "saved manifest of {} addons to {:?}",
+        manifest.addons.len(),
+        &path
+    );
+
+    Ok(())
+}
+
+/// Loads the manifest at `path`, as JSON if `path` ends in `.json` and as
+/// YAML otherwise.
+pub async fn load_manifest(path: PathBuf) -> Result<AddonManifest, FilesystemError> {
+    let file = fs::File::open(&path)?;
+
+    let manifest: AddonManifest = if is_json(&path) {
+        serde_json::from_reader(file)?
+    } else {
+        serde_yaml::from_reader(file)?
+    };
+
+    if manifest.version > MANIFEST_VERSION {
+        return Err(FilesystemError::UnsupportedManifestVersion {
+            version: manifest.version,
+        });
+    }
+
+    Ok(manifest)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| e.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::RepositoryPackage;
+
+    #[test]
+    fn test_missing_addons() {
+        let addon = |folder: &str, kind, id: &str| {
+            let mut addon = Addon::empty(folder);
+            addon.set_repository(
+                RepositoryPackage::from_repo_id(Flavor::Retail, kind, id.to_owned()).unwrap(),
+            );
+            addon
+        };
+
+        let manifest_addon = |folder: &str, repository, id: &str| ManifestAddon {
+            title: folder.to_owned(),
+            repository,
+            id: id.to_owned(),
+            folders: vec![folder.to_owned()],
+            channel: ReleaseChannel::Stable,
+            ignored: false,
+            installed_version: None,
+        };
+
+        let manifest = AddonManifest {
+            version: MANIFEST_VERSION,
+            flavor: Flavor::Retail,
+            exported_at: Utc::now(),
+            addons: vec![
+                manifest_addon("Bagnon", RepositoryKind::Curse, "1592"),
+                manifest_addon("Details", RepositoryKind::Curse, "61284"),
+                manifest_addon("ElvUI", RepositoryKind::Tukui, "-2"),
+                manifest_addon("WeakAuras", RepositoryKind::WowI, "24910"),
+            ],
+        };
+
+        let installed = vec![
+            addon("Bagnon", RepositoryKind::Curse, "1592"),
+            // Same folder, installed from another repository
+            addon("WeakAuras", RepositoryKind::Curse, "65387"),
+        ];
+
+        let missing: Vec<_> = manifest
+            .missing(&installed)
+            .into_iter()
+            .map(|a| a.title.as_str())
+            .collect();
+
+        assert_eq!(missing, vec!["Details", "ElvUI"]);
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{
     addon::{Addon, AddonFolder, AddonState},
-    cache::{AddonCache, AddonCacheEntry, FingerprintCache},
+    cache::{update_curse_cache_entries, AddonCache, AddonCacheEntry, FingerprintCache},
     config::Flavor,
     error::{DownloadError, ParseError, RepositoryError},
-    fs::PersistentData,
+    fs::{config_dir, PersistentData},
     murmur2::calculate_hash,
-    repository::{curse, tukui, wowi, RepositoryIdentifiers, RepositoryKind, RepositoryPackage},
+    repository::{
+        curse, tukui, wowi, RepositoryIdentifiers, RepositoryKind, RepositoryMetadata,
+        RepositoryPackage,
+    },
 };
 use async_std::sync::{Arc, Mutex};
 use fancy_regex::Regex;
@@ -13,22 +16,38 @@ use futures::future::join_all;
 use isahc::http::Uri;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
 
-lazy_static::lazy_static! {
-    static ref CACHED_GAME_INFO: Mutex<Option<curse::GameInfo>> = Mutex::new(None);
-}
-
 #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct Fingerprint {
     pub title: String,
     pub hash: Option<u32>,
     pub modified: SystemTime,
+    /// Hash of the path, size and modification time of every file in the
+    /// folder, so a change to any nested file is detected.
+    #[serde(default)]
+    pub signature: Option<u32>,
+    /// Files `hash` was calculated from.
+    #[serde(default)]
+    pub files: Vec<FileFingerprint>,
+    /// Files right after Ajour last installed or updated the folder, to tell
+    /// which files were modified locally since.
+    #[serde(default)]
+    pub installed_files: Vec<FileFingerprint>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct FileFingerprint {
+    /// Path relative to the addon folder, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub hash: u32,
 }
 
 pub struct ParsingPatterns {
@@ -37,6 +56,132 @@ pub struct ParsingPatterns {
     pub file_parsing_regex: HashMap<String, (regex::Regex, Regex)>,
 }
 
+/// Rules deciding which files of an addon folder are fingerprinted, as found
+/// in the Curse game info. Patterns are matched against lowercase paths
+/// relative to the AddOns directory, with Windows separators.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ParsingRules {
+    /// Files which fingerprinting starts from.
+    pub initial_inclusion_pattern: String,
+    /// Files which are fingerprinted, even if nothing includes them.
+    pub extra_include_pattern: String,
+    pub file_parsing_rules: Vec<FileParsingRule>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct FileParsingRule {
+    /// Extension of the files the rule applies to, including the dot.
+    pub file_extension: String,
+    /// Removes comments before looking for included files.
+    pub comment_strip_pattern: String,
+    /// Captures the path of an included file as the first group.
+    pub inclusion_pattern: String,
+}
+
+impl Default for ParsingRules {
+    /// The rules Curse uses for World of Warcraft addons.
+    fn default() -> Self {
+        ParsingRules {
+            initial_inclusion_pattern: "(?i)^([^/]+)[\\\\/]\\1\\.toc$".to_string(),
+            extra_include_pattern: "(?i)^[^/\\\\]+[/\\\\]Bindings\\.xml$".to_string(),
+            file_parsing_rules: vec![
+                FileParsingRule {
+                    file_extension: ".xml".to_string(),
+                    comment_strip_pattern: "(?s)<!--.*?-->".to_string(),
+                    inclusion_pattern:
+                        "(?i)<(?:Include|Script)\\s+file=[\"\"']((?:(?<!\\.\\.).)+)[\"\"']\\s*/>"
+                            .to_string(),
+                },
+                FileParsingRule {
+                    file_extension: ".toc".to_string(),
+                    comment_strip_pattern: "(?m)\\s*#.*$".to_string(),
+                    inclusion_pattern: "(?mi)^\\s*((?:(?<!\\.\\.).)+\\.(?:xml|lua))\\s*$"
+                        .to_string(),
+                },
+            ],
+        }
+    }
+}
+
+impl PersistentData for ParsingRules {
+    fn relative_path() -> PathBuf {
+        PathBuf::from("parsing_rules.yml")
+    }
+}
+
+impl ParsingRules {
+    /// Returns the rules of the addons section of `game_info`, if it has one.
+    pub fn from_game_info(game_info: &curse::GameInfo) -> Option<ParsingRules> {
+        let section = game_info
+            .category_sections
+            .iter()
+            .find(|s| s.path.to_ascii_lowercase().ends_with("addons"))?;
+
+        if game_info.file_parsing_rules.is_empty() {
+            return None;
+        }
+
+        Some(ParsingRules {
+            initial_inclusion_pattern: section.initial_inclusion_pattern.clone(),
+            extra_include_pattern: section.extra_include_pattern.clone(),
+            file_parsing_rules: game_info
+                .file_parsing_rules
+                .iter()
+                .map(|rule| FileParsingRule {
+                    file_extension: rule.file_extension.clone(),
+                    comment_strip_pattern: rule.comment_strip_pattern.clone(),
+                    inclusion_pattern: rule.inclusion_pattern.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the rules addons are fingerprinted with: `parsing_rules.yml` in
+    /// the config folder, the rules of the last downloaded Curse game info, or
+    /// the built-in rules, in that order. Never touches the network.
+    pub fn load_active() -> ParsingRules {
+        if config_dir().join(ParsingRules::relative_path()).exists() {
+            match ParsingRules::load() {
+                Ok(rules) => return rules,
+                Err(e) => log::error!("failed to load parsing rules: {}", e),
+            }
+        }
+
+        if config_dir().join(curse::GameInfo::relative_path()).exists() {
+            match curse::GameInfo::load() {
+                Ok(game_info) => {
+                    if let Some(rules) = ParsingRules::from_game_info(&game_info) {
+                        return rules;
+                    }
+                }
+                Err(e) => log::error!("failed to load cached game info: {}", e),
+            }
+        }
+
+        ParsingRules::default()
+    }
+
+    fn compile(&self) -> Result<ParsingPatterns, ParseError> {
+        let mut file_parsing_regex = HashMap::new();
+
+        for rule in self.file_parsing_rules.iter() {
+            file_parsing_regex.insert(
+                rule.file_extension.clone(),
+                (
+                    regex::Regex::new(&rule.comment_strip_pattern)?,
+                    Regex::new(&rule.inclusion_pattern)?,
+                ),
+            );
+        }
+
+        Ok(ParsingPatterns {
+            initial_inclusion_regex: Regex::new(&self.initial_inclusion_pattern)?,
+            extra_inclusion_regex: Regex::new(&self.extra_include_pattern)?,
+            file_parsing_regex,
+        })
+    }
+}
+
 pub async fn read_addon_directory<P: AsRef<Path>>(
     addon_cache: Option<Arc<Mutex<AddonCache>>>,
     fingerprint_cache: Option<Arc<Mutex<FingerprintCache>>>,
@@ -86,16 +231,51 @@ pub async fn read_addon_directory<P: AsRef<Path>>(
     // respective fingerprint
     let mut addon_folders = parse_addon_folders(root_dir, flavor, &all_dirs, &fingerprints).await;
 
-    // Get all cached entries
-    let cache_entries = get_cache_entries(flavor, addon_cache).await;
+    // Get all cached entries. Curse addons are matched by fingerprint while
+    // online, their entries only record the last match for when we are offline.
+    let (curse_cache_entries, cache_entries): (Vec<_>, Vec<_>) =
+        get_cache_entries(flavor, addon_cache.clone())
+            .await
+            .into_iter()
+            .partition(|e| e.repository == RepositoryKind::Curse);
+    let offline_cache_entries = [&cache_entries[..], &curse_cache_entries[..]].concat();
 
     // Get fingerprint info for all non-cached addon folders
     let fingerprint_info =
-        get_curse_fingerprint_info(flavor, &addon_folders, &cache_entries).await?;
-
-    // Gets all unique repository packages from the cached ids, toc ids, and fingerprint exact / partial matches
-    let mut all_repo_packages =
-        get_all_repo_packages(flavor, &cache_entries, &addon_folders, &fingerprint_info).await?;
+        match get_curse_fingerprint_info(flavor, &addon_folders, &cache_entries).await {
+            Ok(fingerprint_info) => Some(fingerprint_info),
+            Err(e) => {
+                log::warn!("{} - failed to fetch fingerprint info: {}", flavor, e);
+                None
+            }
+        };
+
+    // Gets all unique repository packages from the cached ids, toc ids, and fingerprint exact / partial matches.
+    // If any of the remote calls fail we are offline, and build stale packages from the
+    // cache entries and toc ids so the addons can still be shown.
+    let repo_packages = match fingerprint_info {
+        Some(fingerprint_info) => {
+            match get_all_repo_packages(flavor, &cache_entries, &addon_folders, &fingerprint_info)
+                .await
+            {
+                Ok(packages) => Some(packages),
+                Err(e) => {
+                    log::warn!("{} - failed to fetch repository packages: {}", flavor, e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    let online = repo_packages.is_some();
+
+    let (mut all_repo_packages, cache_entries) = match repo_packages {
+        Some(packages) => (packages, cache_entries),
+        None => (
+            get_offline_repo_packages(flavor, &offline_cache_entries, &addon_folders),
+            offline_cache_entries,
+        ),
+    };
 
     // Build addons with repo packages & addon folders
     let known_addons = build_addons(
@@ -105,6 +285,16 @@ pub async fn read_addon_directory<P: AsRef<Path>>(
         &cache_entries,
     );
 
+    // Remember which addons were matched to Curse, so they are still known
+    // the next time we are offline
+    if online {
+        if let Some(addon_cache) = addon_cache {
+            if let Err(e) = update_curse_cache_entries(addon_cache, flavor, &known_addons).await {
+                log::warn!("{} - failed to cache curse addons: {}", flavor, e);
+            }
+        }
+    }
+
     // Any remaining addon folders are unknown, we will show them 1:1 in Ajour
     let unknown_addons = addon_folders
         .into_iter()
@@ -156,36 +346,32 @@ async fn fingerprint_all_dirs(
         .par_iter() // Easy parallelization
         .map(|dir_name| {
             let addon_dir = root_dir.join(dir_name);
-            let modified = if let Ok(metadata) = addon_dir.metadata() {
-                metadata.modified().unwrap_or_else(|_| SystemTime::now())
-            } else {
-                SystemTime::now()
-            };
 
-            // If we have a stored fingerprint on disk, we use that.
-            if let Some(fingerprint) = fingerprints
+            // If we have a stored fingerprint on disk, only the files that
+            // changed since are hashed again.
+            let cached = fingerprints
                 .as_ref()
-                .unwrap_or(&&mut vec![])
-                .iter()
-                .find(|f| &f.title == dir_name && f.modified == modified)
-            {
-                let _ = num_cached.fetch_add(1, Ordering::SeqCst);
-                fingerprint.to_owned()
-            } else {
-                let hash_result = fingerprint_addon_dir(&addon_dir);
+                .and_then(|f| f.iter().find(|f| &f.title == dir_name));
 
-                let hash = match hash_result {
-                    Ok(hash) => Some(hash),
-                    Err(e) => {
-                        log::error!("fingerprinting failed for {:?}: {}", addon_dir, e);
-                        None
+            match fingerprint_addon_dir_cached(&addon_dir, cached) {
+                Ok(fingerprint) => {
+                    if cached.map(|c| c.signature) == Some(fingerprint.signature) {
+                        let _ = num_cached.fetch_add(1, Ordering::SeqCst);
                     }
-                };
 
-                Fingerprint {
-                    title: dir_name.to_owned(),
-                    hash,
-                    modified,
+                    fingerprint
+                }
+                Err(e) => {
+                    log::error!("fingerprinting failed for {:?}: {}", addon_dir, e);
+
+                    Fingerprint {
+                        title: dir_name.to_owned(),
+                        hash: None,
+                        modified: SystemTime::now(),
+                        signature: None,
+                        files: vec![],
+                        installed_files: vec![],
+                    }
                 }
             }
         })
@@ -618,6 +804,73 @@ async fn get_all_repo_packages(
     .concat())
 }
 
+/// Builds repository packages without talking to any repository. Metadata is
+/// marked as stale and only contains what we know from the cache entries.
+fn get_offline_repo_packages(
+    flavor: Flavor,
+    cache_entries: &[AddonCacheEntry],
+    addon_folders: &[AddonFolder],
+) -> Vec<RepositoryPackage> {
+    let cached_folder_names: Vec<_> = cache_entries
+        .iter()
+        .map(|e| &e.folder_names)
+        .flatten()
+        .collect();
+
+    let mut repo_packages: Vec<RepositoryPackage> = cache_entries
+        .iter()
+        .filter_map(|e| {
+            let package = if e.repository.is_git() {
+                let url = e.repository_id.parse::<Uri>().ok()?;
+                RepositoryPackage::from_source_url(flavor, url)
+            } else {
+                RepositoryPackage::from_repo_id(flavor, e.repository, e.repository_id.clone())
+            };
+
+            package
+                .map(|r| r.with_metadata(RepositoryMetadata::stale(Some(e.title.clone()))))
+                .ok()
+        })
+        .collect();
+
+    // Non-cached addon folders can still be linked to a repository through
+    // the ids in their toc file
+    for folder in addon_folders
+        .iter()
+        .filter(|folder| !cached_folder_names.contains(&&folder.id))
+    {
+        let identifiers = &folder.repository_identifiers;
+
+        let repo_id = if let Some(id) = identifiers.curse {
+            Some((RepositoryKind::Curse, id.to_string()))
+        } else if let Some(id) = identifiers.tukui.as_ref() {
+            Some((RepositoryKind::Tukui, id.clone()))
+        } else if let Some(id) = identifiers.wowi.as_ref() {
+            Some((RepositoryKind::WowI, id.clone()))
+        } else {
+            None
+        };
+
+        if let Some((kind, id)) = repo_id {
+            if repo_packages.iter().any(|r| r.kind == kind && r.id == id) {
+                continue;
+            }
+
+            if let Ok(package) = RepositoryPackage::from_repo_id(flavor, kind, id) {
+                repo_packages.push(package.with_metadata(RepositoryMetadata::stale(None)));
+            }
+        }
+    }
+
+    log::debug!(
+        "{} - offline, {} stale packages built from cache and toc ids",
+        flavor,
+        repo_packages.len()
+    );
+
+    repo_packages
+}
+
 fn build_addons(
     flavor: Flavor,
     repo_packages: &mut Vec<RepositoryPackage>,
@@ -654,6 +907,19 @@ fn build_addons(
 
             if let Ok(mut addon) = Addon::build_with_repo_and_folders(repo_package, folders) {
                 addon.primary_folder_id = e.primary_folder_id.clone();
+
+                // The release recorded in the cache is only trusted as long as
+                // the folders weren't changed since it was installed
+                if e.is_unmodified(&addon.folders) {
+                    if let Some(version) = e.version.clone() {
+                        addon.set_version(version);
+                    }
+
+                    if let Some(file_id) = e.file_id {
+                        addon.set_file_id(file_id);
+                    }
+                }
+
                 Some(addon)
             } else {
                 None
@@ -669,11 +935,17 @@ fn build_addons(
             // Get and remove all matching addon folders
             let module_names = r.metadata.modules();
 
-            // Get and remove all matching addon folders
+            // Get and remove all matching addon folders. Stale packages have no
+            // modules, so we match them against the curse id from the toc instead.
             let folder_idxs: Vec<_> = addon_folders
                 .iter()
                 .enumerate()
-                .filter(|(_, f)| module_names.contains(&f.id))
+                .filter(|(_, f)| {
+                    module_names.contains(&f.id)
+                        || (r.metadata.stale
+                            && f.repository_identifiers.curse.map(|id| id.to_string())
+                                == Some(r.id.clone()))
+                })
                 .map(|(idx, _)| idx)
                 .collect();
 
@@ -787,6 +1059,8 @@ fn build_addons(
     concatenated_addons
 }
 
+/// Fingerprints the folder `addon_id`, which was just installed or updated,
+/// and updates the cache. Its files are kept as the installed files.
 pub async fn update_addon_fingerprint(
     fingerprint_cache: Arc<Mutex<FingerprintCache>>,
     flavor: Flavor,
@@ -797,32 +1071,32 @@ pub async fn update_addon_fingerprint(
 
     let addon_path = addon_dir.as_ref().join(&addon_id);
 
+    // Clone the cached fingerprint, so the folder is hashed without holding the
+    // lock on the cache.
+    let cached = fingerprint_cache
+        .lock()
+        .await
+        .get_mut_for_flavor(flavor)
+        .iter()
+        .find(|f| f.title == addon_id)
+        .cloned();
+
     // Generate new hash, and update collection.
-    match fingerprint_addon_dir(&addon_path) {
-        Ok(hash) => {
+    match fingerprint_addon_dir_cached(&addon_path, cached.as_ref()) {
+        Ok(mut fingerprint) => {
+            fingerprint.installed_files = fingerprint.files.clone();
+
             // Lock Mutex ensuring this is the only operation that can update the cache.
             // This is needed since during `Update All` we can have concurrent operations updating
             // this cache and we need to ensure they don't overwrite eachother.
             let mut fingerprint_cache = fingerprint_cache.lock().await;
 
             let fingerprints = fingerprint_cache.get_mut_for_flavor(flavor);
-            let modified = if let Ok(metadata) = addon_path.metadata() {
-                metadata.modified().unwrap_or_else(|_| SystemTime::now())
-            } else {
-                SystemTime::now()
-            };
 
             // If already in cache, update it. Otherwise add entry to cache
-            if let Some(fingerprint) = fingerprints.iter_mut().find(|f| f.title == addon_id) {
-                fingerprint.hash = Some(hash);
-                fingerprint.modified = modified;
+            if let Some(idx) = fingerprints.iter().position(|f| f.title == addon_id) {
+                fingerprints[idx] = fingerprint;
             } else {
-                let fingerprint = Fingerprint {
-                    title: addon_id.clone(),
-                    hash: Some(hash),
-                    modified,
-                };
-
                 fingerprints.push(fingerprint);
             }
 
@@ -839,25 +1113,165 @@ pub async fn update_addon_fingerprint(
     Ok(())
 }
 
-pub fn fingerprint_addon_dir(addon_dir: &PathBuf) -> Result<u32, ParseError> {
-    let mut to_fingerprint = HashSet::new();
-    let mut to_parse = VecDeque::new();
-    let root_dir = addon_dir.parent().ok_or(ParseError::NoParentDirectory {
-        dir: addon_dir.to_owned(),
-    })?;
+pub fn fingerprint_addon_dir(addon_dir: &Path) -> Result<u32, ParseError> {
+    let files = list_addon_files(addon_dir)?;
 
-    // Add initial files
+    Ok(fingerprint_addon_files(addon_dir, &files, &[])?.0)
+}
+
+/// Returns the fingerprint of each of `folders` in `addon_dir`.
+pub fn fingerprint_folders(
+    addon_dir: &Path,
+    folders: &[String],
+) -> Result<BTreeMap<String, u32>, ParseError> {
+    folders
+        .iter()
+        .map(|folder| {
+            Ok((
+                folder.clone(),
+                fingerprint_addon_dir(&addon_dir.join(folder))?,
+            ))
+        })
+        .collect()
+}
+
+/// Fingerprints `addon_dir`, reusing `cached` if no file in the folder changed
+/// since. Otherwise only the files whose size or modification time changed
+/// are hashed again.
+pub(crate) fn fingerprint_addon_dir_cached(
+    addon_dir: &Path,
+    cached: Option<&Fingerprint>,
+) -> Result<Fingerprint, ParseError> {
+    let modified = if let Ok(metadata) = addon_dir.metadata() {
+        metadata.modified().unwrap_or_else(|_| SystemTime::now())
+    } else {
+        SystemTime::now()
+    };
+
+    let files = list_addon_files(addon_dir)?;
+    let signature = Some(files_signature(addon_dir, &files));
+
+    if let Some(cached) = cached {
+        if cached.hash.is_some() && cached.signature == signature {
+            return Ok(Fingerprint {
+                modified,
+                ..cached.clone()
+            });
+        }
+    }
+
+    let cached_files = cached.map(|c| c.files.as_slice()).unwrap_or_default();
+    let (hash, files) = fingerprint_addon_files(addon_dir, &files, cached_files)?;
+
+    Ok(Fingerprint {
+        title: addon_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        hash: Some(hash),
+        modified,
+        signature,
+        files,
+        installed_files: cached
+            .map(|c| c.installed_files.clone())
+            .unwrap_or_default(),
+    })
+}
+
+/// A file in an addon folder, along with its size and modification time.
+struct AddonFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Returns every file in `addon_dir` and its sub folders.
+fn list_addon_files(addon_dir: &Path) -> Result<Vec<AddonFile>, ParseError> {
     let glob_pattern = format!(
         "{}/**/*.*",
         addon_dir.to_str().ok_or(ParseError::InvalidUTF8Path {
             path: addon_dir.to_owned(),
         })?
     );
+
+    let mut files = vec![];
+
     for path in glob::glob(&glob_pattern)? {
         let path = path?;
-        if !path.is_file() {
-            continue;
-        }
+        let metadata = match path.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+
+        files.push(AddonFile {
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Path of `path` relative to `addon_dir`, with `/` separators.
+fn relative_file_path(addon_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(addon_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace("\\", "/")
+}
+
+/// Hashes the path, size and modification time of every file in `files`.
+fn files_signature(addon_dir: &Path, files: &[AddonFile]) -> u32 {
+    let mut lines: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let modified = file
+                .modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+
+            format!(
+                "{}:{}:{}",
+                relative_file_path(addon_dir, &file.path),
+                file.size,
+                modified
+            )
+        })
+        .collect();
+    lines.sort_unstable();
+
+    calculate_hash(lines.join("\n").as_bytes(), 1)
+}
+
+/// Hashes a single file the way Curse does, ignoring whitespace.
+fn fingerprint_file(path: &Path) -> Result<u32, ParseError> {
+    let data: Vec<_> = std::fs::read(path)?
+        .into_iter()
+        .filter(|&b| b != b' ' && b != b'\n' && b != b'\r' && b != b'\t')
+        .collect();
+
+    Ok(calculate_hash(&data, 1))
+}
+
+/// Calculates the fingerprint of `addon_dir` from `files`, the files in the
+/// folder. Hashes in `cached` are reused for files whose size and modification
+/// time didn't change. Returns the fingerprint and each hashed file.
+fn fingerprint_addon_files(
+    addon_dir: &Path,
+    files: &[AddonFile],
+    cached: &[FileFingerprint],
+) -> Result<(u32, Vec<FileFingerprint>), ParseError> {
+    let mut to_fingerprint = HashSet::new();
+    let mut to_parse = VecDeque::new();
+    let root_dir = addon_dir.parent().ok_or(ParseError::NoParentDirectory {
+        dir: addon_dir.to_owned(),
+    })?;
+
+    // Add initial files
+    for file in files {
+        let path = &file.path;
 
         // Test relative path matches regexes
         let relative_path = path
@@ -870,12 +1284,12 @@ pub fn fingerprint_addon_dir(addon_dir: &PathBuf) -> Result<u32, ParseError> {
             .initial_inclusion_regex
             .is_match(&relative_path)?
         {
-            to_parse.push_back(path);
+            to_parse.push_back(path.clone());
         } else if RE_PARSING_PATTERNS
             .extra_inclusion_regex
             .is_match(&relative_path)?
         {
-            to_fingerprint.insert(path);
+            to_fingerprint.insert(path.clone());
         }
     }
 
@@ -944,20 +1358,46 @@ pub fn fingerprint_addon_dir(addon_dir: &PathBuf) -> Result<u32, ParseError> {
         }
     }
 
-    // Calculate fingerprints
-    let mut fingerprints = vec![];
+    // Calculate fingerprints, reusing the hash of unchanged files
+    let listed: HashMap<_, _> = files.iter().map(|f| (f.path.as_path(), f)).collect();
+    let cached: HashMap<_, _> = cached.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let mut file_fingerprints = vec![];
     for path in to_fingerprint.iter() {
-        let data: Vec<_> = std::fs::read(path)?
-            .into_iter()
-            .filter(|&b| b != b' ' && b != b'\n' && b != b'\r' && b != b'\t')
-            .collect();
+        let relative_path = relative_file_path(addon_dir, path);
+        let (size, modified) = match listed.get(path.as_path()) {
+            Some(file) => (file.size, file.modified),
+            None => {
+                let metadata = path.metadata()?;
+                (
+                    metadata.len(),
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                )
+            }
+        };
+
+        let hash = match cached.get(relative_path.as_str()) {
+            Some(file) if file.size == size && file.modified == modified => file.hash,
+            _ => fingerprint_file(path)?,
+        };
+
+        file_fingerprints.push(FileFingerprint {
+            path: relative_path,
+            size,
+            modified,
+            hash,
+        });
+    }
+    file_fingerprints.sort_unstable_by(|a, b| a.path.cmp(&b.path));
 
-        let hash = calculate_hash(&data, 1);
+    let hash = combine_file_hashes(&file_fingerprints);
 
-        fingerprints.push(hash);
-    }
+    Ok((hash, file_fingerprints))
+}
 
-    // Calculate overall fingerprint
+/// Calculates the overall fingerprint of a folder from its files.
+fn combine_file_hashes(files: &[FileFingerprint]) -> u32 {
+    let mut fingerprints: Vec<_> = files.iter().map(|f| f.hash).collect();
     fingerprints.sort_unstable();
     let to_hash = fingerprints
         .iter()
@@ -965,7 +1405,168 @@ pub fn fingerprint_addon_dir(addon_dir: &PathBuf) -> Result<u32, ParseError> {
         .collect::<Vec<_>>()
         .join("");
 
-    Ok(calculate_hash(to_hash.as_bytes(), 1))
+    calculate_hash(to_hash.as_bytes(), 1)
+}
+
+/// Breakdown of the fingerprint of an addon folder per file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintDiagnostics {
+    pub folder: String,
+    pub fingerprint: u32,
+    /// Fingerprint Curse has for the folder in the installed release, if known.
+    pub expected: Option<u32>,
+    /// `true` if the files right after installing the folder are known, so
+    /// changed files can be told apart.
+    pub has_baseline: bool,
+    /// Files included by the parsing rules, and any installed file which is
+    /// gone, sorted by path.
+    pub files: Vec<FileDiagnostics>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiagnostics {
+    /// Path relative to the addon folder, with `/` separators.
+    pub path: String,
+    /// Murmur2 hash of the file without whitespace. `None` if it was removed.
+    pub hash: Option<u32>,
+    pub change: Option<FileChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl std::fmt::Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FileChange::Added => "added",
+            FileChange::Modified => "modified",
+            FileChange::Removed => "removed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FingerprintDiagnostics {
+    /// Returns `true` if the folder has the fingerprint Curse expects, or
+    /// `None` if it isn't known.
+    pub fn is_match(&self) -> Option<bool> {
+        self.expected.map(|expected| expected == self.fingerprint)
+    }
+
+    /// Files which differ from the installed files.
+    pub fn changed_files(&self) -> impl Iterator<Item = &FileDiagnostics> {
+        self.files.iter().filter(|f| f.change.is_some())
+    }
+}
+
+/// Fingerprints `addon_dir` file by file. `installed` are the files of the
+/// folder right after it was installed. They're only compared against if they
+/// add up to `expected`, the fingerprint Curse has for the folder, when known.
+pub fn diagnose_addon_dir(
+    addon_dir: &Path,
+    expected: Option<u32>,
+    installed: &[FileFingerprint],
+) -> Result<FingerprintDiagnostics, ParseError> {
+    let files = list_addon_files(addon_dir)?;
+    let (fingerprint, file_fingerprints) = fingerprint_addon_files(addon_dir, &files, &[])?;
+
+    let baseline = if installed.is_empty() {
+        None
+    } else {
+        match expected {
+            Some(expected) if combine_file_hashes(installed) != expected => None,
+            _ => Some(installed),
+        }
+    };
+
+    let mut files: Vec<_> = file_fingerprints
+        .iter()
+        .map(|file| {
+            let change =
+                baseline.map(
+                    |baseline| match baseline.iter().find(|f| f.path == file.path) {
+                        Some(installed) if installed.hash == file.hash => None,
+                        Some(_) => Some(FileChange::Modified),
+                        None => Some(FileChange::Added),
+                    },
+                );
+
+            FileDiagnostics {
+                path: file.path.clone(),
+                hash: Some(file.hash),
+                change: change.flatten(),
+            }
+        })
+        .collect();
+
+    if let Some(baseline) = baseline {
+        files.extend(
+            baseline
+                .iter()
+                .filter(|installed| !file_fingerprints.iter().any(|f| f.path == installed.path))
+                .map(|installed| FileDiagnostics {
+                    path: installed.path.clone(),
+                    hash: None,
+                    change: Some(FileChange::Removed),
+                }),
+        );
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    Ok(FingerprintDiagnostics {
+        folder: addon_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        fingerprint,
+        expected,
+        has_baseline: baseline.is_some(),
+        files,
+    })
+}
+
+/// Diagnoses the fingerprint of every folder of `addon` in `addon_dir`, the
+/// AddOns directory of `flavor`. The installed files are taken from
+/// `fingerprint_cache`.
+pub async fn diagnose_addon_fingerprints(
+    fingerprint_cache: Option<Arc<Mutex<FingerprintCache>>>,
+    flavor: Flavor,
+    addon: Addon,
+    addon_dir: PathBuf,
+) -> Result<Vec<FingerprintDiagnostics>, ParseError> {
+    let mut installed_files = HashMap::new();
+
+    if let Some(fingerprint_cache) = fingerprint_cache {
+        let mut fingerprint_cache = fingerprint_cache.lock().await;
+
+        for fingerprint in fingerprint_cache.get_mut_for_flavor(flavor).iter() {
+            if addon.folders.iter().any(|f| f.id == fingerprint.title) {
+                installed_files.insert(
+                    fingerprint.title.clone(),
+                    fingerprint.installed_files.clone(),
+                );
+            }
+        }
+    }
+
+    addon
+        .folders
+        .iter()
+        .map(|folder| {
+            diagnose_addon_dir(
+                &addon_dir.join(&folder.id),
+                addon.expected_fingerprint(&folder.id),
+                installed_files
+                    .get(&folder.id)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+            )
+        })
+        .collect()
 }
 
 /// Finds a case sensitive path from an insensitive path
@@ -1009,25 +1610,15 @@ where
 lazy_static::lazy_static! {
     static ref RE_TOC_LINE: regex::Regex = regex::Regex::new(r"^##\s*(?P<key>.*?)\s*:\s?(?P<value>.*)").unwrap();
     static ref RE_TOC_TITLE: regex::Regex = regex::Regex::new(r"\|(?:[a-fA-F\d]{9}|T[^|]*|t|r|$)").unwrap();
-    static ref RE_PARSING_PATTERNS: ParsingPatterns = {
-        let mut file_parsing_regex = HashMap::new();
-        file_parsing_regex.insert(".xml".to_string(), (
-            regex::Regex::new("(?s)<!--.*?-->").unwrap(),
-            Regex::new("(?i)<(?:Include|Script)\\s+file=[\"\"']((?:(?<!\\.\\.).)+)[\"\"']\\s*/>").unwrap(),
-        ));
-
-
-        file_parsing_regex.insert(".toc".to_string(), (
-            regex::Regex::new("(?m)\\s*#.*$").unwrap(),
-            Regex::new("(?mi)^\\s*((?:(?<!\\.\\.).)+\\.(?:xml|lua))\\s*$").unwrap(),
-        ));
-
-        ParsingPatterns {
-            extra_inclusion_regex: Regex::new("(?i)^[^/\\\\]+[/\\\\]Bindings\\.xml$").unwrap(),
-            initial_inclusion_regex: Regex::new("(?i)^([^/]+)[\\\\/]\\1\\.toc$").unwrap(),
-            file_parsing_regex,
-        }
-    };
+    static ref RE_PARSING_PATTERNS: ParsingPatterns = ParsingRules::load_active()
+        .compile()
+        .unwrap_or_else(|e| {
+            log::error!("invalid parsing rules, using the built-in rules: {}", e);
+
+            ParsingRules::default()
+                .compile()
+                .expect("built-in parsing rules are valid")
+        });
 }
 
 /// Helper function to parse a given TOC file
@@ -1052,6 +1643,8 @@ pub fn parse_toc_path(toc_path: &PathBuf) -> Option<AddonFolder> {
     let mut notes: Option<String> = None;
     let mut version: Option<String> = None;
     let mut dependencies: Vec<String> = Vec::new();
+    let mut saved_variables: Vec<String> = Vec::new();
+    let mut saved_variables_per_character: Vec<String> = Vec::new();
     let mut wowi_id: Option<String> = None;
     let mut tukui_id: Option<String> = None;
     let mut curse_id: Option<i32> = None;
@@ -1086,6 +1679,15 @@ pub fn parse_toc_path(toc_path: &PathBuf) -> Option<AddonFolder> {
                 "Dependencies" | "RequiredDeps" => {
                     dependencies.append(&mut split_dependencies_into_vec(&cap["value"]));
                 }
+                // Global names saved to WTF, used to find the SavedVariables files
+                // of the addon.
+                "SavedVariables" => {
+                    saved_variables.append(&mut split_dependencies_into_vec(&cap["value"]));
+                }
+                "SavedVariablesPerCharacter" => {
+                    saved_variables_per_character
+                        .append(&mut split_dependencies_into_vec(&cap["value"]));
+                }
                 "X-Tukui-ProjectID" => tukui_id = Some(cap["value"].to_string()),
                 "X-WoWI-ID" => wowi_id = Some(cap["value"].to_string()),
                 "X-Curse-Project-ID" => {
@@ -1115,6 +1717,8 @@ pub fn parse_toc_path(toc_path: &PathBuf) -> Option<AddonFolder> {
         version,
         repository_identifiers,
         dependencies,
+        saved_variables,
+        saved_variables_per_character,
     ))
 }
 
@@ -1146,6 +1750,34 @@ fn format_interface_into_game_version(interface: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fs::test_dir;
+    use std::fs;
+
+    /// Writes the addon folder `Foo` to `root_dir`. Its toc includes a file in
+    /// a sub folder.
+    fn write_test_addon(root_dir: &Path) -> PathBuf {
+        let addon_dir = root_dir.join("Foo");
+        fs::create_dir_all(addon_dir.join("Libs")).unwrap();
+
+        fs::write(
+            addon_dir.join("Foo.toc"),
+            "## Title: Foo\nFoo.lua\nLibs\\Lib.lua\n",
+        )
+        .unwrap();
+        fs::write(addon_dir.join("Foo.lua"), "print('foo')").unwrap();
+        fs::write(addon_dir.join("Libs/Lib.lua"), "print('lib')").unwrap();
+
+        addon_dir
+    }
+
+    fn file_hash(fingerprint: &Fingerprint, path: &str) -> u32 {
+        fingerprint
+            .files
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| f.hash)
+            .unwrap()
+    }
 
     #[test]
     fn test_toc_title() {
@@ -1194,4 +1826,162 @@ mod tests {
         let interface = "100000";
         assert_eq!("100000", format_interface_into_game_version(interface));
     }
+
+    #[test]
+    fn test_default_parsing_rules() {
+        let patterns = ParsingRules::default().compile().unwrap();
+
+        assert!(patterns
+            .initial_inclusion_regex
+            .is_match("details\\details.toc")
+            .unwrap());
+        assert!(patterns
+            .extra_inclusion_regex
+            .is_match("details\\bindings.xml")
+            .unwrap());
+        assert!(patterns.file_parsing_regex.contains_key(".xml"));
+        assert!(patterns.file_parsing_regex.contains_key(".toc"));
+    }
+
+    #[test]
+    fn test_parsing_rules_from_game_info() {
+        let section = |name: &str, path: &str| curse::CategorySection {
+            id: 1,
+            game_id: 1,
+            name: name.to_owned(),
+            package_type: 1,
+            path: path.to_owned(),
+            initial_inclusion_pattern: format!("{}-initial", name),
+            extra_include_pattern: format!("{}-extra", name),
+            game_category_id: 1,
+        };
+
+        let mut game_info = curse::GameInfo {
+            id: 1,
+            name: "World of Warcraft".to_owned(),
+            slug: "wow".to_owned(),
+            date_modified: String::new(),
+            file_parsing_rules: vec![curse::FileParsingRule {
+                comment_strip_pattern: "--.*$".to_owned(),
+                file_extension: ".lua".to_owned(),
+                inclusion_pattern: "(.*)".to_owned(),
+                game_id: 1,
+                id: 1,
+            }],
+            category_sections: vec![
+                section("Textures", "interface\\textures"),
+                section("Addons", "interface\\addons"),
+            ],
+        };
+
+        let rules = ParsingRules::from_game_info(&game_info).unwrap();
+        assert_eq!(rules.initial_inclusion_pattern, "Addons-initial");
+        assert_eq!(rules.extra_include_pattern, "Addons-extra");
+        assert_eq!(rules.file_parsing_rules[0].file_extension, ".lua");
+
+        game_info.file_parsing_rules.clear();
+        assert_eq!(ParsingRules::from_game_info(&game_info), None);
+    }
+
+    #[test]
+    fn test_fingerprint_cached_nested_change() {
+        let dir = test_dir("fingerprint_cached_nested_change");
+        let addon_dir = write_test_addon(&dir);
+
+        let cached = fingerprint_addon_dir_cached(&addon_dir, None).unwrap();
+        assert_eq!(cached.title, "Foo");
+        assert_eq!(cached.files.len(), 3);
+        assert_eq!(
+            cached.hash,
+            Some(fingerprint_addon_dir(&addon_dir).unwrap())
+        );
+
+        let unchanged = fingerprint_addon_dir_cached(&addon_dir, Some(&cached)).unwrap();
+        assert_eq!(unchanged.hash, cached.hash);
+        assert_eq!(unchanged.signature, cached.signature);
+
+        // Changing a file in a sub folder doesn't touch the addon folder itself,
+        // but still changes its signature
+        fs::write(addon_dir.join("Libs/Lib.lua"), "print('lib changed')").unwrap();
+
+        let files = list_addon_files(&addon_dir).unwrap();
+        assert_ne!(Some(files_signature(&addon_dir, &files)), cached.signature);
+
+        let changed = fingerprint_addon_dir_cached(&addon_dir, Some(&cached)).unwrap();
+        assert_ne!(changed.hash, cached.hash);
+        assert_eq!(
+            changed.hash,
+            Some(fingerprint_addon_dir(&addon_dir).unwrap())
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_fingerprint_cached_reuses_file_hashes() {
+        let dir = test_dir("fingerprint_cached_reuses_file_hashes");
+        let addon_dir = write_test_addon(&dir);
+
+        // A made up hash shows whether the cached hash of a file is reused
+        let mut cached = fingerprint_addon_dir_cached(&addon_dir, None).unwrap();
+        for file in cached.files.iter_mut().filter(|f| f.path == "Foo.lua") {
+            file.hash = 1;
+        }
+
+        fs::write(addon_dir.join("Libs/Lib.lua"), "print('lib changed')").unwrap();
+
+        let fingerprint = fingerprint_addon_dir_cached(&addon_dir, Some(&cached)).unwrap();
+        assert_eq!(file_hash(&fingerprint, "Foo.lua"), 1);
+        assert_eq!(
+            file_hash(&fingerprint, "Libs/Lib.lua"),
+            fingerprint_file(&addon_dir.join("Libs/Lib.lua")).unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diagnose_addon_dir() {
+        let dir = test_dir("diagnose_addon_dir");
+        let addon_dir = write_test_addon(&dir);
+
+        let installed = fingerprint_addon_dir_cached(&addon_dir, None).unwrap();
+        let expected = installed.hash.unwrap();
+
+        let diagnostics = diagnose_addon_dir(&addon_dir, Some(expected), &installed.files).unwrap();
+        assert_eq!(diagnostics.is_match(), Some(true));
+        assert!(diagnostics.has_baseline);
+        assert_eq!(diagnostics.changed_files().count(), 0);
+
+        fs::write(addon_dir.join("Foo.lua"), "print('foo changed')").unwrap();
+        fs::write(addon_dir.join("Bindings.xml"), "<Bindings></Bindings>").unwrap();
+        fs::remove_file(addon_dir.join("Libs/Lib.lua")).unwrap();
+
+        let diagnostics = diagnose_addon_dir(&addon_dir, Some(expected), &installed.files).unwrap();
+        assert_eq!(diagnostics.is_match(), Some(false));
+
+        let changes: Vec<_> = diagnostics
+            .changed_files()
+            .map(|f| (f.path.as_str(), f.change.unwrap()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("Bindings.xml", FileChange::Added),
+                ("Foo.lua", FileChange::Modified),
+                ("Libs/Lib.lua", FileChange::Removed),
+            ]
+        );
+
+        // Installed files which don't add up to the expected fingerprint aren't
+        // compared against
+        let diagnostics =
+            diagnose_addon_dir(&addon_dir, Some(expected.wrapping_add(1)), &installed.files)
+                .unwrap();
+        assert!(!diagnostics.has_baseline);
+        assert_eq!(diagnostics.changed_files().count(), 0);
+        assert_eq!(diagnostics.files.len(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
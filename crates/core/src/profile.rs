@@ -0,0 +1,186 @@
+//! Named sets of addons per flavor. Switching to a profile parks the addons
+//! which aren't part of it in a folder next to `AddOns`, so neither WoW nor
+//! Ajour sees them until they are switched back in.
+
+use crate::config::{Config, Flavor};
+use crate::error::FilesystemError;
+use crate::fs::addon_folders;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folders which were moved when switching profile.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileSwitch {
+    /// Folders moved out of `AddOns`.
+    pub parked: Vec<String>,
+    /// Folders moved back into `AddOns`.
+    pub unparked: Vec<String>,
+    /// Folders of the profile which are neither in `AddOns` nor parked.
+    pub missing: Vec<String>,
+}
+
+/// Returns the folders of profile `name` of `flavor`.
+pub fn profile_folders(
+    config: &Config,
+    flavor: Flavor,
+    name: &str,
+) -> Result<Vec<String>, FilesystemError> {
+    config
+        .addons
+        .profiles
+        .get(&flavor)
+        .and_then(|profiles| profiles.get(name))
+        .cloned()
+        .ok_or_else(|| FilesystemError::UnknownProfile {
+            name: name.to_owned(),
+        })
+}
+
+/// Saves the addon folders in `addon_dir` as profile `name` of `flavor`,
+/// replacing any profile with the same name. Returns the number of folders.
+pub fn save_profile(
+    config: &mut Config,
+    flavor: Flavor,
+    name: String,
+    addon_dir: &Path,
+) -> Result<usize, FilesystemError> {
+    let folders = addon_folders(addon_dir)?;
+    let count = folders.len();
+
+    config
+        .addons
+        .profiles
+        .entry(flavor)
+        .or_default()
+        .insert(name.clone(), folders);
+    config.addons.active_profile.insert(flavor, name);
+
+    Ok(count)
+}
+
+/// Deletes profile `name` of `flavor`. Parked addons stay parked until
+/// another profile is switched to.
+pub fn delete_profile(
+    config: &mut Config,
+    flavor: Flavor,
+    name: &str,
+) -> Result<(), FilesystemError> {
+    config
+        .addons
+        .profiles
+        .get_mut(&flavor)
+        .and_then(|profiles| profiles.remove(name))
+        .ok_or_else(|| FilesystemError::UnknownProfile {
+            name: name.to_owned(),
+        })?;
+
+    if config
+        .addons
+        .active_profile
+        .get(&flavor)
+        .map(String::as_str)
+        == Some(name)
+    {
+        config.addons.active_profile.remove(&flavor);
+    }
+
+    Ok(())
+}
+
+/// Parks the folders in `addon_dir` which aren't in `profile`, and moves the
+/// parked folders which are back into `addon_dir`. With no profile, every
+/// parked folder is moved back.
+pub async fn switch_profile(
+    addon_dir: PathBuf,
+    parked_dir: PathBuf,
+    profile: Option<Vec<String>>,
+) -> Result<ProfileSwitch, FilesystemError> {
+    let active = addon_folders(&addon_dir)?;
+    let parked = addon_folders(&parked_dir)?;
+
+    let switch = plan_switch(&active, &parked, profile.as_deref());
+
+    if !switch.parked.is_empty() {
+        fs::create_dir_all(&parked_dir)?;
+    }
+
+    for folder in &switch.parked {
+        let to = parked_dir.join(folder);
+
+        // An addon installed while it was parked replaces the parked copy
+        if to.exists() {
+            fs::remove_dir_all(&to)?;
+        }
+
+        fs::rename(addon_dir.join(folder), to)?;
+    }
+
+    for folder in &switch.unparked {
+        fs::rename(parked_dir.join(folder), addon_dir.join(folder))?;
+    }
+
+    log::debug!(
+        "switched profile: {} folders parked, {} unparked",
+        switch.parked.len(),
+        switch.unparked.len()
+    );
+
+    Ok(switch)
+}
+
+/// Returns which folders to park and unpark to go from `active` and `parked`
+/// folders to `profile`.
+fn plan_switch(active: &[String], parked: &[String], profile: Option<&[String]>) -> ProfileSwitch {
+    let in_profile = |folder: &String| profile.map_or(true, |p| p.contains(folder));
+
+    let missing = match profile {
+        Some(profile) => profile
+            .iter()
+            .filter(|f| !active.contains(f) && !parked.contains(f))
+            .cloned()
+            .collect(),
+        None => vec![],
+    };
+
+    ProfileSwitch {
+        parked: active.iter().filter(|f| !in_profile(f)).cloned().collect(),
+        // Folders which are both active and parked stay where they are
+        unparked: parked
+            .iter()
+            .filter(|f| in_profile(f) && !active.contains(f))
+            .cloned()
+            .collect(),
+        missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| (*n).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_plan_switch() {
+        let active = folders(&["Bagnon", "DBM-Core", "Details", "Gladius"]);
+        let parked = folders(&["BigWigs", "OmniCC"]);
+
+        let raid = folders(&["BigWigs", "DBM-Core", "Details", "WeakAuras"]);
+
+        let switch = plan_switch(&active, &parked, Some(&raid));
+
+        assert_eq!(switch.parked, folders(&["Bagnon", "Gladius"]));
+        assert_eq!(switch.unparked, folders(&["BigWigs"]));
+        assert_eq!(switch.missing, folders(&["WeakAuras"]));
+
+        // Without a profile, everything is moved back
+        let switch = plan_switch(&active, &parked, None);
+
+        assert!(switch.parked.is_empty());
+        assert_eq!(switch.unparked, folders(&["BigWigs", "OmniCC"]));
+        assert!(switch.missing.is_empty());
+    }
+}
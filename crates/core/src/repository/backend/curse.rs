@@ -1,7 +1,7 @@
 use super::*;
 use crate::config::Flavor;
 use crate::error::DownloadError;
-use crate::network::post_json_async;
+use crate::network::{post_json_async, request_async};
 use crate::repository::{ReleaseChannel, RemotePackage};
 
 use async_trait::async_trait;
@@ -13,6 +13,7 @@ use std::collections::HashMap;
 
 const API_ENDPOINT: &str = "https://addons-ecs.forgesvc.net/api/v2";
 const FINGERPRINT_API_ENDPOINT: &str = "https://hub.wowup.io/curseforge/addons/fingerprint";
+const WOW_GAME_ID: i64 = 1;
 
 #[derive(Debug, Clone)]
 pub struct Curse {
@@ -138,6 +139,12 @@ pub(crate) fn metadata_from_fingerprint_info(
     metadata.version = version;
     metadata.file_id = file_id;
     metadata.game_version = game_version;
+    metadata.module_fingerprints = info
+        .file
+        .modules
+        .iter()
+        .map(|m| (m.foldername.clone(), m.fingerprint))
+        .collect();
 
     metadata
 }
@@ -181,6 +188,22 @@ pub(crate) async fn fetch_remote_packages_by_ids(
     }
 }
 
+/// Fetches the game info of World of Warcraft, which holds the rules for which
+/// files of an addon are fingerprinted.
+pub async fn fetch_game_info() -> Result<GameInfo, DownloadError> {
+    let url = format!("{}/game/{}", API_ENDPOINT, WOW_GAME_ID);
+    let mut resp = request_async(&url, vec![], None).await?;
+    if resp.status().is_success() {
+        let game_info = resp.json()?;
+        Ok(game_info)
+    } else {
+        Err(DownloadError::InvalidStatusCode {
+            code: resp.status(),
+            url,
+        })
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// Struct for applying curse details to an `Addon`.
@@ -218,7 +241,7 @@ pub struct Module {
     pub type_field: i64,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GameInfo {
     pub id: i64,
@@ -229,7 +252,7 @@ pub struct GameInfo {
     pub category_sections: Vec<CategorySection>,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileParsingRule {
     pub comment_strip_pattern: String,
@@ -239,7 +262,7 @@ pub struct FileParsingRule {
     pub id: i64,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CategorySection {
     pub id: i64,
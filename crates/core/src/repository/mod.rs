@@ -135,6 +135,25 @@ impl RepositoryPackage {
         })
     }
 
+    /// Creates a package from a `kind` and the `id` stored for it, which is the
+    /// source url for Git repositories.
+    pub fn from_kind_and_id(
+        flavor: Flavor,
+        kind: RepositoryKind,
+        id: String,
+    ) -> Result<Self, RepositoryError> {
+        match kind {
+            RepositoryKind::Git(_) => {
+                let url = id
+                    .parse::<Uri>()
+                    .map_err(|_| RepositoryError::GitInvalidUrl { url: id.clone() })?;
+
+                RepositoryPackage::from_source_url(flavor, url)
+            }
+            kind => RepositoryPackage::from_repo_id(flavor, kind, id),
+        }
+    }
+
     pub(crate) fn with_metadata(mut self, metadata: RepositoryMetadata) -> Self {
         self.metadata = metadata;
 
@@ -171,6 +190,14 @@ pub struct RepositoryMetadata {
 
     /// Remote packages available from the Repository
     pub(crate) remote_packages: HashMap<ReleaseChannel, RemotePackage>,
+
+    /// Fingerprint of each folder in the installed release. Only known for
+    /// Curse addons matched by fingerprint.
+    pub(crate) module_fingerprints: HashMap<String, u32>,
+
+    /// Set when the repository couldn't be reached and this metadata was
+    /// built from cached / local data instead
+    pub(crate) stale: bool,
 }
 
 impl RepositoryMetadata {
@@ -178,6 +205,16 @@ impl RepositoryMetadata {
         Default::default()
     }
 
+    /// Metadata used when the repository is unreachable. Only fields we
+    /// already know locally are set.
+    pub(crate) fn stale(title: Option<String>) -> Self {
+        RepositoryMetadata {
+            title,
+            stale: true,
+            ..Default::default()
+        }
+    }
+
     pub(crate) fn modules(&self) -> Vec<String> {
         let mut entries: Vec<_> = self.remote_packages.iter().collect();
         entries.sort_by_key(|(key, _)| *key);
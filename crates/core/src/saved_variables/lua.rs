@@ -0,0 +1,622 @@
+//! Parser and serializer for the Lua files WoW writes SavedVariables to. They
+//! only contain assignments of literal values to global names, eg.
+//!
+//! ```lua
+//! BagnonDB = {
+//!     ["version"] = "9.0.2",
+//!     ["profiles"] = {
+//!         "Default", -- [1]
+//!     },
+//! }
+//! ```
+
+use crate::error::ParseError;
+
+use std::fmt::{self, Display, Formatter, Write};
+
+/// A literal Lua value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Boolean(bool),
+    /// The number as written, so it serializes back unchanged.
+    Number(String),
+    /// Lua strings are bytes and aren't always valid UTF-8.
+    String(Vec<u8>),
+    Table(LuaTable),
+}
+
+/// A table constructor. Entries without a key are positional.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LuaTable {
+    pub entries: Vec<(Option<LuaValue>, LuaValue)>,
+}
+
+/// The global assignments of a SavedVariables file, in order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LuaChunk {
+    pub variables: Vec<(String, LuaValue)>,
+}
+
+impl LuaChunk {
+    /// Parses the contents of a SavedVariables file.
+    pub fn parse(input: &[u8]) -> Result<LuaChunk, ParseError> {
+        let mut parser = Parser { input, pos: 0 };
+        let mut variables = vec![];
+
+        loop {
+            parser.skip_whitespace()?;
+
+            if parser.is_eof() {
+                break;
+            }
+
+            let name = parser
+                .identifier()
+                .ok_or_else(|| parser.error("expected a variable name"))?;
+
+            parser.skip_whitespace()?;
+            parser.expect(b'=')?;
+
+            let value = parser.value()?;
+
+            parser.skip_whitespace()?;
+            parser.eat(b';');
+
+            variables.push((name, value));
+        }
+
+        Ok(LuaChunk { variables })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LuaValue> {
+        self.variables
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl Display for LuaChunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.variables {
+            write!(f, "\n{} = ", name)?;
+            write_value(f, value, 0)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl LuaValue {
+    /// Number of entries if this is a table.
+    pub fn entry_count(&self) -> Option<usize> {
+        match self {
+            LuaValue::Table(table) => Some(table.entries.len()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for LuaValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_value(f, self, 0)
+    }
+}
+
+fn write_value(f: &mut Formatter<'_>, value: &LuaValue, depth: usize) -> fmt::Result {
+    match value {
+        LuaValue::Nil => f.write_str("nil"),
+        LuaValue::Boolean(b) => write!(f, "{}", b),
+        LuaValue::Number(n) => f.write_str(n),
+        LuaValue::String(s) => write_string(f, s),
+        LuaValue::Table(table) => {
+            f.write_str("{\n")?;
+
+            let mut index = 0;
+
+            for (key, value) in &table.entries {
+                indent(f, depth + 1)?;
+
+                if let Some(key) = key {
+                    f.write_char('[')?;
+                    write_value(f, key, depth + 1)?;
+                    f.write_str("] = ")?;
+                    write_value(f, value, depth + 1)?;
+                    f.write_str(",\n")?;
+                } else {
+                    index += 1;
+
+                    write_value(f, value, depth + 1)?;
+                    writeln!(f, ", -- [{}]", index)?;
+                }
+            }
+
+            indent(f, depth)?;
+            f.write_char('}')
+        }
+    }
+}
+
+fn indent(f: &mut Formatter<'_>, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        f.write_char('\t')?;
+    }
+
+    Ok(())
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &[u8]) -> fmt::Result {
+    f.write_char('"')?;
+
+    let mut bytes = s.iter().peekable();
+
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            b'\n' => f.write_str("\\n")?,
+            b'\r' => f.write_str("\\r")?,
+            // Bytes are written as decimal escapes, padded when a digit
+            // follows so it isn't read as part of the escape
+            b if b < 0x20 || b == 0x7f => {
+                if matches!(bytes.peek(), Some(n) if n.is_ascii_digit()) {
+                    write!(f, "\\{:03}", b)?;
+                } else {
+                    write!(f, "\\{}", b)?;
+                }
+            }
+            _ => {
+                // Keep valid UTF-8 sequences as they are
+                let len = utf8_len(b);
+                let mut sequence = vec![b];
+
+                for _ in 1..len {
+                    match bytes.peek() {
+                        Some(&&n) if n & 0xc0 == 0x80 => {
+                            sequence.push(n);
+                            bytes.next();
+                        }
+                        _ => break,
+                    }
+                }
+
+                match std::str::from_utf8(&sequence) {
+                    Ok(s) => f.write_str(s)?,
+                    Err(_) => {
+                        for b in sequence {
+                            write!(f, "\\{}", b)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    f.write_char('"')
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn is_eof(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), ParseError> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", b as char)))
+        }
+    }
+
+    fn error(&self, reason: &str) -> ParseError {
+        let line = self.input[..self.pos.min(self.input.len())]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1;
+
+        ParseError::InvalidLua {
+            line,
+            reason: reason.to_owned(),
+        }
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.peek() {
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(b'-') if self.peek_at(1) == Some(b'-') => {
+                    self.pos += 2;
+
+                    if self.peek() == Some(b'[') && self.long_bracket_level().is_some() {
+                        self.long_string()?;
+                    } else {
+                        while let Some(b) = self.peek() {
+                            if b == b'\n' {
+                                break;
+                            }
+                            self.pos += 1;
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        let start = self.pos;
+
+        match self.peek() {
+            Some(b) if b.is_ascii_alphabetic() || b == b'_' => self.pos += 1,
+            _ => return None,
+        }
+
+        while let Some(b) = self.peek() {
+            if b.is_ascii_alphanumeric() || b == b'_' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        Some(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn value(&mut self) -> Result<LuaValue, ParseError> {
+        self.skip_whitespace()?;
+
+        match self.peek() {
+            Some(b'{') => self.table().map(LuaValue::Table),
+            Some(b'"') | Some(b'\'') => self.string().map(LuaValue::String),
+            Some(b'[') if self.long_bracket_level().is_some() => {
+                self.long_string().map(LuaValue::String)
+            }
+            Some(b) if b == b'-' || b == b'.' || b.is_ascii_digit() => self.number(),
+            Some(b) if b.is_ascii_alphabetic() => {
+                let start = self.pos;
+
+                match self.identifier().as_deref() {
+                    Some("nil") => Ok(LuaValue::Nil),
+                    Some("true") => Ok(LuaValue::Boolean(true)),
+                    Some("false") => Ok(LuaValue::Boolean(false)),
+                    // Written by WoW for infinite and undefined numbers
+                    Some("inf") | Some("nan") => Ok(LuaValue::Number(
+                        String::from_utf8_lossy(&self.input[start..self.pos]).into_owned(),
+                    )),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("expected a value"))
+                    }
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn table(&mut self) -> Result<LuaTable, ParseError> {
+        self.expect(b'{')?;
+
+        let mut table = LuaTable::default();
+
+        loop {
+            self.skip_whitespace()?;
+
+            if self.eat(b'}') {
+                return Ok(table);
+            }
+
+            let entry = if self.peek() == Some(b'[') && self.long_bracket_level().is_none() {
+                // `[key] = value`
+                self.pos += 1;
+                let key = self.value()?;
+
+                self.skip_whitespace()?;
+                self.expect(b']')?;
+                self.skip_whitespace()?;
+                self.expect(b'=')?;
+
+                (Some(key), self.value()?)
+            } else {
+                // `name = value` or a positional value
+                let start = self.pos;
+
+                match self.identifier() {
+                    Some(name) => {
+                        self.skip_whitespace()?;
+
+                        if self.peek() == Some(b'=') && self.peek_at(1) != Some(b'=') {
+                            self.pos += 1;
+
+                            (Some(LuaValue::String(name.into_bytes())), self.value()?)
+                        } else {
+                            self.pos = start;
+                            (None, self.value()?)
+                        }
+                    }
+                    None => (None, self.value()?),
+                }
+            };
+
+            table.entries.push(entry);
+
+            self.skip_whitespace()?;
+
+            if !self.eat(b',') && !self.eat(b';') {
+                self.skip_whitespace()?;
+                self.expect(b'}')?;
+
+                return Ok(table);
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<LuaValue, ParseError> {
+        let start = self.pos;
+
+        self.eat(b'-');
+
+        // `-inf` and `-nan`
+        if matches!(self.peek(), Some(b) if b.is_ascii_alphabetic()) {
+            return match self.identifier().as_deref() {
+                Some("inf") | Some("nan") => Ok(LuaValue::Number(
+                    String::from_utf8_lossy(&self.input[start..self.pos]).into_owned(),
+                )),
+                _ => Err(self.error("invalid number")),
+            };
+        }
+
+        let hex = self.peek() == Some(b'0') && matches!(self.peek_at(1), Some(b'x') | Some(b'X'));
+
+        if hex {
+            self.pos += 2;
+        }
+
+        while let Some(b) = self.peek() {
+            let is_exponent = if hex {
+                b == b'p' || b == b'P'
+            } else {
+                b == b'e' || b == b'E'
+            };
+
+            if is_exponent {
+                self.pos += 1;
+
+                if matches!(self.peek(), Some(b'+') | Some(b'-')) {
+                    self.pos += 1;
+                }
+            } else if (hex && b.is_ascii_hexdigit()) || b.is_ascii_digit() || b == b'.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+
+        if !text.bytes().any(|b| b.is_ascii_digit()) {
+            return Err(self.error("invalid number"));
+        }
+
+        Ok(LuaValue::Number(text))
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let quote = self.peek().ok_or_else(|| self.error("expected a string"))?;
+        self.pos += 1;
+
+        let mut s = vec![];
+
+        loop {
+            let b = self.peek().ok_or_else(|| self.error("unfinished string"))?;
+            self.pos += 1;
+
+            match b {
+                b if b == quote => return Ok(s),
+                b'\n' => return Err(self.error("unfinished string")),
+                b'\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("unfinished string"))?;
+                    self.pos += 1;
+
+                    match escape {
+                        b'n' | b'\n' => s.push(b'\n'),
+                        b't' => s.push(b'\t'),
+                        b'r' => s.push(b'\r'),
+                        b'a' => s.push(0x07),
+                        b'b' => s.push(0x08),
+                        b'f' => s.push(0x0c),
+                        b'v' => s.push(0x0b),
+                        b'\\' | b'"' | b'\'' => s.push(escape),
+                        b'x' => {
+                            let digits = self
+                                .input
+                                .get(self.pos..self.pos + 2)
+                                .and_then(|d| std::str::from_utf8(d).ok())
+                                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                                .ok_or_else(|| self.error("invalid escape"))?;
+
+                            self.pos += 2;
+                            s.push(digits);
+                        }
+                        b'z' => {
+                            while matches!(self.peek(), Some(b) if b.is_ascii_whitespace()) {
+                                self.pos += 1;
+                            }
+                        }
+                        b if b.is_ascii_digit() => {
+                            let mut value = u32::from(b - b'0');
+
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d) if d.is_ascii_digit() => {
+                                        value = value * 10 + u32::from(d - b'0');
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+
+                            if value > 255 {
+                                return Err(self.error("invalid escape"));
+                            }
+
+                            s.push(value as u8);
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                b => s.push(b),
+            }
+        }
+    }
+
+    /// Returns the level of the long bracket at the current position, eg. 2
+    /// for `[==[`.
+    fn long_bracket_level(&self) -> Option<usize> {
+        if self.peek() != Some(b'[') {
+            return None;
+        }
+
+        let mut level = 0;
+
+        loop {
+            match self.peek_at(level + 1) {
+                Some(b'=') => level += 1,
+                Some(b'[') => return Some(level),
+                _ => return None,
+            }
+        }
+    }
+
+    fn long_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let level = self
+            .long_bracket_level()
+            .ok_or_else(|| self.error("expected a string"))?;
+        self.pos += level + 2;
+
+        // A newline right after the opening bracket is skipped
+        self.eat(b'\r');
+        self.eat(b'\n');
+
+        let close = format!("]{}]", "=".repeat(level)).into_bytes();
+
+        let rest = &self.input[self.pos..];
+        let end = rest
+            .windows(close.len())
+            .position(|w| w == close.as_slice())
+            .ok_or_else(|| self.error("unfinished long string"))?;
+
+        let s = rest[..end].to_vec();
+        self.pos += end + close.len();
+
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let input = br#"
+BagnonDB = {
+	["version"] = "9.0.2",
+	["count"] = 3,
+	["scale"] = -1.5e-3,
+	["enabled"] = true,
+	["profiles"] = {
+		"Default", -- [1]
+		"Raid\"s\\n", -- [2]
+		nil, -- [3]
+	},
+	[42] = { name = 'x', [[long
+string]] },
+}
+BagnonCharacterDB = nil
+"#;
+
+        let chunk = LuaChunk::parse(input).unwrap();
+
+        assert_eq!(chunk.variables.len(), 2);
+
+        let db = chunk.get("BagnonDB").unwrap();
+        assert_eq!(db.entry_count(), Some(6));
+
+        match db {
+            LuaValue::Table(table) => {
+                assert_eq!(
+                    table.entries[2],
+                    (
+                        Some(LuaValue::String(b"scale".to_vec())),
+                        LuaValue::Number("-1.5e-3".to_owned())
+                    )
+                );
+            }
+            _ => panic!("expected a table"),
+        }
+
+        assert_eq!(chunk.get("BagnonCharacterDB"), Some(&LuaValue::Nil));
+
+        // Serializing and parsing again gives the same values
+        let serialized = chunk.to_string();
+        assert_eq!(LuaChunk::parse(serialized.as_bytes()).unwrap(), chunk);
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let value = LuaValue::String(b"a\"b\\c\nd\x01\x012\xe2\x9c\x93\xff".to_vec());
+        let serialized = format!("X = {}", value);
+
+        assert_eq!(serialized, "X = \"a\\\"b\\\\c\\nd\\1\\0012\u{2713}\\255\"");
+        assert_eq!(
+            LuaChunk::parse(serialized.as_bytes()).unwrap().get("X"),
+            Some(&value)
+        );
+    }
+
+    #[test]
+    fn test_invalid_lua() {
+        assert!(matches!(
+            LuaChunk::parse(b"Foo = {\n[\"a\"] = 1,\n[\"b\"] = "),
+            Err(ParseError::InvalidLua { line: 3, .. })
+        ));
+        assert!(LuaChunk::parse(b"Foo = \"unfinished").is_err());
+        assert!(LuaChunk::parse(b"Foo = os.exit()").is_err());
+    }
+}
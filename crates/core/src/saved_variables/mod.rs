@@ -0,0 +1,489 @@
+//! SavedVariables files in the WTF folder, where addons store their settings.
+//! Each addon folder has at most one file per account, and one per character.
+
+pub mod lua;
+
+use self::lua::LuaChunk;
+use crate::addon::{Addon, AddonFolder};
+use crate::backup::{backup_folders, BackupFolder, BackupOptions};
+use crate::config::{BackupCompression, BackupFormat};
+use crate::error::{FilesystemError, ParseError};
+use crate::fs::addon_folders;
+use crate::wtf::{find_characters, sub_folders, Character};
+
+use chrono::NaiveDateTime;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether a SavedVariables file is shared by an account or belongs to a
+/// single character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    Account(String),
+    Character(Character),
+}
+
+impl Scope {
+    /// Returns the name of the account, or `<name>-<realm>` of the character.
+    pub fn name(&self) -> String {
+        match self {
+            Scope::Account(account) => account.clone(),
+            Scope::Character(character) => format!("{}-{}", character.name, character.realm),
+        }
+    }
+
+    /// Returns the SavedVariables folder of the scope in `wtf_dir`.
+    pub fn saved_variables_dir(&self, wtf_dir: &Path) -> PathBuf {
+        match self {
+            Scope::Account(account) => wtf_dir.join("Account").join(account).join("SavedVariables"),
+            Scope::Character(character) => character.dir.join("SavedVariables"),
+        }
+    }
+
+    /// Returns the path of the SavedVariables file of `folder` in this scope.
+    pub fn saved_variables_path(&self, wtf_dir: &Path, folder: &str) -> PathBuf {
+        self.saved_variables_dir(wtf_dir)
+            .join(format!("{}.lua", folder))
+    }
+
+    /// Returns the variables `folder` declares for this scope in its `.toc`
+    /// file.
+    pub fn declared_variables<'a>(&self, folder: &'a AddonFolder) -> &'a [String] {
+        match self {
+            Scope::Account(_) => &folder.saved_variables,
+            Scope::Character(_) => &folder.saved_variables_per_character,
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Account(account) => write!(f, "{} (account)", account),
+            Scope::Character(character) => write!(f, "{}", character),
+        }
+    }
+}
+
+/// A SavedVariables file and a summary of what it contains.
+#[derive(Debug, Clone)]
+pub struct SavedVariablesFile {
+    /// Addon folder the file belongs to.
+    pub folder: String,
+    pub scope: Scope,
+    pub path: PathBuf,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Variables in the file, with the number of entries of those which are
+    /// tables.
+    pub variables: Vec<(String, Option<usize>)>,
+    /// Set if the file couldn't be parsed.
+    pub error: Option<String>,
+}
+
+/// SavedVariables of an addon folder which no installed addon declares for the
+/// scope, eg. left behind by a deleted addon.
+#[derive(Debug, Clone)]
+pub struct OrphanedSavedVariables {
+    pub folder: String,
+    pub scope: Scope,
+    /// The file along with its backups, eg. the `.lua.bak` WoW keeps of the
+    /// previous session.
+    pub paths: Vec<PathBuf>,
+    /// Size of all the files in bytes.
+    pub size: u64,
+}
+
+/// Returns every account and character in `wtf_dir`, accounts first.
+pub fn find_scopes(wtf_dir: &Path) -> Result<Vec<Scope>, FilesystemError> {
+    let mut scopes: Vec<_> = sub_folders(&wtf_dir.join("Account"))?
+        .into_iter()
+        .map(Scope::Account)
+        .collect();
+
+    scopes.extend(find_characters(wtf_dir)?.into_iter().map(Scope::Character));
+
+    Ok(scopes)
+}
+
+/// Finds the scope named `name` in `wtf_dir`. Characters are named
+/// `<name>-<realm>`, or just `<name>` if it's unique. `account` narrows the
+/// search down to a single account. Without `name`, the scope is the account,
+/// which must be given unless there is only one.
+pub fn find_scope(
+    wtf_dir: &Path,
+    account: Option<&str>,
+    name: Option<&str>,
+) -> Result<Scope, FilesystemError> {
+    let in_account = |a: &str| account.map_or(true, |account| account.eq_ignore_ascii_case(a));
+
+    let mut matches: Vec<_> = find_scopes(wtf_dir)?
+        .into_iter()
+        .filter(|scope| match (scope, name) {
+            (Scope::Account(a), None) => in_account(a),
+            (Scope::Character(c), Some(name)) => {
+                in_account(&c.account)
+                    && (c.name.eq_ignore_ascii_case(name)
+                        || format!("{}-{}", c.name, c.realm).eq_ignore_ascii_case(name))
+            }
+            _ => false,
+        })
+        .collect();
+
+    let name = name.or(account).unwrap_or_default().to_owned();
+
+    match matches.len() {
+        0 => Err(FilesystemError::UnknownAccountOrCharacter { name }),
+        1 => Ok(matches.remove(0)),
+        _ => Err(FilesystemError::AmbiguousCharacter { name }),
+    }
+}
+
+/// Returns the SavedVariables files of every folder of `addon` in `wtf_dir`.
+pub async fn load_saved_variables(
+    addon: Addon,
+    wtf_dir: PathBuf,
+) -> Result<Vec<SavedVariablesFile>, FilesystemError> {
+    let scopes = find_scopes(&wtf_dir)?;
+
+    let mut files = vec![];
+
+    for folder in addon.folders.iter() {
+        for scope in scopes.iter() {
+            if scope.declared_variables(folder).is_empty() {
+                continue;
+            }
+
+            let path = scope.saved_variables_path(&wtf_dir, &folder.id);
+
+            if !path.is_file() {
+                continue;
+            }
+
+            let size = fs::metadata(&path)?.len();
+
+            let (variables, error) = match read_saved_variables(&path) {
+                Ok(chunk) => (
+                    chunk
+                        .variables
+                        .into_iter()
+                        .map(|(name, value)| (name, value.entry_count()))
+                        .collect(),
+                    None,
+                ),
+                Err(error) => (vec![], Some(error.to_string())),
+            };
+
+            files.push(SavedVariablesFile {
+                folder: folder.id.clone(),
+                scope: scope.clone(),
+                path,
+                size,
+                variables,
+                error,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Finds the SavedVariables in `wtf_dir` which none of the `installed` folders
+/// declare for their scope. Files of folders in `addon_dir` or `parked_dir`
+/// which aren't in `installed`, eg. because their `.toc` couldn't be read, and
+/// of Blizzard's own addons are never orphaned.
+pub async fn find_orphaned_saved_variables(
+    wtf_dir: PathBuf,
+    addon_dir: PathBuf,
+    parked_dir: PathBuf,
+    installed: Vec<AddonFolder>,
+) -> Result<Vec<OrphanedSavedVariables>, FilesystemError> {
+    let mut unknown = addon_folders(&addon_dir)?;
+    unknown.extend(addon_folders(&parked_dir)?);
+    unknown.retain(|name| !installed.iter().any(|f| f.id.eq_ignore_ascii_case(name)));
+
+    let mut orphans: Vec<OrphanedSavedVariables> = vec![];
+
+    for scope in find_scopes(&wtf_dir)? {
+        let dir = scope.saved_variables_dir(&wtf_dir);
+
+        if !dir.is_dir() {
+            continue;
+        }
+
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let file_name = entry.file_name();
+            let folder = match file_name.to_str().and_then(saved_variables_folder) {
+                Some(folder) => folder,
+                None => continue,
+            };
+
+            let is_declared = installed.iter().any(|f| {
+                f.id.eq_ignore_ascii_case(folder) && !scope.declared_variables(f).is_empty()
+            });
+
+            if is_declared
+                || unknown.iter().any(|name| name.eq_ignore_ascii_case(folder))
+                || folder.to_ascii_lowercase().starts_with("blizzard_")
+            {
+                continue;
+            }
+
+            let size = entry.metadata()?.len();
+
+            match orphans
+                .iter_mut()
+                .find(|o| o.scope == scope && o.folder.eq_ignore_ascii_case(folder))
+            {
+                Some(orphan) => {
+                    orphan.paths.push(entry.path());
+                    orphan.size += size;
+                }
+                None => orphans.push(OrphanedSavedVariables {
+                    folder: folder.to_owned(),
+                    scope: scope.clone(),
+                    paths: vec![entry.path()],
+                    size,
+                }),
+            }
+        }
+    }
+
+    Ok(orphans)
+}
+
+/// Backs up the files of `orphans` to a .zip archive in `backup_dir`, then
+/// deletes them. Returns when the backup was taken.
+pub async fn delete_orphaned_saved_variables(
+    orphans: Vec<OrphanedSavedVariables>,
+    wow_dir: PathBuf,
+    backup_dir: PathBuf,
+    compression: BackupCompression,
+) -> Result<NaiveDateTime, FilesystemError> {
+    let paths: Vec<_> = orphans.into_iter().flat_map(|o| o.paths).collect();
+
+    fs::create_dir_all(&backup_dir)?;
+
+    // Everything is backed up and kept, regardless of the backup settings
+    let options = BackupOptions {
+        format: BackupFormat::Zip,
+        retention: Default::default(),
+        filter: Default::default(),
+        compression,
+    };
+
+    let as_of = backup_folders(
+        paths
+            .iter()
+            .map(|path| BackupFolder::new(path, &wow_dir))
+            .collect(),
+        backup_dir,
+        options,
+    )
+    .await?;
+
+    for path in &paths {
+        fs::remove_file(path)?;
+    }
+
+    log::debug!("deleted {} orphaned SavedVariables files", paths.len());
+
+    Ok(as_of)
+}
+
+/// Returns the addon folder a file in a SavedVariables folder belongs to, eg.
+/// `Bagnon` for `Bagnon.lua.bak`.
+fn saved_variables_folder(file_name: &str) -> Option<&str> {
+    let lowercase = file_name.to_ascii_lowercase();
+    let end = lowercase.rfind(".lua")?;
+
+    match &lowercase[end..] {
+        ".lua" | ".lua.bak" | ".lua.ajour.bak" if end > 0 => Some(&file_name[..end]),
+        _ => None,
+    }
+}
+
+/// Reads and parses the SavedVariables file at `path`.
+pub fn read_saved_variables(path: &Path) -> Result<LuaChunk, ParseError> {
+    LuaChunk::parse(&fs::read(path)?)
+}
+
+/// Copies the SavedVariables file at `src` to `dest`, after checking it can be
+/// parsed. If `dest` is a folder, the file keeps its name.
+pub async fn export_saved_variables(src: PathBuf, dest: PathBuf) -> Result<PathBuf, ParseError> {
+    read_saved_variables(&src)?;
+
+    let dest = match src.file_name() {
+        Some(name) if dest.is_dir() => dest.join(name),
+        _ => dest,
+    };
+
+    fs::copy(&src, &dest)?;
+
+    log::debug!("exported SavedVariables {:?} to {:?}", &src, &dest);
+
+    Ok(dest)
+}
+
+/// Imports the SavedVariables file at `src` as the file of `folder` in `scope`.
+/// The file must parse, and only contain variables `folder` declares for
+/// `scope`. An existing file is kept as `<folder>.lua.ajour.bak`, which is
+/// returned.
+pub async fn import_saved_variables(
+    src: PathBuf,
+    folder: AddonFolder,
+    scope: Scope,
+    wtf_dir: PathBuf,
+) -> Result<Option<PathBuf>, ParseError> {
+    let contents = fs::read(&src)?;
+    let chunk = LuaChunk::parse(&contents)?;
+
+    check_declared(&chunk, &folder.id, scope.declared_variables(&folder))?;
+
+    let dest = scope.saved_variables_path(&wtf_dir, &folder.id);
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let backup = if dest.exists() {
+        let backup = dest.with_extension("lua.ajour.bak");
+        fs::copy(&dest, &backup)?;
+
+        Some(backup)
+    } else {
+        None
+    };
+
+    // Written next to the file and renamed over it, so a failed write doesn't
+    // leave a truncated file behind
+    let tmp = dest.with_extension("lua.ajour.tmp");
+    fs::write(&tmp, &contents)?;
+    fs::rename(&tmp, &dest)?;
+
+    log::debug!("imported SavedVariables {:?} to {:?}", &src, &dest);
+
+    Ok(backup)
+}
+
+/// Returns an error for the first variable of `chunk` which isn't in
+/// `declared`.
+fn check_declared(chunk: &LuaChunk, folder: &str, declared: &[String]) -> Result<(), ParseError> {
+    match chunk
+        .variables
+        .iter()
+        .find(|(name, _)| !declared.contains(name))
+    {
+        Some((name, _)) => Err(ParseError::UndeclaredSavedVariable {
+            name: name.clone(),
+            folder: folder.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::test_dir;
+
+    #[test]
+    fn test_saved_variables_folder() {
+        assert_eq!(saved_variables_folder("Bagnon.lua"), Some("Bagnon"));
+        assert_eq!(saved_variables_folder("Bagnon.lua.bak"), Some("Bagnon"));
+        assert_eq!(
+            saved_variables_folder("Bagnon.LUA.ajour.bak"),
+            Some("Bagnon")
+        );
+        assert_eq!(saved_variables_folder("Bagnon.lua.ajour.tmp"), None);
+        assert_eq!(saved_variables_folder("Bagnon.txt"), None);
+        assert_eq!(saved_variables_folder(".lua"), None);
+    }
+
+    #[test]
+    fn test_check_declared() {
+        let chunk = LuaChunk::parse(b"BagnonDB = {}\nBagnonCharacterDB = nil").unwrap();
+
+        let declared = vec!["BagnonDB".to_owned(), "BagnonCharacterDB".to_owned()];
+        assert!(check_declared(&chunk, "Bagnon", &declared).is_ok());
+
+        let declared = vec!["BagnonDB".to_owned()];
+        assert!(matches!(
+            check_declared(&chunk, "Bagnon", &declared),
+            Err(ParseError::UndeclaredSavedVariable { name, .. }) if name == "BagnonCharacterDB"
+        ));
+    }
+
+    #[test]
+    fn test_find_orphaned_saved_variables() {
+        let dir = test_dir("find_orphaned_saved_variables");
+        let wtf_dir = dir.join("WTF");
+        let addon_dir = dir.join("AddOns");
+        let parked_dir = dir.join("Parked");
+
+        let account_dir = wtf_dir.join("Account/ACCOUNT/SavedVariables");
+        let character_dir = wtf_dir.join("Account/ACCOUNT/Realm/Name/SavedVariables");
+
+        fs::create_dir_all(&account_dir).unwrap();
+        for name in &[
+            "Details.lua",
+            "Old.lua",
+            "Old.lua.bak",
+            "Parked.lua",
+            "Unparsed.lua",
+            "Blizzard_CombatLog.lua",
+            "README.txt",
+        ] {
+            fs::write(account_dir.join(name), "").unwrap();
+        }
+        fs::create_dir_all(&character_dir).unwrap();
+        fs::write(character_dir.join("Details.lua"), "").unwrap();
+
+        fs::create_dir_all(addon_dir.join("Details")).unwrap();
+        fs::create_dir_all(addon_dir.join("Unparsed")).unwrap();
+        fs::create_dir_all(parked_dir.join("Parked")).unwrap();
+
+        // Details only declares account wide SavedVariables
+        let details = AddonFolder::new(
+            "Details".to_owned(),
+            "Details".to_owned(),
+            None,
+            addon_dir.join("Details"),
+            None,
+            None,
+            None,
+            Default::default(),
+            vec![],
+            vec!["DetailsDB".to_owned()],
+            vec![],
+        );
+
+        let mut orphans = async_std::task::block_on(find_orphaned_saved_variables(
+            wtf_dir,
+            addon_dir,
+            parked_dir,
+            vec![details],
+        ))
+        .unwrap();
+        orphans.sort_by(|a, b| a.folder.cmp(&b.folder));
+
+        assert_eq!(orphans.len(), 2);
+
+        assert_eq!(orphans[0].folder, "Details");
+        assert!(matches!(orphans[0].scope, Scope::Character(_)));
+        assert_eq!(orphans[0].paths, vec![character_dir.join("Details.lua")]);
+
+        assert_eq!(orphans[1].folder, "Old");
+        assert_eq!(orphans[1].scope, Scope::Account("ACCOUNT".to_owned()));
+        assert_eq!(orphans[1].paths.len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
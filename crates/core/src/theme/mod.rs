@@ -55,6 +55,27 @@ pub struct ColorPalette {
 }
 
 impl Theme {
+    /// Returns the themes shipped with Ajour, sorted by name.
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::alliance(),
+            Theme::ayu(),
+            Theme::dark(),
+            Theme::dracula(),
+            Theme::ferra(),
+            Theme::forest_night(),
+            Theme::gruvbox(),
+            Theme::horde(),
+            Theme::light(),
+            Theme::nord(),
+            Theme::one_dark(),
+            Theme::outrun(),
+            Theme::solarized_dark(),
+            Theme::solarized_light(),
+            Theme::sort(),
+        ]
+    }
+
     pub fn dark() -> Theme {
         Theme {
             name: "Dark".to_string(),
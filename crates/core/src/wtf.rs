@@ -0,0 +1,203 @@
+//! Accounts, realms and characters in the WTF folder, and the per character
+//! `AddOns.txt` which stores which addons are enabled.
+
+use crate::error::FilesystemError;
+
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A character, from `WTF/Account/<account>/<realm>/<character>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Character {
+    pub account: String,
+    pub realm: String,
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+impl Character {
+    /// Path of the `AddOns.txt` of the character.
+    pub fn addons_txt_path(&self) -> PathBuf {
+        self.dir.join("AddOns.txt")
+    }
+}
+
+impl Display for Character {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {} ({})", self.name, self.realm, self.account)
+    }
+}
+
+/// Returns the characters of every account in `wtf_dir`, sorted by account,
+/// realm and name.
+pub fn find_characters(wtf_dir: &Path) -> Result<Vec<Character>, FilesystemError> {
+    let mut characters = vec![];
+
+    for account in sub_folders(&wtf_dir.join("Account"))? {
+        let account_dir = wtf_dir.join("Account").join(&account);
+
+        for realm in sub_folders(&account_dir)? {
+            let realm_dir = account_dir.join(&realm);
+
+            for name in sub_folders(&realm_dir)? {
+                characters.push(Character {
+                    account: account.clone(),
+                    realm: realm.clone(),
+                    dir: realm_dir.join(&name),
+                    name,
+                });
+            }
+        }
+    }
+
+    Ok(characters)
+}
+
+/// Returns the sorted names of the folders in `dir`, leaving out
+/// `SavedVariables`.
+pub(crate) fn sub_folders(dir: &Path) -> Result<Vec<String>, FilesystemError> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut folders = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        if let Some(name) = entry.file_name().to_str() {
+            if !name.eq_ignore_ascii_case("SavedVariables") {
+                folders.push(name.to_owned());
+            }
+        }
+    }
+
+    folders.sort();
+
+    Ok(folders)
+}
+
+/// Enabled state of addons for a character, one `<folder>: enabled` or
+/// `<folder>: disabled` line per addon. Addons which aren't listed are
+/// enabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddOnsTxt {
+    entries: Vec<(String, bool)>,
+}
+
+impl AddOnsTxt {
+    pub fn parse(contents: &str) -> AddOnsTxt {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let mut split = line.trim().rsplitn(2, ':');
+                let state = split.next()?.trim();
+                let folder = split.next()?.trim();
+
+                if folder.is_empty() {
+                    return None;
+                }
+
+                Some((folder.to_owned(), !state.eq_ignore_ascii_case("disabled")))
+            })
+            .collect();
+
+        AddOnsTxt { entries }
+    }
+
+    /// Returns `true` if addon `folder` is enabled.
+    pub fn is_enabled(&self, folder: &str) -> bool {
+        self.entries
+            .iter()
+            .find(|(f, _)| f == folder)
+            .map_or(true, |(_, enabled)| *enabled)
+    }
+
+    pub fn set_enabled(&mut self, folder: &str, enabled: bool) {
+        match self.entries.iter_mut().find(|(f, _)| f == folder) {
+            Some(entry) => entry.1 = enabled,
+            None => self.entries.push((folder.to_owned(), enabled)),
+        }
+    }
+}
+
+impl Display for AddOnsTxt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (folder, enabled) in &self.entries {
+            let state = if *enabled { "enabled" } else { "disabled" };
+
+            writeln!(f, "{}: {}", folder, state)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the `AddOns.txt` of `character`. A character without one has every
+/// addon enabled.
+pub fn load_addons_txt(character: &Character) -> Result<AddOnsTxt, FilesystemError> {
+    let path = character.addons_txt_path();
+
+    if !path.exists() {
+        return Ok(AddOnsTxt::default());
+    }
+
+    Ok(AddOnsTxt::parse(&fs::read_to_string(path)?))
+}
+
+/// Saves `addons_txt` as the `AddOns.txt` of `character`. WoW overwrites it
+/// when the character logs out, so changes made while logged in are lost.
+pub fn save_addons_txt(
+    character: &Character,
+    addons_txt: &AddOnsTxt,
+) -> Result<(), FilesystemError> {
+    fs::write(character.addons_txt_path(), addons_txt.to_string())?;
+
+    log::debug!("saved AddOns.txt of {}", character);
+
+    Ok(())
+}
+
+/// Returns every character in `wtf_dir` along with its `AddOns.txt`.
+pub async fn load_characters(
+    wtf_dir: PathBuf,
+) -> Result<Vec<(Character, AddOnsTxt)>, FilesystemError> {
+    find_characters(&wtf_dir)?
+        .into_iter()
+        .map(|character| {
+            let addons_txt = load_addons_txt(&character)?;
+
+            Ok((character, addons_txt))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addons_txt() {
+        let mut addons_txt =
+            AddOnsTxt::parse("Bagnon: enabled\r\nDetails: disabled\nWeakAuras : enabled\n\n");
+
+        assert!(addons_txt.is_enabled("Bagnon"));
+        assert!(!addons_txt.is_enabled("Details"));
+        assert!(addons_txt.is_enabled("WeakAuras"));
+        // Not listed
+        assert!(addons_txt.is_enabled("Plater"));
+
+        addons_txt.set_enabled("Bagnon", false);
+        addons_txt.set_enabled("Plater", false);
+
+        assert_eq!(
+            addons_txt.to_string(),
+            "Bagnon: disabled\nDetails: disabled\nWeakAuras: enabled\nPlater: disabled\n"
+        );
+    }
+}
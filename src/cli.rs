@@ -1,8 +1,8 @@
 use crate::VERSION;
 
-use ajour_core::config::Flavor;
+use ajour_core::config::{BackupCompression, BackupFormat, Flavor};
+use ajour_core::repository::ReleaseChannel;
 
-use isahc::http::Uri;
 use structopt::{
     clap::{self, AppSettings},
     StructOpt,
@@ -83,15 +83,203 @@ pub struct Opts {
 #[derive(Debug, StructOpt)]
 pub enum Command {
     /// Update all addons from the command line then exit
-    Update,
+    Update {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to update addons for. If ommited, all flavors will be included.
+        flavor: Option<Flavor>,
+        #[structopt()]
+        /// space separated list of addons to update, by folder name or title. If ommited, all addons will be updated.
+        addons: Vec<String>,
+        #[structopt(short, long, number_of_values = 1)]
+        /// addon to skip, by folder name or title. Can be given multiple times.
+        exclude: Vec<String>,
+        #[structopt(long)]
+        /// only check for updates, without updating
+        dry_run: bool,
+        #[structopt(long, parse(try_from_str = str_to_report_format), possible_values = &["json"])]
+        /// print a report of updated, failed and skipped addons
+        report: Option<ReportFormat>,
+    },
     /// Install an addon from the command line
     Install {
         #[structopt(parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
         /// flavor to install addon under
         flavor: Flavor,
         #[structopt()]
-        /// source url [Github & Gitlab currently supported]
-        url: Uri,
+        /// addon to install. Can be `curse:<id>`, `wowi:<id>`, `tukui:<id>`, an addon name from the catalog,
+        /// a Github / Gitlab url or a CurseForge / WoWInterface / Tukui addon page url
+        addon: String,
+        #[structopt(short, long, default_value = "stable", parse(try_from_str = str_to_release_channel), possible_values = &["stable","beta","alpha"])]
+        /// release channel to install
+        channel: ReleaseChannel,
+    },
+    /// List installed addons
+    List {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to list addons for. If ommited, all flavors will be included.
+        flavor: Option<Flavor>,
+        #[structopt(long)]
+        /// only include addons with an update available
+        outdated: bool,
+        #[structopt(long)]
+        /// only include addons not linked to a repository
+        unknown: bool,
+        #[structopt(long)]
+        /// only include ignored addons
+        ignored: bool,
+        #[structopt(long, default_value = "table", parse(try_from_str = str_to_output_format), possible_values = &["table","json","csv"])]
+        /// output format
+        format: OutputFormat,
+    },
+    /// Delete addons from disk
+    Remove {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addons. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(required = true)]
+        /// space separated list of addons, by folder name or title
+        addons: Vec<String>,
+    },
+    /// Ignore addons so they are skipped when updating
+    Ignore {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addons. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(required = true)]
+        /// space separated list of addons, by folder name or title
+        addons: Vec<String>,
+    },
+    /// Stop ignoring addons
+    Unignore {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addons. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(required = true)]
+        /// space separated list of addons, by folder name or title
+        addons: Vec<String>,
+    },
+    /// Set the release channel addons are updated from
+    Channel {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addons. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(parse(try_from_str = str_to_release_channel), possible_values = &["stable","beta","alpha"])]
+        /// release channel to use
+        channel: ReleaseChannel,
+        #[structopt(required = true)]
+        /// space separated list of addons, by folder name or title
+        addons: Vec<String>,
+    },
+    /// Revert addons to the version before they were last updated
+    Revert {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addons. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(required = true)]
+        /// space separated list of addons, by folder name or title
+        addons: Vec<String>,
+    },
+    /// Save the installed addons to a manifest, to install the same addons elsewhere
+    Export {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to export addons for. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt()]
+        /// file to save the manifest to. Saved as JSON if it ends in `.json`, otherwise as YAML
+        path: PathBuf,
+    },
+    /// Install the addons in a manifest which aren't installed yet
+    Import {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to install addons for. If ommited, the flavor of the manifest is used.
+        flavor: Option<Flavor>,
+        #[structopt(long)]
+        /// only list the addons which would be installed
+        dry_run: bool,
+        #[structopt()]
+        /// manifest to import
+        path: PathBuf,
+    },
+    /// Record the exact release of each installed addon in a lockfile
+    Lock {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to lock addons for. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt()]
+        /// file to save the lockfile to. If ommited, the lockfile of the flavor in the config folder is used.
+        path: Option<PathBuf>,
+    },
+    /// Install the addons in a lockfile
+    Sync {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to install addons for. If ommited, the flavor of the lockfile is used.
+        flavor: Option<Flavor>,
+        #[structopt(long)]
+        /// install exactly the locked releases, reinstalling addons which don't match the lockfile
+        locked: bool,
+        #[structopt(long)]
+        /// only list the addons which would be installed
+        dry_run: bool,
+        #[structopt()]
+        /// lockfile to install from. If ommited, the lockfile of the flavor in the config folder is used.
+        path: Option<PathBuf>,
+    },
+    /// Switch between named sets of addons
+    Profile {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the profiles. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Inspect, export and import the SavedVariables of an addon
+    SavedVariables {
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor of the addon. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(subcommand)]
+        command: SavedVariablesCommand,
+    },
+    /// Get and set config values
+    Config {
+        #[structopt(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Restore your WTF and/or AddOns folders from a backup
+    Restore {
+        #[structopt(short, long, default_value = "both", parse(try_from_str = str_to_backup_folder), possible_values = &["both","wtf","addons"])]
+        /// folder to restore
+        backup_folder: BackupFolder,
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// space separated list of flavors to restore. If ommited, all flavors in the backup will be restored.
+        flavors: Vec<Flavor>,
+        #[structopt(short, long)]
+        /// list the contents of the backup without restoring
+        list: bool,
+        #[structopt()]
+        /// backup archive or incremental snapshot to restore
+        archive: PathBuf,
+    },
+    /// Search the addon catalog
+    Search {
+        #[structopt()]
+        /// search query. Supports `source:<source>` and `category:<category>` filters
+        query: String,
+        #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
+        /// flavor to search addons for. If ommited, the flavor selected in Ajour is used.
+        flavor: Option<Flavor>,
+        #[structopt(short, long)]
+        /// only include addons from this source [curse, tukui, wowi or a custom catalog name]
+        source: Option<String>,
+        #[structopt(short, long)]
+        /// only include addons in this category
+        category: Option<String>,
+        #[structopt(short, long, default_value = "25")]
+        /// maximum number of results
+        limit: usize,
+        #[structopt(long)]
+        /// print results as json
+        json: bool,
     },
     /// Backup your WTF and/or AddOns folders
     Backup {
@@ -101,10 +289,136 @@ pub enum Command {
         #[structopt(short, long, parse(try_from_str = str_to_flavor), possible_values = &["retail","ptr","beta","classic","classic_ptr"])]
         /// space separated list of flavors to include in backup. If ommited, all flavors will be included.
         flavors: Vec<Flavor>,
+        #[structopt(long, parse(try_from_str = str_to_backup_format), possible_values = &["zip","incremental"])]
+        /// how to save the backup. If ommited, the format set in Ajour is used.
+        format: Option<BackupFormat>,
+        #[structopt(long, number_of_values = 1)]
+        /// only back up files matching this pattern, or in a folder matching it. Can be given multiple times.
+        include: Vec<String>,
+        #[structopt(long, number_of_values = 1)]
+        /// skip files and folders matching this pattern, eg. `*.bak` or `Cache`. Can be given multiple times.
+        exclude: Vec<String>,
+        #[structopt(long, parse(try_from_str = str_to_backup_compression), possible_values = &["stored","deflated","bzip2"])]
+        /// compression of .zip archives. If ommited, the compression set in Ajour is used.
+        compression: Option<BackupCompression>,
         #[structopt()]
         /// folder to save backups to
         destination: PathBuf,
     },
+    /// Save a backup as a .zip archive, eg. to copy an incremental backup elsewhere
+    ExportBackup {
+        #[structopt()]
+        /// backup archive or incremental snapshot (`ajour_snapshots/*.json`) to export
+        archive: PathBuf,
+        #[structopt()]
+        /// folder to save the .zip archive to
+        destination: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCommand {
+    /// Print the value of a key
+    Get {
+        #[structopt()]
+        /// config key, eg. `wow.directory`
+        key: String,
+    },
+    /// Set the value of a key
+    Set {
+        #[structopt()]
+        /// config key, eg. `wow.directory`
+        key: String,
+        #[structopt()]
+        /// value to set
+        value: String,
+    },
+    /// Reset a key to its default value
+    Unset {
+        #[structopt()]
+        /// config key, eg. `wow.directory`
+        key: String,
+    },
+    /// Print all keys which have a value
+    List,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ProfileCommand {
+    /// List the profiles, the active profile is marked with `*`
+    List,
+    /// Save the addons in the AddOns folder as a profile
+    Save {
+        #[structopt()]
+        /// name of the profile
+        name: String,
+    },
+    /// Switch to a profile, parking the addons which aren't part of it
+    Switch {
+        #[structopt()]
+        /// name of the profile
+        name: String,
+    },
+    /// Move all parked addons back into the AddOns folder
+    Reset,
+    /// Delete a profile
+    Delete {
+        #[structopt()]
+        /// name of the profile
+        name: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SavedVariablesCommand {
+    /// List the SavedVariables files of an addon with their size and variables
+    List {
+        #[structopt()]
+        /// addon title or folder id
+        addon: String,
+    },
+    /// Copy the SavedVariables of an account or character to a file
+    Export {
+        #[structopt(long)]
+        /// addon folder the SavedVariables belong to. If ommited, the primary folder is used.
+        folder: Option<String>,
+        #[structopt(long)]
+        /// account to export from. Can be ommited if there is only one.
+        account: Option<String>,
+        #[structopt(long)]
+        /// character to export from, as `<name>` or `<name>-<realm>`. If ommited, the account wide SavedVariables are exported.
+        character: Option<String>,
+        #[structopt()]
+        /// addon title or folder id
+        addon: String,
+        #[structopt()]
+        /// file or folder to export to
+        destination: PathBuf,
+    },
+    /// Replace the SavedVariables of an account or character with an exported file
+    Import {
+        #[structopt(long)]
+        /// addon folder the SavedVariables belong to. If ommited, the primary folder is used.
+        folder: Option<String>,
+        #[structopt(long)]
+        /// account to import to. Can be ommited if there is only one.
+        account: Option<String>,
+        #[structopt(long)]
+        /// character to import to, as `<name>` or `<name>-<realm>`. If ommited, the account wide SavedVariables are replaced.
+        character: Option<String>,
+        #[structopt()]
+        /// addon title or folder id
+        addon: String,
+        #[structopt()]
+        /// exported SavedVariables file
+        path: PathBuf,
+    },
+    /// List the SavedVariables which no installed addon uses, eg. of deleted addons
+    Orphaned {
+        #[structopt(long)]
+        /// back up the orphaned SavedVariables, then delete them
+        delete: bool,
+    },
 }
 
 fn str_to_flavor(s: &str) -> Result<Flavor, &'static str> {
@@ -118,6 +432,15 @@ fn str_to_flavor(s: &str) -> Result<Flavor, &'static str> {
     }
 }
 
+fn str_to_release_channel(s: &str) -> Result<ReleaseChannel, &'static str> {
+    match s {
+        "stable" => Ok(ReleaseChannel::Stable),
+        "beta" => Ok(ReleaseChannel::Beta),
+        "alpha" => Ok(ReleaseChannel::Alpha),
+        _ => Err("valid values are ['stable','beta','alpha']"),
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum BackupFolder {
     Both,
@@ -133,3 +456,48 @@ fn str_to_backup_folder(s: &str) -> Result<BackupFolder, &'static str> {
         _ => Err("valid values are ['both','wtf','addons']"),
     }
 }
+
+fn str_to_backup_format(s: &str) -> Result<BackupFormat, &'static str> {
+    match s {
+        "zip" => Ok(BackupFormat::Zip),
+        "incremental" => Ok(BackupFormat::Incremental),
+        _ => Err("valid values are ['zip','incremental']"),
+    }
+}
+
+fn str_to_backup_compression(s: &str) -> Result<BackupCompression, &'static str> {
+    match s {
+        "stored" => Ok(BackupCompression::Stored),
+        "deflated" => Ok(BackupCompression::Deflated),
+        "bzip2" => Ok(BackupCompression::Bzip2),
+        _ => Err("valid values are ['stored','deflated','bzip2']"),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+fn str_to_output_format(s: &str) -> Result<OutputFormat, &'static str> {
+    match s {
+        "table" => Ok(OutputFormat::Table),
+        "json" => Ok(OutputFormat::Json),
+        "csv" => Ok(OutputFormat::Csv),
+        _ => Err("valid values are ['table','json','csv']"),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+}
+
+fn str_to_report_format(s: &str) -> Result<ReportFormat, &'static str> {
+    match s {
+        "json" => Ok(ReportFormat::Json),
+        _ => Err("valid values are ['json']"),
+    }
+}
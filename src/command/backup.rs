@@ -1,17 +1,23 @@
 use crate::cli::BackupFolder;
 use crate::Result;
 
-use ajour_core::backup::{self, backup_folders};
-use ajour_core::config::{load_config, Flavor};
+use ajour_core::backup::{self, backup_folders, BackupOptions};
+use ajour_core::config::{load_config, BackupCompression, BackupFormat, Flavor};
 use anyhow::format_err;
 
 use async_std::task;
 use std::path::PathBuf;
 
+/// Back up folders to `destination`. `format`, `compression` and the include
+/// and exclude patterns override or add to the options set in the config.
 pub fn backup(
     backup_folder: BackupFolder,
     destination: PathBuf,
     flavors: Vec<Flavor>,
+    format: Option<BackupFormat>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    compression: Option<BackupCompression>,
 ) -> Result<()> {
     task::block_on(async {
         let config = load_config().await?;
@@ -26,7 +32,7 @@ pub fn backup(
             return Err(format_err!("destination must be a folder, not a file"));
         }
 
-        let wow_dir = config.wow.directory.as_ref().ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
+        let wow_dir = config.wow.directory.as_ref().ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
 
         log::info!(
             "Backing up:\n\tbackup folders: {:?}\n\tflavors: {:?}\n\tdestination: {:?}",
@@ -38,8 +44,8 @@ pub fn backup(
         let mut src_folders = vec![];
 
         for flavor in flavors {
-            let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
-            let wtf_directory = config.get_wtf_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
+            let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+            let wtf_directory = config.get_wtf_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
 
             let addons_folder = backup::BackupFolder::new(&addon_directory, &wow_dir);
             let wtf_folder = backup::BackupFolder::new(&wtf_directory, &wow_dir);
@@ -64,10 +70,39 @@ pub fn backup(
             }
         }
 
-        backup_folders(src_folders, destination).await?;
+        let mut options = BackupOptions::from_config(&config);
+        options.format = format.unwrap_or(options.format);
+        options.compression = compression.unwrap_or(options.compression);
+        options.filter.include.extend(include);
+        options.filter.exclude.extend(exclude);
+
+        log::debug!("backup options: {:?}", options);
+
+        backup_folders(src_folders, destination, options).await?;
 
         log::info!("Backup complete!");
 
         Ok(())
     })
 }
+
+pub fn export_backup(archive: PathBuf, destination: PathBuf) -> Result<()> {
+    task::block_on(async {
+        if !archive.is_file() {
+            return Err(format_err!("archive must be a file: {:?}", archive));
+        }
+
+        if !destination.is_dir() {
+            return Err(format_err!("destination must be a folder, not a file"));
+        }
+
+        let config = load_config().await?;
+
+        let exported =
+            backup::export_backup(archive, destination, config.backup_compression).await?;
+
+        log::info!("Exported to {:?}", exported);
+
+        Ok(())
+    })
+}
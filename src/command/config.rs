@@ -0,0 +1,54 @@
+use crate::cli::ConfigCommand;
+use crate::Result;
+
+use ajour_core::config::load_config;
+use ajour_core::fs::PersistentData;
+use ajour_core::theme::{load_user_themes, Theme};
+
+use anyhow::format_err;
+use async_std::task;
+
+pub fn config(command: ConfigCommand) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+
+        match command {
+            ConfigCommand::Get { key } => match config.get_key(&key)? {
+                Some(value) => println!("{}", value),
+                None => log::info!("{} is not set", key),
+            },
+            ConfigCommand::Set { key, value } => {
+                if key == "theme" {
+                    let mut themes = Theme::built_in();
+                    themes.extend(load_user_themes().await);
+
+                    if !themes.iter().any(|t| t.name == value) {
+                        return Err(format_err!(
+                            "Unknown theme {:?}, available themes: {}",
+                            value,
+                            themes
+                                .iter()
+                                .map(|t| t.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                }
+
+                config.set_key(&key, &value)?;
+                config.save()?;
+            }
+            ConfigCommand::Unset { key } => {
+                config.unset_key(&key)?;
+                config.save()?;
+            }
+            ConfigCommand::List => {
+                for (key, value) in config.list_keys() {
+                    println!("{} = {}", key, value);
+                }
+            }
+        }
+
+        Result::Ok(())
+    })
+}
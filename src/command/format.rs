@@ -0,0 +1,46 @@
+/// Prints `rows` as columns aligned to the widest cell, with `header` on top.
+pub(super) fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<_> = header.iter().map(|h| h.chars().count()).collect();
+
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    println!("{}", format_row(header.to_vec()));
+
+    for row in rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// Prints `rows` as csv, with `header` on the first line.
+pub(super) fn print_csv(header: &[&str], rows: &[Vec<String>]) {
+    println!("{}", header.join(","));
+
+    for row in rows {
+        let cells: Vec<_> = row.iter().map(|cell| escape_csv(cell)).collect();
+
+        println!("{}", cells.join(","));
+    }
+}
+
+fn escape_csv(cell: &str) -> String {
+    if cell.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_owned()
+    }
+}
@@ -1,14 +1,17 @@
+use super::search::load_catalog;
 use crate::{log_error, Result};
 
 use ajour_core::addon::Addon;
 use ajour_core::cache::{
-    load_addon_cache, load_fingerprint_cache, update_addon_cache, AddonCacheEntry,
+    cache_installed_addon, load_addon_cache, load_fingerprint_cache, AddonCache, FingerprintCache,
 };
-use ajour_core::config::{load_config, Flavor};
-use ajour_core::fs::install_addon;
+use ajour_core::catalog::{Catalog, CatalogAddon, Source};
+use ajour_core::config::{load_config, Config, Flavor};
+use ajour_core::fs::{install_addon, PersistentData};
+use ajour_core::lockfile::lock_installed_addon;
 use ajour_core::network::download_addon;
 use ajour_core::parse::update_addon_fingerprint;
-use ajour_core::repository::RepositoryPackage;
+use ajour_core::repository::{ReleaseChannel, RepositoryKind, RepositoryPackage};
 
 use anyhow::{format_err, Context};
 use async_std::sync::{Arc, Mutex};
@@ -17,83 +20,436 @@ use futures::future::join_all;
 use isahc::http::Uri;
 
 use std::collections::hash_map::DefaultHasher;
-use std::convert::TryFrom;
 use std::hash::Hasher;
 
-pub fn install_from_source(url: Uri, flavor: Flavor) -> Result<()> {
+/// Install an addon from `addon`, which can be:
+///
+/// - `curse:<id>`, `wowi:<id>` or `tukui:<id>`
+/// - the name of an addon in the catalog
+/// - a GitHub or GitLab repository url
+/// - a CurseForge, WoWInterface or Tukui addon page url
+pub fn install(addon: String, flavor: Flavor, release_channel: ReleaseChannel) -> Result<()> {
     task::block_on(async {
-        log::debug!("Fetching remote info for {:?}", &url);
+        log::debug!("Fetching remote info for {:?}", &addon);
 
-        // Will use hash of url as temp name to download zip as
-        let mut hasher = DefaultHasher::new();
-        hasher.write(url.to_string().as_bytes());
-        let url_hash = hasher.finish();
-
-        let config = load_config().await?;
+        let mut config = load_config().await?;
         let addon_cache = Arc::new(Mutex::new(load_addon_cache().await?));
         let fingerprint_cache = Arc::new(Mutex::new(load_fingerprint_cache().await?));
 
-        // Fetch the remote repo metadata
-        let mut repo_package = RepositoryPackage::from_source_url(Flavor::Retail, url)?;
-        repo_package.resolve_metadata().await?;
+        let repo_package = resolve_repo_package(&config, &addon, flavor).await?;
 
-        // Build an addon using this repo package
-        let mut addon = Addon::empty(&format!("{}", url_hash));
-        addon.set_repository(repo_package);
+        install_package(
+            &mut config,
+            addon_cache,
+            fingerprint_cache,
+            repo_package,
+            flavor,
+            release_channel,
+        )
+        .await?;
 
-        log::debug!("Installing {} for {:?}", addon.title(), flavor);
+        log::debug!("Addon successfully installed!");
 
-        let download_directory = config.get_download_directory_for_flavor(flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
-        let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
+        Result::Ok(())
+    })
+}
 
-        // Download the addon
-        download_addon(&addon, &download_directory).await?;
-        log::debug!("Addon downloaded");
+/// Downloads and installs the `release_channel` release of `repo_package` for
+/// `flavor`, then adds the installed addon to the caches.
+pub(super) async fn install_package(
+    config: &mut Config,
+    addon_cache: Arc<Mutex<AddonCache>>,
+    fingerprint_cache: Arc<Mutex<FingerprintCache>>,
+    mut repo_package: RepositoryPackage,
+    flavor: Flavor,
+    release_channel: ReleaseChannel,
+) -> Result<Addon> {
+    // Will use hash of the repository id as temp name to download zip as
+    let mut hasher = DefaultHasher::new();
+    hasher.write(repo_package.id.as_bytes());
+    let addon_hash = hasher.finish();
 
-        // Install the addon and update Addon with the unpacked folders
-        let addon_folders = install_addon(&addon, &download_directory, &addon_directory).await?;
-        log::debug!("Addon unpacked");
+    // Fetch the remote repo metadata
+    repo_package.resolve_metadata().await?;
 
-        addon.update_addon_folders(addon_folders);
+    // Build an addon using this repo package
+    let mut addon = Addon::empty(&format!("{}", addon_hash));
+    addon.set_repository(repo_package);
+    addon.release_channel = release_channel;
 
-        // Update cache with new entry
-        if let Ok(entry) = AddonCacheEntry::try_from(&addon) {
-            update_addon_cache(addon_cache.clone(), entry, flavor).await?;
-        }
+    if addon.relevant_release_package().is_none() {
+        log::warn!(
+            "{} has no {} release, installing latest available release",
+            addon.title(),
+            release_channel
+        );
+    }
+
+    let addon = install_release(config, addon_cache, fingerprint_cache, addon, flavor).await?;
+
+    // Persist the release channel so updates stay on the same channel
+    if release_channel != ReleaseChannel::default() {
+        config
+            .addons
+            .release_channels
+            .entry(flavor)
+            .or_default()
+            .insert(addon.primary_folder_id.clone(), release_channel);
+
+        config.save()?;
+    }
+
+    Ok(addon)
+}
+
+/// Downloads and installs the release `addon` resolves to for `flavor`, then
+/// adds the installed addon to the caches and the lockfile.
+pub(super) async fn install_release(
+    config: &Config,
+    addon_cache: Arc<Mutex<AddonCache>>,
+    fingerprint_cache: Arc<Mutex<FingerprintCache>>,
+    mut addon: Addon,
+    flavor: Flavor,
+) -> Result<Addon> {
+    log::debug!("Installing {} for {:?}", addon.title(), flavor);
+
+    let download_directory = config.get_download_directory_for_flavor(flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+    let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+    // Download the addon
+    download_addon(&addon, &download_directory).await?;
+    log::debug!("Addon downloaded");
+
+    // Install the addon and update Addon with the unpacked folders
+    let addon_folders = install_addon(&addon, &download_directory, &addon_directory).await?;
+    log::debug!("Addon unpacked");
+
+    addon.update_addon_folders(addon_folders);
+
+    // Update cache with new entry
+    if addon.repository_kind().is_some() {
+        cache_installed_addon(
+            addon_cache.clone(),
+            addon.clone(),
+            addon_directory.clone(),
+            flavor,
+        )
+        .await?;
+    }
+
+    // Stores each folder name we need to fingerprint
+    let mut folders_to_fingerprint = vec![];
 
-        // Stores each folder name we need to fingerprint
-        let mut folders_to_fingerprint = vec![];
+    // Store all folder names
+    folders_to_fingerprint.extend(addon.folders.iter().map(|f| {
+        (
+            fingerprint_cache.clone(),
+            flavor,
+            &addon_directory,
+            f.id.clone(),
+        )
+    }));
 
-        // Store all folder names
-        folders_to_fingerprint.extend(addon.folders.iter().map(|f| {
+    // Call `update_addon_fingerprint` on each folder concurrently
+    for (addon_dir, result) in join_all(folders_to_fingerprint.into_iter().map(
+        |(fingerprint_cache, flavor, addon_dir, addon_id)| async move {
             (
-                fingerprint_cache.clone(),
-                flavor,
-                &addon_directory,
-                f.id.clone(),
+                addon_dir,
+                update_addon_fingerprint(fingerprint_cache, flavor, addon_dir, addon_id).await,
             )
-        }));
-
-        // Call `update_addon_fingerprint` on each folder concurrently
-        for (addon_dir, result) in join_all(folders_to_fingerprint.into_iter().map(
-            |(fingerprint_cache, flavor, addon_dir, addon_id)| async move {
-                (
-                    addon_dir,
-                    update_addon_fingerprint(fingerprint_cache, flavor, addon_dir, addon_id).await,
-                )
-            },
-        ))
+        },
+    ))
+    .await
+    {
+        if let Err(e) = result.context(format!("failed to fingerprint folder: {:?}", addon_dir)) {
+            // Log any errors fingerprinting the folder
+            log_error(&e);
+        }
+    }
+
+    // Record the installed release in the lockfile
+    if let Err(e) = lock_installed_addon(flavor, addon.clone(), addon_directory.clone())
         .await
-        {
-            if let Err(e) = result.context(format!("failed to fingerprint folder: {:?}", addon_dir))
-            {
-                // Log any errors fingerprinting the folder
-                log_error(&e);
-            }
+        .context(format!("failed to lock {}", addon.title()))
+    {
+        log_error(&e);
+    }
+
+    Ok(addon)
+}
+
+/// Resolves `addon` into a `RepositoryPackage` for `flavor`.
+async fn resolve_repo_package(
+    config: &Config,
+    addon: &str,
+    flavor: Flavor,
+) -> Result<RepositoryPackage> {
+    if let Some((kind, id)) = repo_id_from_prefix(addon) {
+        return Ok(RepositoryPackage::from_repo_id(flavor, kind, id)?);
+    }
+
+    let url = addon.parse::<Uri>().ok().filter(|url| url.host().is_some());
+
+    if let Some(url) = url {
+        let host = url.host().unwrap_or_default().trim_start_matches("www.");
+
+        if host == "github.com" || host == "gitlab.com" {
+            return Ok(RepositoryPackage::from_source_url(flavor, url)?);
         }
 
-        log::debug!("Addon successfully installed!");
+        // CurseForge urls only contain the slug, so we look up the id in the
+        // catalog. The id is part of WoWInterface and Tukui urls.
+        let catalog = load_catalog(config).await?;
 
-        Result::Ok(())
-    })
+        let (kind, id) = find_by_website_url(&catalog, &url, flavor)
+            .map(repo_id_from_catalog_addon)
+            .or_else(|| repo_id_from_website_url(&url))
+            .ok_or_else(|| format_err!("No addon found for {} for {}", url, flavor))?;
+
+        return Ok(RepositoryPackage::from_repo_id(flavor, kind, id)?);
+    }
+
+    // Catalog name
+    let catalog = load_catalog(config).await?;
+
+    let (kind, id) = catalog
+        .addons
+        .iter()
+        .filter(|a| a.name.eq_ignore_ascii_case(addon))
+        .filter(|a| {
+            a.game_versions
+                .iter()
+                .any(|gv| gv.flavor == flavor.base_flavor())
+        })
+        .max_by_key(|a| a.number_of_downloads)
+        .map(repo_id_from_catalog_addon)
+        .ok_or_else(|| {
+            format_err!(
+                "No addon named {} found in the catalog for {}. Use `ajour search` to find the addon.",
+                addon,
+                flavor
+            )
+        })?;
+
+    Ok(RepositoryPackage::from_repo_id(flavor, kind, id)?)
+}
+
+/// Parses `curse:<id>`, `wowi:<id>` or `tukui:<id>`.
+fn repo_id_from_prefix(addon: &str) -> Option<(RepositoryKind, String)> {
+    let (prefix, id) = split_once(addon, ':')?;
+
+    let kind = match prefix.to_lowercase().as_str() {
+        "curse" => RepositoryKind::Curse,
+        "wowi" => RepositoryKind::WowI,
+        "tukui" => RepositoryKind::Tukui,
+        _ => return None,
+    };
+
+    Some((kind, id.trim().to_owned())).filter(|(_, id)| !id.is_empty())
+}
+
+fn find_by_website_url<'a>(
+    catalog: &'a Catalog,
+    url: &Uri,
+    flavor: Flavor,
+) -> Option<&'a CatalogAddon> {
+    let url = normalize_url(&url.to_string());
+
+    catalog
+        .addons
+        .iter()
+        .filter(|a| {
+            a.game_versions
+                .iter()
+                .any(|gv| gv.flavor == flavor.base_flavor())
+        })
+        .find(|a| normalize_url(&a.website_url) == url)
+}
+
+fn normalize_url(url: &str) -> String {
+    url.trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .trim_end_matches('/')
+        .to_lowercase()
+}
+
+fn repo_id_from_catalog_addon(addon: &CatalogAddon) -> (RepositoryKind, String) {
+    let kind = match addon.source {
+        Source::Curse => RepositoryKind::Curse,
+        Source::Tukui => RepositoryKind::Tukui,
+        Source::WowI => RepositoryKind::WowI,
+    };
+
+    (kind, addon.id.to_string())
+}
+
+/// Parses the id out of a WoWInterface or Tukui addon page url, eg.
+/// `https://www.wowinterface.com/downloads/info5108-Clique.html` or
+/// `https://www.tukui.org/addons.php?id=3`.
+fn repo_id_from_website_url(url: &Uri) -> Option<(RepositoryKind, String)> {
+    let host = url.host()?.trim_start_matches("www.");
+
+    match host {
+        "wowinterface.com" => {
+            let file = url.path().rsplit('/').next()?;
+            let id: String = file
+                .strip_prefix("info")?
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+
+            Some((RepositoryKind::WowI, id)).filter(|(_, id)| !id.is_empty())
+        }
+        "tukui.org" => {
+            let query = url.query()?;
+
+            query
+                .split('&')
+                .find_map(|pair| match split_once(pair, '=')? {
+                    ("id", id) => Some((RepositoryKind::Tukui, id.to_owned())),
+                    ("ui", "tukui") => Some((RepositoryKind::Tukui, "-1".to_owned())),
+                    ("ui", "elvui") => Some((RepositoryKind::Tukui, "-2".to_owned())),
+                    _ => None,
+                })
+        }
+        _ => None,
+    }
+}
+
+fn split_once(s: &str, delimiter: char) -> Option<(&str, &str)> {
+    let idx = s.find(delimiter)?;
+
+    Some((&s[..idx], &s[idx + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addon(id: i32, website_url: &str, flavor: &str) -> CatalogAddon {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "websiteUrl": website_url,
+            "dateReleased": "",
+            "name": "",
+            "categories": [],
+            "summary": "",
+            "numberOfDownloads": 0,
+            "flavors": null,
+            "gameVersions": [{ "gameVersion": "", "flavor": flavor }],
+            "source": "curse",
+        }))
+        .unwrap()
+    }
+
+    fn repo_id(url: &str) -> Option<(RepositoryKind, String)> {
+        repo_id_from_website_url(&url.parse().unwrap())
+    }
+
+    #[test]
+    fn test_repo_id_from_prefix() {
+        assert_eq!(
+            repo_id_from_prefix("curse:123"),
+            Some((RepositoryKind::Curse, "123".to_owned()))
+        );
+        assert_eq!(
+            repo_id_from_prefix("WowI:5108"),
+            Some((RepositoryKind::WowI, "5108".to_owned()))
+        );
+        assert_eq!(
+            repo_id_from_prefix("tukui:-2"),
+            Some((RepositoryKind::Tukui, "-2".to_owned()))
+        );
+
+        assert_eq!(repo_id_from_prefix("curse:"), None);
+        assert_eq!(repo_id_from_prefix("github:123"), None);
+        assert_eq!(repo_id_from_prefix("https://github.com/foo/bar"), None);
+        assert_eq!(repo_id_from_prefix("WeakAuras"), None);
+    }
+
+    #[test]
+    fn test_repo_id_from_website_url() {
+        assert_eq!(
+            repo_id("https://www.wowinterface.com/downloads/info5108-Clique.html"),
+            Some((RepositoryKind::WowI, "5108".to_owned()))
+        );
+        assert_eq!(
+            repo_id("https://wowinterface.com/downloads/info5108"),
+            Some((RepositoryKind::WowI, "5108".to_owned()))
+        );
+        assert_eq!(
+            repo_id("https://www.tukui.org/addons.php?id=3"),
+            Some((RepositoryKind::Tukui, "3".to_owned()))
+        );
+        assert_eq!(
+            repo_id("https://www.tukui.org/download.php?ui=elvui"),
+            Some((RepositoryKind::Tukui, "-2".to_owned()))
+        );
+        assert_eq!(
+            repo_id("https://www.tukui.org/download.php?ui=tukui"),
+            Some((RepositoryKind::Tukui, "-1".to_owned()))
+        );
+
+        assert_eq!(
+            repo_id("https://www.wowinterface.com/downloads/index.php"),
+            None
+        );
+        assert_eq!(
+            repo_id("https://www.wowinterface.com/downloads/info-Clique.html"),
+            None
+        );
+        assert_eq!(repo_id("https://www.tukui.org/addons.php"), None);
+        assert_eq!(repo_id("https://www.tukui.org/download.php?ui=other"), None);
+        assert_eq!(
+            repo_id("https://www.curseforge.com/wow/addons/weakauras-2"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(
+            normalize_url("https://www.curseforge.com/wow/addons/WeakAuras-2/"),
+            "curseforge.com/wow/addons/weakauras-2"
+        );
+        assert_eq!(
+            normalize_url("http://curseforge.com/wow/addons/weakauras-2"),
+            "curseforge.com/wow/addons/weakauras-2"
+        );
+    }
+
+    #[test]
+    fn test_split_once() {
+        assert_eq!(split_once("curse:123", ':'), Some(("curse", "123")));
+        assert_eq!(split_once("id=3=4", '='), Some(("id", "3=4")));
+        assert_eq!(split_once("curse", ':'), None);
+    }
+
+    #[test]
+    fn test_find_by_website_url() {
+        let catalog = Catalog {
+            addons: vec![
+                addon(
+                    1,
+                    "https://www.curseforge.com/wow/addons/foo",
+                    "wow_classic",
+                ),
+                addon(2, "https://www.curseforge.com/wow/addons/foo", "wow_retail"),
+                addon(
+                    3,
+                    "https://www.curseforge.com/wow/addons/bar",
+                    "wow_classic",
+                ),
+            ],
+        };
+
+        let url = "https://curseforge.com/wow/addons/Foo/".parse().unwrap();
+        let found = find_by_website_url(&catalog, &url, Flavor::RetailPTR).map(|a| a.id);
+        assert_eq!(found, Some(2));
+
+        let url = "https://www.curseforge.com/wow/addons/bar".parse().unwrap();
+        assert!(find_by_website_url(&catalog, &url, Flavor::Retail).is_none());
+        assert!(find_by_website_url(&catalog, &url, Flavor::Classic).is_some());
+    }
 }
@@ -0,0 +1,242 @@
+use super::format;
+use crate::cli::OutputFormat;
+use crate::log_error;
+use crate::Result;
+
+use ajour_core::addon::{Addon, AddonState};
+use ajour_core::cache::{load_addon_cache, load_fingerprint_cache};
+use ajour_core::config::{load_config, Flavor};
+use ajour_core::parse::read_addon_directory;
+use ajour_core::repository::{GitKind, ReleaseChannel, RepositoryKind};
+
+use anyhow::{format_err, Context};
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+use serde::Serialize;
+
+/// Columns of the table and csv output, in the same order as the fields of `ListRow`.
+const HEADER: [&str; 11] = [
+    "flavor",
+    "title",
+    "folders",
+    "local_version",
+    "remote_version",
+    "channel",
+    "source",
+    "repository_id",
+    "game_version",
+    "state",
+    "stale",
+];
+
+pub fn list(
+    flavor: Option<Flavor>,
+    outdated: bool,
+    unknown: bool,
+    ignored: bool,
+    output_format: OutputFormat,
+) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+
+        let addon_cache = Arc::new(Mutex::new(load_addon_cache().await?));
+        let fingerprint_cache = Arc::new(Mutex::new(load_fingerprint_cache().await?));
+
+        // When listing all flavors, flavors without a directory are skipped.
+        let flavors = match flavor {
+            Some(flavor) => vec![flavor],
+            None => Flavor::ALL.to_vec(),
+        };
+        let is_single_flavor = flavor.is_some();
+
+        let mut rows = vec![];
+
+        for flavor in flavors {
+            let addon_directory = match config.get_addon_directory_for_flavor(&flavor) {
+                Some(addon_directory) if addon_directory.exists() => addon_directory,
+                Some(addon_directory) if is_single_flavor => {
+                    return Err(format_err!(
+                        "No AddOns folder found for {} at {:?}",
+                        flavor,
+                        addon_directory
+                    ));
+                }
+                None if is_single_flavor => {
+                    return Err(format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."));
+                }
+                _ => continue,
+            };
+
+            let addons = match read_addon_directory(
+                Some(addon_cache.clone()),
+                Some(fingerprint_cache.clone()),
+                &addon_directory,
+                flavor,
+            )
+            .await
+            .context(format!("failed to read addons for {}", flavor))
+            {
+                Ok(addons) => addons,
+                Err(e) if is_single_flavor => return Err(e),
+                // One broken flavor shouldn't hide the addons of the others
+                Err(e) => {
+                    log_error(&e);
+                    continue;
+                }
+            };
+
+            // Get any saved release channel preferences from config
+            let release_channels = config
+                .addons
+                .release_channels
+                .get(&flavor)
+                .cloned()
+                .unwrap_or_default();
+
+            // Get any ingnored addons from the config
+            let ignored_ids = config
+                .addons
+                .ignored
+                .get(&flavor)
+                .cloned()
+                .unwrap_or_default();
+
+            for mut addon in addons {
+                // Apply release channel preference, else determine it from the
+                // installed version.
+                if let Some(channel) = release_channels.get(&addon.primary_folder_id) {
+                    addon.release_channel = *channel;
+                } else {
+                    for (channel, package) in addon.remote_packages() {
+                        if package.file_id == addon.file_id() {
+                            addon.release_channel = channel;
+                            break;
+                        }
+                    }
+                }
+
+                if let Some(package) = addon.relevant_release_package() {
+                    if addon.is_updatable(&package) {
+                        addon.state = AddonState::Updatable;
+                    }
+                }
+
+                if ignored_ids.iter().any(|i| i == &addon.primary_folder_id) {
+                    addon.state = AddonState::Ignored;
+                }
+
+                rows.push(ListRow::new(flavor, &addon));
+            }
+        }
+
+        // If no filter is given, all addons are listed.
+        if outdated || unknown || ignored {
+            rows.retain(|row| match row.state {
+                ListState::Outdated => outdated,
+                ListState::Unknown => unknown,
+                ListState::Ignored => ignored,
+                ListState::UpToDate => false,
+            });
+        }
+
+        match output_format {
+            OutputFormat::Table => format::print_table(&HEADER, &to_cells(&rows)),
+            OutputFormat::Csv => format::print_csv(&HEADER, &to_cells(&rows)),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        }
+
+        Result::Ok(())
+    })
+}
+
+/// An installed addon. Field names and values are part of the output format
+/// and documented in CLI.md.
+#[derive(Debug, Serialize)]
+struct ListRow {
+    flavor: &'static str,
+    title: String,
+    folders: Vec<String>,
+    local_version: Option<String>,
+    remote_version: Option<String>,
+    channel: &'static str,
+    source: Option<&'static str>,
+    repository_id: Option<String>,
+    game_version: Option<String>,
+    state: ListState,
+    stale: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum ListState {
+    UpToDate,
+    Outdated,
+    Ignored,
+    Unknown,
+}
+
+impl ListState {
+    fn as_str(self) -> &'static str {
+        match self {
+            ListState::UpToDate => "up-to-date",
+            ListState::Outdated => "outdated",
+            ListState::Ignored => "ignored",
+            ListState::Unknown => "unknown",
+        }
+    }
+}
+
+impl ListRow {
+    fn new(flavor: Flavor, addon: &Addon) -> Self {
+        let state = match addon.state {
+            AddonState::Ignored => ListState::Ignored,
+            AddonState::Updatable => ListState::Outdated,
+            _ if addon.repository_kind().is_none() => ListState::Unknown,
+            _ => ListState::UpToDate,
+        };
+
+        ListRow {
+            flavor: flavor.as_str(),
+            title: addon.title().to_owned(),
+            folders: addon.folders.iter().map(|f| f.id.clone()).collect(),
+            local_version: addon.version().map(str::to_owned),
+            remote_version: addon.relevant_release_package().map(|p| p.version),
+            channel: match addon.release_channel {
+                ReleaseChannel::Stable => "stable",
+                ReleaseChannel::Beta => "beta",
+                ReleaseChannel::Alpha => "alpha",
+            },
+            source: addon.repository_kind().map(|kind| match kind {
+                RepositoryKind::Curse => "curse",
+                RepositoryKind::Tukui => "tukui",
+                RepositoryKind::WowI => "wowi",
+                RepositoryKind::Git(GitKind::Github) => "github",
+                RepositoryKind::Git(GitKind::Gitlab) => "gitlab",
+            }),
+            repository_id: addon.repository_id().map(str::to_owned),
+            game_version: addon.game_version().map(str::to_owned),
+            state,
+            stale: addon.is_remote_stale(),
+        }
+    }
+}
+
+fn to_cells(rows: &[ListRow]) -> Vec<Vec<String>> {
+    rows.iter()
+        .map(|row| {
+            vec![
+                row.flavor.to_owned(),
+                row.title.clone(),
+                row.folders.join(" "),
+                row.local_version.clone().unwrap_or_default(),
+                row.remote_version.clone().unwrap_or_default(),
+                row.channel.to_owned(),
+                row.source.unwrap_or_default().to_owned(),
+                row.repository_id.clone().unwrap_or_default(),
+                row.game_version.clone().unwrap_or_default(),
+                row.state.as_str().to_owned(),
+                row.stale.to_string(),
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,260 @@
+use super::install::{install_package, install_release};
+use super::manage::read_installed_addons;
+use crate::{log_error, Result};
+
+use ajour_core::cache::{load_addon_cache, load_fingerprint_cache};
+use ajour_core::config::{load_config, Flavor};
+use ajour_core::fs::PersistentData;
+use ajour_core::lockfile::{
+    installed_release, load_lockfile, save_lockfile, LockedAddon, Lockfile,
+};
+use ajour_core::parse::fingerprint_folders;
+use ajour_core::repository::{ReleaseChannel, RepositoryPackage};
+
+use anyhow::{format_err, Context};
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+
+use std::path::PathBuf;
+
+/// Record the release each installed addon of a flavor is installed from in a
+/// lockfile.
+pub fn lock(flavor: Option<Flavor>, path: Option<PathBuf>) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+        let path = match path {
+            Some(path) => path,
+            None => Lockfile::path(flavor)?,
+        };
+
+        let previous = if path.exists() {
+            load_lockfile(path.clone()).await?
+        } else {
+            Lockfile::new(flavor)
+        };
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+
+        let mut lockfile = Lockfile::new(flavor);
+        let mut skipped = vec![];
+
+        for addon in installed.iter().filter(|a| a.repository_kind().is_some()) {
+            let folders: Vec<_> = addon.folders.iter().map(|f| f.id.clone()).collect();
+
+            let fingerprints = match fingerprint_folders(&addon_directory, &folders)
+                .with_context(|| format!("Failed to fingerprint {}", addon.title()))
+            {
+                Ok(fingerprints) => fingerprints,
+                Err(e) => {
+                    log_error(&e);
+                    skipped.push(addon.title().to_owned());
+                    continue;
+                }
+            };
+
+            // Keep the previously locked release if the addon hasn't changed
+            // since, it might not be available from the repository anymore
+            if let Some(locked) = previous
+                .get(addon)
+                .filter(|locked| locked.mismatches(&fingerprints).is_empty())
+            {
+                lockfile.insert(locked.clone());
+                continue;
+            }
+
+            match installed_release(addon)
+                .and_then(|package| LockedAddon::new(addon, package, fingerprints))
+            {
+                Some(locked) => lockfile.insert(locked),
+                None => skipped.push(addon.title().to_owned()),
+            }
+        }
+
+        let locked = lockfile.addons.len();
+
+        save_lockfile(&lockfile, path.clone()).await?;
+
+        log::info!("Locked {} addons in {:?}", locked, path);
+
+        if !skipped.is_empty() {
+            log::warn!(
+                "Skipped {} addons whose installed release couldn't be found: {}",
+                skipped.len(),
+                skipped.join(", ")
+            );
+        }
+
+        Result::Ok(())
+    })
+}
+
+/// Install the addons in a lockfile. With `locked`, every addon which doesn't
+/// match the lockfile is installed from exactly the locked release. Otherwise
+/// only missing addons are installed, from the latest release of their
+/// channel.
+pub fn sync(
+    flavor: Option<Flavor>,
+    path: Option<PathBuf>,
+    locked: bool,
+    dry_run: bool,
+) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+
+        let (flavor, lockfile) = match path {
+            Some(path) => {
+                let lockfile = load_lockfile(path).await?;
+
+                (flavor.unwrap_or(lockfile.flavor), lockfile)
+            }
+            None => {
+                let flavor = flavor.unwrap_or(config.wow.flavor);
+                let path = Lockfile::path(flavor)?;
+
+                if !path.exists() {
+                    return Err(format_err!(
+                        "No lockfile for {}. Use `ajour lock` to create one.",
+                        flavor
+                    ));
+                }
+
+                (flavor, load_lockfile(path).await?)
+            }
+        };
+
+        let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+        let out_of_sync: Vec<_> = lockfile
+            .addons
+            .iter()
+            .filter(|addon| {
+                if locked {
+                    !addon.is_installed(&addon_directory)
+                } else {
+                    addon
+                        .folders
+                        .first()
+                        .map_or(false, |folder| !addon_directory.join(folder).exists())
+                }
+            })
+            .collect();
+
+        log::info!(
+            "{} of {} addons in the lockfile need to be installed for {}",
+            out_of_sync.len(),
+            lockfile.addons.len(),
+            flavor
+        );
+
+        let addon_cache = Arc::new(Mutex::new(load_addon_cache().await?));
+        let fingerprint_cache = Arc::new(Mutex::new(load_fingerprint_cache().await?));
+
+        let mut failed = vec![];
+
+        for addon in out_of_sync {
+            if dry_run {
+                if locked {
+                    log::info!("Would install {} {}", addon.title, addon.version);
+                } else {
+                    log::info!("Would install {} ({})", addon.title, addon.repository);
+                }
+                continue;
+            }
+
+            let result = if locked {
+                match addon.to_addon(flavor) {
+                    Ok(pinned) => {
+                        install_release(
+                            &config,
+                            addon_cache.clone(),
+                            fingerprint_cache.clone(),
+                            pinned,
+                            flavor,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            } else {
+                match RepositoryPackage::from_kind_and_id(
+                    flavor,
+                    addon.repository,
+                    addon.id.clone(),
+                ) {
+                    Ok(repo_package) => {
+                        install_package(
+                            &mut config,
+                            addon_cache.clone(),
+                            fingerprint_cache.clone(),
+                            repo_package,
+                            flavor,
+                            addon.channel,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            };
+
+            match result.with_context(|| format!("Failed to install {}", addon.title)) {
+                Ok(installed) => {
+                    if !locked {
+                        log::info!("Installed {}", addon.title);
+                        continue;
+                    }
+
+                    log::info!("Installed {} {}", addon.title, addon.version);
+
+                    // Persist the release channel so updates stay on the same channel
+                    if addon.channel != ReleaseChannel::default() {
+                        config
+                            .addons
+                            .release_channels
+                            .entry(flavor)
+                            .or_default()
+                            .insert(installed.primary_folder_id.clone(), addon.channel);
+
+                        config.save()?;
+                    }
+
+                    let folders: Vec<_> = installed.folders.iter().map(|f| f.id.clone()).collect();
+                    let mismatches = fingerprint_folders(&addon_directory, &folders)
+                        .map(|fingerprints| {
+                            addon
+                                .mismatches(&fingerprints)
+                                .into_iter()
+                                .map(str::to_owned)
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_else(|_| addon.folders.clone());
+
+                    if !mismatches.is_empty() {
+                        log::warn!(
+                            "{} doesn't match the lockfile after installing, fingerprints differ for: {}",
+                            addon.title,
+                            mismatches.join(", ")
+                        );
+                    }
+                }
+                Err(e) => {
+                    log_error(&e);
+                    failed.push(addon.title.clone());
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(format_err!(
+                "Failed to install {} addons: {}",
+                failed.len(),
+                failed.join(", ")
+            ));
+        }
+
+        Result::Ok(())
+    })
+}
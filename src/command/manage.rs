@@ -0,0 +1,219 @@
+use crate::Result;
+
+use ajour_core::addon::Addon;
+use ajour_core::backup::addon::{revert_addon, SnapshotSettings};
+use ajour_core::cache::{
+    load_addon_cache, load_fingerprint_cache, remove_addon_cache_entry, AddonCache, AddonCacheEntry,
+};
+use ajour_core::config::{load_config, Config, Flavor};
+use ajour_core::fs::{delete_addons, PersistentData};
+use ajour_core::lockfile::unlock_addon;
+use ajour_core::parse::read_addon_directory;
+use ajour_core::repository::ReleaseChannel;
+
+use anyhow::format_err;
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+
+use std::convert::TryFrom;
+
+/// Delete addons from disk and remove them from the cache and lockfile.
+pub fn remove(flavor: Option<Flavor>, addons: Vec<String>) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let addon_cache = Arc::new(Mutex::new(load_addon_cache().await?));
+        let installed = read_installed_addons(&config, flavor, Some(addon_cache.clone())).await?;
+
+        for addon in find_addons(&installed, &addons)? {
+            delete_addons(&addon.folders)?;
+
+            // Delete the entry for this cached addon
+            if let Ok(entry) = AddonCacheEntry::try_from(addon) {
+                remove_addon_cache_entry(addon_cache.clone(), entry, flavor).await?;
+            }
+
+            // Remove the addon from the lockfile, so it isn't installed again
+            unlock_addon(flavor, addon.clone()).await?;
+
+            log::info!("Removed {} ({})", addon.title(), &addon.primary_folder_id);
+        }
+
+        Result::Ok(())
+    })
+}
+
+/// Ignore addons so they are skipped when updating.
+pub fn ignore(flavor: Option<Flavor>, addons: Vec<String>) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+        let ignored = config.addons.ignored.entry(flavor).or_default();
+
+        for addon in find_addons(&installed, &addons)? {
+            if !ignored.contains(&addon.primary_folder_id) {
+                ignored.push(addon.primary_folder_id.clone());
+            }
+
+            log::info!("Ignored {} ({})", addon.title(), &addon.primary_folder_id);
+        }
+
+        config.save()?;
+
+        Result::Ok(())
+    })
+}
+
+/// Stop ignoring addons.
+pub fn unignore(flavor: Option<Flavor>, addons: Vec<String>) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        // Addons which have been deleted since they were ignored can still be
+        // unignored by their folder id.
+        let ignored = config.addons.ignored.entry(flavor).or_default();
+        let (by_id, rest): (Vec<_>, Vec<_>) = addons.into_iter().partition(|a| ignored.contains(a));
+        ignored.retain(|i| !by_id.contains(i));
+
+        if !rest.is_empty() {
+            let installed = read_installed_addons(&config, flavor, None).await?;
+            let ignored = config.addons.ignored.entry(flavor).or_default();
+
+            for addon in find_addons(&installed, &rest)? {
+                ignored.retain(|i| i != &addon.primary_folder_id);
+            }
+        }
+
+        config.save()?;
+
+        Result::Ok(())
+    })
+}
+
+/// Set the release channel addons are updated from.
+pub fn channel(
+    flavor: Option<Flavor>,
+    release_channel: ReleaseChannel,
+    addons: Vec<String>,
+) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+        let release_channels = config.addons.release_channels.entry(flavor).or_default();
+
+        for addon in find_addons(&installed, &addons)? {
+            if addon.repository_kind().is_none() {
+                log::warn!(
+                    "{} isn't linked to a repository, release channel has no effect",
+                    addon.title()
+                );
+            }
+
+            release_channels.insert(addon.primary_folder_id.clone(), release_channel);
+
+            log::info!(
+                "Set release channel of {} ({}) to {}",
+                addon.title(),
+                &addon.primary_folder_id,
+                release_channel
+            );
+        }
+
+        config.save()?;
+
+        Result::Ok(())
+    })
+}
+
+/// Revert addons to the snapshot taken before they were last updated.
+pub fn revert(flavor: Option<Flavor>, addons: Vec<String>) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let settings = SnapshotSettings::from_config(&config, flavor)
+            .ok_or_else(|| format_err!("Snapshots are turned off or no WoW directory is set"))?;
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+
+        for addon in find_addons(&installed, &addons)? {
+            let as_of = revert_addon(addon.clone(), flavor, settings.clone()).await?;
+
+            // The lockfile still has the release we reverted from, which
+            // `sync --locked` would install again
+            unlock_addon(flavor, addon.clone()).await?;
+
+            log::info!(
+                "Reverted {} ({}) to {}",
+                addon.title(),
+                &addon.primary_folder_id,
+                as_of.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+
+        Result::Ok(())
+    })
+}
+
+pub(super) async fn read_installed_addons(
+    config: &Config,
+    flavor: Flavor,
+    addon_cache: Option<Arc<Mutex<AddonCache>>>,
+) -> Result<Vec<Addon>> {
+    let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+    let addon_cache = match addon_cache {
+        Some(addon_cache) => addon_cache,
+        None => Arc::new(Mutex::new(load_addon_cache().await?)),
+    };
+    let fingerprint_cache = Arc::new(Mutex::new(load_fingerprint_cache().await?));
+
+    Ok(read_addon_directory(
+        Some(addon_cache),
+        Some(fingerprint_cache),
+        &addon_directory,
+        flavor,
+    )
+    .await?)
+}
+
+/// Returns the addon matching each of `queries`, either by primary folder id
+/// or title. Errors if a query matches no addon or more than one by title.
+pub(super) fn find_addons<'a>(
+    installed: &'a [Addon],
+    queries: &[String],
+) -> Result<Vec<&'a Addon>> {
+    queries
+        .iter()
+        .map(|query| {
+            if let Some(addon) = installed.iter().find(|a| &a.primary_folder_id == query) {
+                return Ok(addon);
+            }
+
+            let by_title: Vec<_> = installed
+                .iter()
+                .filter(|a| a.title().eq_ignore_ascii_case(query))
+                .collect();
+
+            match by_title.as_slice() {
+                [addon] => Ok(*addon),
+                [] => Err(format_err!("No addon found matching {:?}", query)),
+                addons => Err(format_err!(
+                    "{:?} matches more than one addon, use the folder id instead: {}",
+                    query,
+                    addons
+                        .iter()
+                        .map(|a| a.primary_folder_id.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            }
+        })
+        .collect()
+}
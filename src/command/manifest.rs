@@ -0,0 +1,130 @@
+use super::install::install_package;
+use super::manage::read_installed_addons;
+use crate::{log_error, Result};
+
+use ajour_core::cache::{load_addon_cache, load_fingerprint_cache};
+use ajour_core::config::{load_config, Flavor};
+use ajour_core::fs::PersistentData;
+use ajour_core::manifest::{load_manifest, save_manifest, AddonManifest};
+use ajour_core::repository::RepositoryPackage;
+
+use anyhow::{format_err, Context};
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+
+use std::path::PathBuf;
+
+/// Save the installed addons of a flavor to a manifest.
+pub fn export(flavor: Option<Flavor>, path: PathBuf) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+        let skipped = installed
+            .iter()
+            .filter(|a| a.repository_kind().is_none())
+            .count();
+
+        let manifest = AddonManifest::new(flavor, &installed, &config);
+        let exported = manifest.addons.len();
+
+        save_manifest(manifest, path.clone()).await?;
+
+        log::info!("Exported {} addons to {:?}", exported, path);
+
+        if skipped > 0 {
+            log::warn!(
+                "Skipped {} addons which aren't linked to a repository",
+                skipped
+            );
+        }
+
+        Result::Ok(())
+    })
+}
+
+/// Install the addons in a manifest which aren't installed yet.
+pub fn import(flavor: Option<Flavor>, path: PathBuf, dry_run: bool) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+
+        let manifest = load_manifest(path).await?;
+        let flavor = flavor.unwrap_or(manifest.flavor);
+
+        let addon_cache = Arc::new(Mutex::new(load_addon_cache().await?));
+        let fingerprint_cache = Arc::new(Mutex::new(load_fingerprint_cache().await?));
+
+        let installed = read_installed_addons(&config, flavor, Some(addon_cache.clone())).await?;
+        let missing = manifest.missing(&installed);
+
+        log::info!(
+            "{} of {} addons in the manifest aren't installed for {}",
+            missing.len(),
+            manifest.addons.len(),
+            flavor
+        );
+
+        let mut failed = vec![];
+
+        for addon in missing {
+            if dry_run {
+                log::info!("Would install {} ({})", addon.title, addon.repository);
+                continue;
+            }
+
+            let result = match RepositoryPackage::from_kind_and_id(
+                flavor,
+                addon.repository,
+                addon.id.clone(),
+            ) {
+                Ok(repo_package) => {
+                    install_package(
+                        &mut config,
+                        addon_cache.clone(),
+                        fingerprint_cache.clone(),
+                        repo_package,
+                        flavor,
+                        addon.channel,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            match result.with_context(|| format!("Failed to install {}", addon.title)) {
+                Ok(installed) => {
+                    log::info!(
+                        "Installed {} ({})",
+                        addon.title,
+                        &installed.primary_folder_id
+                    );
+
+                    if addon.ignored {
+                        let ignored = config.addons.ignored.entry(flavor).or_default();
+
+                        if !ignored.contains(&installed.primary_folder_id) {
+                            ignored.push(installed.primary_folder_id.clone());
+                        }
+
+                        config.save()?;
+                    }
+                }
+                Err(e) => {
+                    log_error(&e);
+                    failed.push(addon.title.clone());
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(format_err!(
+                "Failed to install {} addons: {}",
+                failed.len(),
+                failed.join(", ")
+            ));
+        }
+
+        Result::Ok(())
+    })
+}
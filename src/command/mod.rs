@@ -1,8 +1,37 @@
 mod backup;
-pub use backup::backup;
+pub use backup::{backup, export_backup};
+
+mod config;
+pub use config::config;
+
+mod format;
 
 mod install;
-pub use install::install_from_source;
+pub use install::install;
+
+mod list;
+pub use list::list;
+
+mod lock;
+pub use lock::{lock, sync};
+
+mod manage;
+pub use manage::{channel, ignore, remove, revert, unignore};
+
+mod manifest;
+pub use manifest::{export, import};
+
+mod profile;
+pub use profile::profile;
+
+mod restore;
+pub use restore::restore;
+
+mod saved_variables;
+pub use saved_variables::saved_variables;
+
+mod search;
+pub use search::search;
 
 mod update;
-pub use update::update_all_addons;
+pub use update::update;
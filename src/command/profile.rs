@@ -0,0 +1,91 @@
+use crate::cli::ProfileCommand;
+use crate::Result;
+
+use ajour_core::config::{load_config, Flavor};
+use ajour_core::fs::PersistentData;
+use ajour_core::profile::{
+    delete_profile, profile_folders, save_profile, switch_profile, ProfileSwitch,
+};
+
+use anyhow::format_err;
+use async_std::task;
+
+pub fn profile(flavor: Option<Flavor>, command: ProfileCommand) -> Result<()> {
+    task::block_on(async {
+        let mut config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+        let parked_directory = config
+            .get_parked_addon_directory_for_flavor(&flavor)
+            .ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+        match command {
+            ProfileCommand::List => {
+                let active = config.addons.active_profile.get(&flavor);
+
+                match config.addons.profiles.get(&flavor) {
+                    Some(profiles) if !profiles.is_empty() => {
+                        for (name, folders) in profiles {
+                            let marker = if Some(name) == active { "*" } else { " " };
+
+                            println!("{} {} ({} addons)", marker, name, folders.len());
+                        }
+                    }
+                    _ => log::info!("No profiles for {}", flavor),
+                }
+            }
+            ProfileCommand::Save { name } => {
+                let count = save_profile(&mut config, flavor, name.clone(), &addon_directory)?;
+                config.save()?;
+
+                log::info!("Saved {} addon folders as profile {}", count, name);
+            }
+            ProfileCommand::Switch { name } => {
+                let folders = profile_folders(&config, flavor, &name)?;
+
+                let switch =
+                    switch_profile(addon_directory, parked_directory, Some(folders)).await?;
+
+                config.addons.active_profile.insert(flavor, name.clone());
+                config.save()?;
+
+                log::info!("Switched to profile {}", name);
+                log_switch(&switch);
+            }
+            ProfileCommand::Reset => {
+                let switch = switch_profile(addon_directory, parked_directory, None).await?;
+
+                config.addons.active_profile.remove(&flavor);
+                config.save()?;
+
+                log::info!("Moved all parked addons back");
+                log_switch(&switch);
+            }
+            ProfileCommand::Delete { name } => {
+                delete_profile(&mut config, flavor, &name)?;
+                config.save()?;
+
+                log::info!("Deleted profile {}", name);
+            }
+        }
+
+        Result::Ok(())
+    })
+}
+
+fn log_switch(switch: &ProfileSwitch) {
+    log::info!(
+        "{} addon folders parked, {} moved back",
+        switch.parked.len(),
+        switch.unparked.len()
+    );
+
+    if !switch.missing.is_empty() {
+        log::warn!(
+            "{} addon folders of the profile aren't installed: {}",
+            switch.missing.len(),
+            switch.missing.join(", ")
+        );
+    }
+}
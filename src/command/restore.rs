@@ -0,0 +1,67 @@
+use crate::cli::BackupFolder;
+use crate::Result;
+
+use ajour_core::backup::{archive_contents, restore_backup, BackupKind, RestoreSelection};
+use ajour_core::config::{load_config, Flavor};
+
+use anyhow::format_err;
+use async_std::task;
+use std::path::PathBuf;
+
+pub fn restore(
+    archive: PathBuf,
+    backup_folder: BackupFolder,
+    flavors: Vec<Flavor>,
+    list: bool,
+) -> Result<()> {
+    task::block_on(async {
+        if !archive.is_file() {
+            return Err(format_err!("archive must be a file: {:?}", archive));
+        }
+
+        if list {
+            for section in archive_contents(archive).await? {
+                println!(
+                    "{}\t{}\t{} files\t{} bytes\t{}",
+                    section.flavor,
+                    section.kind,
+                    section.files,
+                    section.size,
+                    section.path.display()
+                );
+            }
+
+            return Ok(());
+        }
+
+        let config = load_config().await?;
+
+        let wow_dir = config.wow.directory.clone().ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+        let selection = RestoreSelection {
+            flavors,
+            kinds: match backup_folder {
+                BackupFolder::Both => vec![],
+                BackupFolder::AddOns => vec![BackupKind::AddOns],
+                BackupFolder::WTF => vec![BackupKind::WTF],
+            },
+        };
+
+        log::info!("Restoring {:?}", &archive);
+
+        let result =
+            restore_backup(archive, wow_dir, selection, config.get_snapshot_directory()).await?;
+
+        if let Some(snapshot) = &result.snapshot {
+            log::info!("Previous state saved to {:?}", snapshot);
+        }
+
+        for section in result.restored.iter() {
+            log::info!("\t{} - {}", section.flavor, section.kind);
+        }
+
+        log::info!("Restore complete!");
+
+        Ok(())
+    })
+}
@@ -0,0 +1,179 @@
+use super::manage::{find_addons, read_installed_addons};
+use crate::cli::SavedVariablesCommand;
+use crate::Result;
+
+use ajour_core::addon::{Addon, AddonFolder};
+use ajour_core::config::{load_config, Flavor};
+use ajour_core::saved_variables::{
+    delete_orphaned_saved_variables, export_saved_variables, find_orphaned_saved_variables,
+    find_scope, import_saved_variables, load_saved_variables,
+};
+
+use anyhow::format_err;
+use async_std::task;
+
+pub fn saved_variables(flavor: Option<Flavor>, command: SavedVariablesCommand) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let wtf_directory = config.get_wtf_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+        let installed = read_installed_addons(&config, flavor, None).await?;
+
+        match command {
+            SavedVariablesCommand::List { addon } => {
+                let addon = find_addon(&installed, addon)?;
+
+                let files = load_saved_variables(addon.clone(), wtf_directory).await?;
+
+                if files.is_empty() {
+                    log::info!("No SavedVariables for {}", addon.title());
+                }
+
+                for file in files {
+                    println!("{}\t{}\t{} bytes", file.folder, file.scope, file.size);
+
+                    if let Some(error) = file.error {
+                        println!("\t{}", error);
+                    }
+
+                    for (name, entries) in file.variables {
+                        match entries {
+                            Some(entries) => println!("\t{}\t{} entries", name, entries),
+                            None => println!("\t{}", name),
+                        }
+                    }
+                }
+            }
+            SavedVariablesCommand::Export {
+                addon,
+                folder,
+                account,
+                character,
+                destination,
+            } => {
+                let addon = find_addon(&installed, addon)?;
+                let folder = find_folder(addon, folder.as_deref())?;
+
+                let scope = find_scope(&wtf_directory, account.as_deref(), character.as_deref())?;
+                let path = scope.saved_variables_path(&wtf_directory, &folder.id);
+
+                if !path.is_file() {
+                    return Err(format_err!(
+                        "{} has no SavedVariables for {}",
+                        scope,
+                        folder.id
+                    ));
+                }
+
+                let dest = export_saved_variables(path, destination).await?;
+
+                log::info!(
+                    "Exported SavedVariables of {} for {} to {:?}",
+                    folder.id,
+                    scope,
+                    dest
+                );
+            }
+            SavedVariablesCommand::Import {
+                addon,
+                folder,
+                account,
+                character,
+                path,
+            } => {
+                let addon = find_addon(&installed, addon)?;
+                let folder = find_folder(addon, folder.as_deref())?;
+
+                let scope = find_scope(&wtf_directory, account.as_deref(), character.as_deref())?;
+
+                let backup =
+                    import_saved_variables(path, folder.clone(), scope.clone(), wtf_directory)
+                        .await?;
+
+                log::info!("Imported SavedVariables of {} for {}", folder.id, scope);
+
+                if let Some(backup) = backup {
+                    log::info!("The previous SavedVariables were kept as {:?}", backup);
+                }
+            }
+            SavedVariablesCommand::Orphaned { delete } => {
+                let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+                let parked_directory = config
+                    .get_parked_addon_directory_for_flavor(&flavor)
+                    .ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+
+                let folders = installed
+                    .iter()
+                    .flat_map(|addon| addon.folders.iter().cloned())
+                    .collect();
+
+                let orphans = find_orphaned_saved_variables(
+                    wtf_directory,
+                    addon_directory,
+                    parked_directory,
+                    folders,
+                )
+                .await?;
+
+                if orphans.is_empty() {
+                    log::info!("No orphaned SavedVariables for {}", flavor);
+
+                    return Ok(());
+                }
+
+                for orphan in orphans.iter() {
+                    println!("{}\t{}\t{} bytes", orphan.folder, orphan.scope, orphan.size);
+                }
+
+                let size: u64 = orphans.iter().map(|o| o.size).sum();
+
+                if delete {
+                    let wow_directory = config.wow.directory.clone().ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
+                    let backup_directory = config.get_saved_variables_backup_directory();
+
+                    let count = orphans.len();
+
+                    delete_orphaned_saved_variables(
+                        orphans,
+                        wow_directory,
+                        backup_directory.clone(),
+                        config.backup_compression,
+                    )
+                    .await?;
+
+                    log::info!(
+                        "Deleted {} orphaned SavedVariables ({} bytes), backed up to {:?}",
+                        count,
+                        size,
+                        backup_directory
+                    );
+                } else {
+                    log::info!(
+                        "{} orphaned SavedVariables ({} bytes). Use --delete to back them up and delete them",
+                        orphans.len(),
+                        size
+                    );
+                }
+            }
+        }
+
+        Result::Ok(())
+    })
+}
+
+fn find_addon(installed: &[Addon], query: String) -> Result<&Addon> {
+    Ok(find_addons(installed, &[query])?.remove(0))
+}
+
+/// Returns the folder of `addon` named `folder`, or the primary folder.
+fn find_folder<'a>(addon: &'a Addon, folder: Option<&str>) -> Result<&'a AddonFolder> {
+    let id = folder.unwrap_or(&addon.primary_folder_id);
+
+    addon
+        .folders
+        .iter()
+        .find(|f| f.id.eq_ignore_ascii_case(id))
+        .ok_or_else(|| format_err!("{} has no folder named {}", addon.title(), id))
+}
@@ -0,0 +1,180 @@
+use super::format;
+use crate::Result;
+
+use ajour_core::cache::{load_addon_cache, load_catalog_cache, save_catalog_cache, CatalogCache};
+use ajour_core::catalog::search::{SearchIndex, SearchQuery};
+use ajour_core::catalog::{get_catalog, Catalog, CatalogAddon, Source};
+use ajour_core::config::{load_config, Config, Flavor};
+use ajour_core::fs::addon_folders;
+use ajour_core::repository::RepositoryKind;
+
+use async_std::task;
+use num_format::{Locale, ToFormattedString};
+use serde::Serialize;
+
+pub fn search(
+    query: String,
+    flavor: Option<Flavor>,
+    source: Option<String>,
+    category: Option<String>,
+    limit: usize,
+    json: bool,
+) -> Result<()> {
+    task::block_on(async {
+        let config = load_config().await?;
+        let flavor = flavor.unwrap_or(config.wow.flavor);
+
+        let catalog = load_catalog(&config).await?;
+
+        let mut query = SearchQuery::parse(&query);
+        query.sources.extend(source.map(|s| s.to_lowercase()));
+        query.categories.extend(category.map(|c| c.to_lowercase()));
+
+        let installed = installed_addons(&config, flavor).await;
+
+        let rows: Vec<_> = SearchIndex::new(&catalog.addons)
+            .search(&query)
+            .into_iter()
+            .filter_map(|result| catalog.addons.get(result.index))
+            .filter_map(|addon| SearchRow::new(addon, flavor, &installed))
+            .take(limit)
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        } else {
+            print_table(&rows);
+        }
+
+        Result::Ok(())
+    })
+}
+
+/// Loads the catalog from cache, downloading a new one if the cache is missing
+/// or stale. Falls back to a stale cache if the download fails.
+pub(super) async fn load_catalog(config: &Config) -> Result<Catalog> {
+    let cache = load_catalog_cache().await.ok();
+
+    if let Some(cache) = &cache {
+        if !cache.is_stale() {
+            return Ok(cache.catalog.clone());
+        }
+    }
+
+    log::debug!("Downloading catalog");
+
+    match get_catalog(config.catalog_sources.clone()).await {
+        Ok(catalog) => {
+            if let Err(e) = save_catalog_cache(CatalogCache::new(catalog.clone())).await {
+                log::warn!("failed to save catalog cache: {}", e);
+            }
+
+            Ok(catalog)
+        }
+        Err(e) => match cache {
+            Some(cache) => {
+                log::warn!("failed to download catalog, using cached catalog: {}", e);
+
+                Ok(cache.catalog)
+            }
+            None => Err(e.into()),
+        },
+    }
+}
+
+/// Returns the repository and id of the addons installed for `flavor`, taken
+/// from the addon cache. Entries only count if their folders are still in the
+/// AddOns folder. Returns an empty list if no WoW directory is set.
+async fn installed_addons(config: &Config, flavor: Flavor) -> Vec<(RepositoryKind, String)> {
+    let addon_directory = match config.get_addon_directory_for_flavor(&flavor) {
+        Some(addon_directory) => addon_directory,
+        None => return vec![],
+    };
+
+    let addon_cache = match load_addon_cache().await {
+        Ok(addon_cache) => addon_cache,
+        Err(_) => return vec![],
+    };
+
+    let folders = addon_folders(&addon_directory).unwrap_or_default();
+
+    addon_cache
+        .get_for_flavor(flavor)
+        .iter()
+        .filter(|e| e.folder_names.iter().all(|f| folders.contains(f)))
+        .map(|e| (e.repository, e.repository_id.clone()))
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct SearchRow {
+    id: i32,
+    source: String,
+    name: String,
+    downloads: u64,
+    game_version: String,
+    installed: bool,
+}
+
+impl SearchRow {
+    /// Returns `None` if `addon` isn't available for `flavor`.
+    fn new(
+        addon: &CatalogAddon,
+        flavor: Flavor,
+        installed: &[(RepositoryKind, String)],
+    ) -> Option<Self> {
+        let game_version = addon
+            .game_versions
+            .iter()
+            .find(|gv| gv.flavor == flavor.base_flavor())?;
+
+        let repository = match addon.source {
+            Source::Curse => RepositoryKind::Curse,
+            Source::Tukui => RepositoryKind::Tukui,
+            Source::WowI => RepositoryKind::WowI,
+        };
+        let id = addon.id.to_string();
+        let installed = installed.iter().any(|(r, i)| *r == repository && i == &id);
+
+        Some(SearchRow {
+            id: addon.id,
+            source: addon.source.to_string(),
+            name: addon.name.clone(),
+            downloads: addon.number_of_downloads,
+            game_version: game_version.game_version.clone(),
+            installed,
+        })
+    }
+}
+
+fn print_table(rows: &[SearchRow]) {
+    if rows.is_empty() {
+        println!("No addons found");
+        return;
+    }
+
+    let header = [
+        "ID",
+        "SOURCE",
+        "NAME",
+        "DOWNLOADS",
+        "GAME VERSION",
+        "INSTALLED",
+    ];
+
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.id.to_string(),
+                row.source.clone(),
+                row.name.clone(),
+                row.downloads.to_formatted_string(&Locale::en),
+                row.game_version.clone(),
+                if row.installed { "yes" } else { "no" }.to_owned(),
+            ]
+        })
+        .collect();
+
+    format::print_table(&header, &rows);
+}
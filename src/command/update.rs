@@ -1,29 +1,111 @@
 #![allow(clippy::type_complexity)]
 
+use crate::cli::ReportFormat;
 use crate::log_error;
 use crate::Result;
 
 use ajour_core::addon::Addon;
+use ajour_core::backup::addon::{snapshot_addon, SnapshotSettings};
 use ajour_core::cache::{
-    load_addon_cache, load_fingerprint_cache, update_addon_cache, AddonCache, AddonCacheEntry,
-    FingerprintCache,
+    cache_installed_addon, load_addon_cache, load_fingerprint_cache, AddonCache, FingerprintCache,
 };
 use ajour_core::config::{load_config, Flavor};
 use ajour_core::fs::install_addon;
+use ajour_core::lockfile::lock_installed_addon;
 use ajour_core::network::download_addon;
 use ajour_core::parse::{read_addon_directory, update_addon_fingerprint};
-use ajour_core::repository::RepositoryKind;
 
 use anyhow::{format_err, Context};
 use async_std::sync::{Arc, Mutex};
 use async_std::task;
+use serde::Serialize;
 
 use futures::future::join_all;
 
-use std::convert::TryFrom;
 use std::path::PathBuf;
 
-pub fn update_all_addons() -> Result<()> {
+/// Result of `ajour update`, which decides the exit code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateOutcome {
+    /// All addons are up to date.
+    UpToDate,
+    /// Updates were applied, or found when doing a dry run.
+    Updated,
+    /// One or more addons failed to update, or a flavor couldn't be read.
+    Failed,
+}
+
+impl UpdateOutcome {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            UpdateOutcome::UpToDate => 0,
+            UpdateOutcome::Updated => 2,
+            UpdateOutcome::Failed => 3,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct UpdateReport {
+    dry_run: bool,
+    updated: Vec<ReportEntry>,
+    failed: Vec<ReportEntry>,
+    skipped: Vec<ReportEntry>,
+    failed_flavors: Vec<FlavorError>,
+}
+
+#[derive(Debug, Serialize)]
+struct FlavorError {
+    flavor: &'static str,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ReportEntry {
+    flavor: &'static str,
+    folder: String,
+    title: String,
+    current_version: Option<String>,
+    new_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+}
+
+impl ReportEntry {
+    fn new(flavor: Flavor, addon: &Addon) -> Self {
+        ReportEntry {
+            flavor: flavor.as_str(),
+            folder: addon.primary_folder_id.clone(),
+            title: addon.title().to_owned(),
+            current_version: addon.version().map(str::to_owned),
+            new_version: addon.relevant_release_package().map(|p| p.version),
+            error: None,
+            reason: None,
+        }
+    }
+
+    fn skipped(flavor: Flavor, addon: &Addon, reason: &'static str) -> Self {
+        ReportEntry {
+            reason: Some(reason),
+            ..ReportEntry::new(flavor, addon)
+        }
+    }
+}
+
+/// Update addons.
+///
+/// All addons of all flavors are updated, unless a `flavor` is given or
+/// `addons` are selected by folder name or title. `exclude` takes addons
+/// the same way.
+pub fn update(
+    flavor: Option<Flavor>,
+    addons: Vec<String>,
+    exclude: Vec<String>,
+    dry_run: bool,
+    report_format: Option<ReportFormat>,
+) -> Result<UpdateOutcome> {
     log::info!("Checking for addon updates...");
 
     task::block_on(async {
@@ -34,71 +116,154 @@ pub fn update_all_addons() -> Result<()> {
 
         let addon_cache: Arc<Mutex<_>> = Arc::new(Mutex::new(load_addon_cache().await?));
 
+        let mut report = UpdateReport {
+            dry_run,
+            ..Default::default()
+        };
         let mut addons_to_update = vec![];
 
-        // Update addons for both flavors
-        for flavor in Flavor::ALL.iter() {
+        let flavors = match flavor {
+            Some(flavor) => vec![flavor],
+            None => Flavor::ALL.to_vec(),
+        };
+
+        // Selectors which matched an addon in any flavor
+        let mut matched_selectors = vec![];
+
+        for flavor in flavors {
             // Only returns None if the path isn't set in the config
-            let addon_directory = config.get_addon_directory_for_flavor(flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour and make sure a WoW directory is set before using the command line."))?;
+            let addon_directory = config.get_addon_directory_for_flavor(&flavor).ok_or_else(|| format_err!("No WoW directory set. Launch Ajour or use `ajour config set wow.directory <path>` to set one before using the command line."))?;
 
-            if let Ok(addons) = read_addon_directory(
+            // Flavors that aren't installed have nothing to update
+            if !addon_directory.exists() {
+                continue;
+            }
+
+            let addons_for_flavor = match read_addon_directory(
                 Some(addon_cache.clone()),
                 Some(fingerprint_cache.clone()),
                 &addon_directory,
-                *flavor,
+                flavor,
             )
             .await
+            .context(format!("failed to read addons for {}", flavor))
             {
-                // Get any saved release channel preferences from config
-                let release_channels = config
-                    .addons
-                    .release_channels
-                    .get(flavor)
-                    .cloned()
-                    .unwrap_or_default();
-
-                // Get any ingnored addons from the config
-                let ignored_ids = config
-                    .addons
-                    .ignored
-                    .get(flavor)
-                    .cloned()
-                    .unwrap_or_default();
+                Ok(addons) => addons,
+                Err(e) => {
+                    log_error(&e);
+
+                    report.failed_flavors.push(FlavorError {
+                        flavor: flavor.as_str(),
+                        error: format!("{:#}", e),
+                    });
+                    continue;
+                }
+            };
+
+            let num_stale = addons_for_flavor
+                .iter()
+                .filter(|a| a.is_remote_stale())
+                .count();
+            if num_stale > 0 {
+                log::warn!(
+                    "{} - unable to reach remote repositories, skipping update check for {} addons",
+                    flavor,
+                    num_stale
+                );
+            }
 
-                // Filter out any ignored addons
-                for mut addon in addons
-                    .into_iter()
-                    .filter(|a| !ignored_ids.iter().any(|i| i == &a.primary_folder_id))
-                {
-                    // Apply release channel preference
-                    if let Some(channel) = release_channels.get(&addon.primary_folder_id) {
-                        addon.release_channel = *channel;
+            // Get any saved release channel preferences from config
+            let release_channels = config
+                .addons
+                .release_channels
+                .get(&flavor)
+                .cloned()
+                .unwrap_or_default();
+
+            // Get any ingnored addons from the config
+            let ignored_ids = config
+                .addons
+                .ignored
+                .get(&flavor)
+                .cloned()
+                .unwrap_or_default();
+
+            for mut addon in addons_for_flavor {
+                // Only update selected addons, if any are selected
+                if !addons.is_empty() {
+                    match addons.iter().find(|s| matches_selector(&addon, s)) {
+                        Some(selector) => matched_selectors.push(selector.clone()),
+                        None => continue,
                     }
+                }
+
+                // Apply release channel preference
+                if let Some(channel) = release_channels.get(&addon.primary_folder_id) {
+                    addon.release_channel = *channel;
+                }
+
+                // Stale addons only have what we know from the cache
+                if addon.is_remote_stale() {
+                    report
+                        .skipped
+                        .push(ReportEntry::skipped(flavor, &addon, "unreachable"));
+                    continue;
+                }
 
-                    if let Some(package) = addon.relevant_release_package() {
-                        // Directory to temporarily save downloaded addon
-                        let temp_directory = config
-                            .get_download_directory_for_flavor(*flavor)
-                            .expect("Expected a valid path");
-
-                        // Only add addons that have an update available
-                        if addon.is_updatable(&package) {
-                            addons_to_update.push((
-                                addon_cache.clone(),
-                                fingerprint_cache.clone(),
-                                *flavor,
-                                addon,
-                                temp_directory,
-                                addon_directory.clone(),
-                            ));
-                        }
+                let package = match addon.relevant_release_package() {
+                    Some(package) => package,
+                    None if addon.repository_kind().is_none() => {
+                        report
+                            .skipped
+                            .push(ReportEntry::skipped(flavor, &addon, "unknown"));
+                        continue;
                     }
+                    None => continue,
+                };
+
+                // Only add addons that have an update available
+                if !addon.is_updatable(&package) {
+                    continue;
+                }
+
+                if ignored_ids.iter().any(|i| i == &addon.primary_folder_id) {
+                    report
+                        .skipped
+                        .push(ReportEntry::skipped(flavor, &addon, "ignored"));
+                    continue;
+                }
+
+                if exclude.iter().any(|s| matches_selector(&addon, s)) {
+                    report
+                        .skipped
+                        .push(ReportEntry::skipped(flavor, &addon, "excluded"));
+                    continue;
                 }
+
+                // Directory to temporarily save downloaded addon
+                let temp_directory = config
+                    .get_download_directory_for_flavor(flavor)
+                    .expect("Expected a valid path");
+
+                addons_to_update.push((
+                    addon_cache.clone(),
+                    fingerprint_cache.clone(),
+                    flavor,
+                    addon,
+                    temp_directory,
+                    addon_directory.clone(),
+                    SnapshotSettings::from_config(&config, flavor),
+                ));
             }
         }
 
+        if let Some(selector) = addons.iter().find(|s| !matched_selectors.contains(s)) {
+            return Err(format_err!("No addon found matching {:?}", selector));
+        }
+
         let num_updates = addons_to_update.len();
-        let mut num_errors = 0;
+        // A flavor that couldn't be read was never checked for updates
+        let mut num_errors = report.failed_flavors.len();
 
         log::info!("{} addons have an update available", num_updates);
 
@@ -120,48 +285,98 @@ pub fn update_all_addons() -> Result<()> {
                 );
             });
 
-        if num_updates > 0 {
-            log::info!("Updating... this may take a minute");
-        }
+        if dry_run {
+            report.updated = addons_to_update
+                .iter()
+                .map(|(_, _, flavor, addon, ..)| ReportEntry::new(*flavor, addon))
+                .collect();
+        } else {
+            if num_updates > 0 {
+                log::info!("Updating... this may take a minute");
+            }
 
-        // Call `update_addon` on each addon concurrently
-        for result in join_all(addons_to_update.into_iter().map(update_addon)).await {
-            // Log any errors updating an addon
-            if let Err(e) = result {
-                log_error(&e);
+            let entries: Vec<_> = addons_to_update
+                .iter()
+                .map(|(_, _, flavor, addon, ..)| ReportEntry::new(*flavor, addon))
+                .collect();
+
+            // Call `update_addon` on each addon concurrently
+            for (mut entry, result) in entries
+                .into_iter()
+                .zip(join_all(addons_to_update.into_iter().map(update_addon)).await)
+            {
+                // Log any errors updating an addon
+                if let Err(e) = result {
+                    log_error(&e);
+
+                    num_errors += 1;
+
+                    entry.error = Some(format!("{:#}", e));
+                    report.failed.push(entry);
+                } else {
+                    report.updated.push(entry);
+                }
+            }
 
-                num_errors += 1;
+            if num_errors > 0 {
+                log::error!("{} addons failed to update", num_errors);
+            } else if num_updates > 0 {
+                log::info!("All addons updated successfully!");
             }
         }
 
-        if num_errors > 0 {
-            log::error!("{} addons failed to update", num_errors);
-        } else if num_updates > 0 {
-            log::info!("All addons updated successfully!");
-        } else if num_updates == 0 {
+        if num_updates == 0 && num_errors == 0 {
             log::info!("All addons are up to date!");
         }
 
-        Result::Ok(())
+        if let Some(ReportFormat::Json) = report_format {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+
+        let outcome = if num_errors > 0 {
+            UpdateOutcome::Failed
+        } else if num_updates > 0 {
+            UpdateOutcome::Updated
+        } else {
+            UpdateOutcome::UpToDate
+        };
+
+        Result::Ok(outcome)
     })
 }
 
+/// Returns `true` if `selector` is the primary folder id or title of `addon`.
+fn matches_selector(addon: &Addon, selector: &str) -> bool {
+    addon.primary_folder_id == selector || addon.title().eq_ignore_ascii_case(selector)
+}
+
 /// Updates an addon
 ///
 /// Downloads the latest file, extracts it and refingerprints the addon, saving it to the cache.
 async fn update_addon(
-    (addon_cache, fingerprint_cache, flavor, mut addon, temp_directory, addon_directory): (
+    (addon_cache, fingerprint_cache, flavor, mut addon, temp_directory, addon_directory, snapshot): (
         Arc<Mutex<AddonCache>>,
         Arc<Mutex<FingerprintCache>>,
         Flavor,
         Addon,
         PathBuf,
         PathBuf,
+        Option<SnapshotSettings>,
     ),
 ) -> Result<()> {
     // Download the update to the temp directory
     download_addon(&addon, &temp_directory).await?;
 
+    // Snapshot the current version so the update can be reverted
+    if let Some(settings) = snapshot {
+        if let Err(e) = snapshot_addon(addon.clone(), flavor, settings)
+            .await
+            .context(format!("failed to snapshot {}", addon.title()))
+        {
+            log_error(&e);
+        }
+    }
+
     // Extracts addon from the downloaded archive to the addon directory and removes the archive
     let installed_folders = install_addon(&addon, &temp_directory, &addon_directory).await?;
 
@@ -197,14 +412,17 @@ async fn update_addon(
         }
     }
 
-    // Update cache for addon
-    if addon.repository_kind() == Some(RepositoryKind::Tukui)
-        || addon.repository_kind() == Some(RepositoryKind::WowI)
-        || matches!(addon.repository_kind(), Some(RepositoryKind::Git(_)))
+    // Record the installed release in the lockfile
+    if let Err(e) = lock_installed_addon(flavor, addon.clone(), addon_directory.clone())
+        .await
+        .context(format!("failed to lock {}", addon.title()))
     {
-        if let Ok(entry) = AddonCacheEntry::try_from(&addon) {
-            update_addon_cache(addon_cache, entry, flavor).await?;
-        }
+        log_error(&e);
+    }
+
+    // Update cache for addon
+    if addon.repository_kind().is_some() {
+        cache_installed_addon(addon_cache, addon, addon_directory, flavor).await?;
     }
 
     Ok(())
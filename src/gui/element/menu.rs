@@ -20,6 +20,7 @@ pub fn data_container<'a>(
     mode: &Mode,
     state: &HashMap<Mode, State>,
     error: &Option<anyhow::Error>,
+    is_offline: bool,
     config: &Config,
     valid_flavors: &[Flavor],
     settings_button_state: &'a mut button::State,
@@ -367,7 +368,25 @@ pub fn data_container<'a>(
         .align_items(Align::Center);
 
     // Add space above settings_row.
-    let settings_column = Column::new().push(settings_row);
+    let mut settings_column = Column::new().push(settings_row);
+
+    // Displays a banner if we couldn't reach the network and are showing
+    // addons built from cached data.
+    if is_offline {
+        let offline_text = Text::new(
+            "Offline - showing cached addon data. Updates are unavailable until Ajour is back online.",
+        )
+        .size(DEFAULT_FONT_SIZE)
+        .width(Length::Fill)
+        .horizontal_alignment(HorizontalAlignment::Center);
+
+        let offline_container = Container::new(offline_text)
+            .width(Length::Fill)
+            .padding(5)
+            .style(style::NormalErrorForegroundContainer(color_palette));
+
+        settings_column = settings_column.push(offline_container);
+    }
 
     // Wraps it in a container.
     Container::new(settings_column).style(style::BrightForegroundContainer(color_palette))
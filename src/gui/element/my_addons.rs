@@ -1,16 +1,21 @@
 use {
     super::{DEFAULT_FONT_SIZE, DEFAULT_PADDING},
     crate::gui::{
-        style, ColumnKey, ColumnState, Config, ExpandType, Flavor, Interaction, Message, Mode,
-        ReleaseChannel, SortDirection, State,
+        style, ColumnKey, ColumnState, Config, ExpandType, FingerprintDiagnosticsState, Flavor,
+        Interaction, Message, Mode, ProfileChoice, ProfileState, ReleaseChannel,
+        SavedVariablesState, SortDirection, State,
     },
     ajour_core::{
         addon::{Addon, AddonState},
         theme::ColorPalette,
+        wtf::{AddOnsTxt, Character},
     },
     ajour_widgets::{header, Header, TableRow},
     chrono::prelude::*,
-    iced::{button, Align, Button, Column, Container, Element, Length, PickList, Row, Space, Text},
+    iced::{
+        button, Align, Button, Checkbox, Column, Container, Element, Length, PickList, Row, Space,
+        Text,
+    },
     std::collections::HashMap,
 };
 
@@ -95,12 +100,16 @@ pub fn titles_row_header<'a>(
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn data_row_container<'a, 'b>(
     color_palette: ColorPalette,
     addon: &'a mut Addon,
     is_addon_expanded: bool,
     expand_type: &'a ExpandType,
     column_config: &'b [(ColumnKey, Length, bool)],
+    characters: &'b [(Character, AddOnsTxt)],
+    saved_variables_state: Option<&'a mut SavedVariablesState>,
+    fingerprint_diagnostics_state: Option<&'a mut FingerprintDiagnosticsState>,
 ) -> TableRow<'a, Message> {
     let default_height = Length::Units(26);
     let default_row_height = 26;
@@ -113,6 +122,7 @@ pub fn data_row_container<'a, 'b>(
     let website_url = addon.website_url().map(str::to_string);
     let changelog_url = addon.changelog_url().map(str::to_string);
     let repository_kind = addon.repository_kind();
+    let addon_id = addon.primary_folder_id.clone();
 
     // Check if current addon is expanded.
     let addon_cloned = addon.clone();
@@ -580,10 +590,201 @@ pub fn data_row_container<'a, 'b>(
 
             let changelog_button: Element<Interaction> = changelog_button.into();
 
+            // Restores the snapshot taken before the addon was last updated
+            let revert_button: Element<Interaction> = Button::new(
+                &mut addon.revert_btn_state,
+                Text::new("Revert").size(DEFAULT_FONT_SIZE),
+            )
+            .on_press(Interaction::Revert(addon.primary_folder_id.clone()))
+            .style(style::DefaultButton(color_palette))
+            .into();
+
             let test_row = Row::new()
                 .push(release_channel_list)
                 .push(release_date_text_container);
 
+            // Enabled state of the addon per character, from their AddOns.txt.
+            let characters_title = Text::new("Enabled for").size(DEFAULT_FONT_SIZE);
+            let characters_title_container = Container::new(characters_title)
+                .style(style::HoverableBrightForegroundContainer(color_palette));
+            let mut characters_column = Column::new().spacing(3);
+
+            if characters.is_empty() {
+                characters_column = characters_column
+                    .push(Text::new("No characters found").size(DEFAULT_FONT_SIZE));
+            }
+
+            for (idx, (character, addons_txt)) in characters.iter().enumerate() {
+                let id = addon.primary_folder_id.clone();
+                let checkbox = Checkbox::new(
+                    addons_txt.is_enabled(&id),
+                    character.to_string(),
+                    move |is_checked| {
+                        Message::Interaction(Interaction::ToggleAddonEnabled(
+                            idx,
+                            id.clone(),
+                            is_checked,
+                        ))
+                    },
+                )
+                .style(style::DefaultCheckbox(color_palette))
+                .text_size(DEFAULT_FONT_SIZE)
+                .spacing(5);
+
+                characters_column = characters_column.push(checkbox);
+            }
+
+            // SavedVariables files of the addon, with their size and variables.
+            let saved_variables_title = Text::new("SavedVariables").size(DEFAULT_FONT_SIZE);
+            let saved_variables_title_container = Container::new(saved_variables_title)
+                .style(style::HoverableBrightForegroundContainer(color_palette));
+            let mut saved_variables_column = Column::new().spacing(3);
+
+            if let Some(state) = saved_variables_state.filter(|state| state.addon_id == addon_id) {
+                if state.files.is_empty() {
+                    saved_variables_column = saved_variables_column
+                        .push(Text::new("No SavedVariables found").size(DEFAULT_FONT_SIZE));
+                }
+
+                for (idx, row) in state.files.iter_mut().enumerate() {
+                    let file = &row.file;
+
+                    let title = Text::new(format!(
+                        "{}  -  {}  -  {:.1} KB",
+                        file.folder,
+                        file.scope,
+                        file.size as f64 / 1024.0
+                    ))
+                    .size(DEFAULT_FONT_SIZE);
+
+                    let variables = match &file.error {
+                        Some(error) => error.clone(),
+                        None => file
+                            .variables
+                            .iter()
+                            .map(|(name, entries)| match entries {
+                                Some(entries) => format!("{} ({} entries)", name, entries),
+                                None => name.clone(),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    };
+                    let variables_text = Text::new(variables).size(DEFAULT_FONT_SIZE);
+                    let variables_container = Container::new(variables_text)
+                        .style(style::FadedBrightForegroundContainer(color_palette));
+
+                    let mut export_button = Button::new(
+                        &mut row.export_btn_state,
+                        Text::new("Export").size(DEFAULT_FONT_SIZE),
+                    )
+                    .style(style::DefaultButton(color_palette));
+
+                    // A file which can't be parsed isn't worth exporting.
+                    if file.error.is_none() {
+                        export_button =
+                            export_button.on_press(Interaction::ExportSavedVariables(idx));
+                    }
+
+                    let export_button: Element<Interaction> = export_button.into();
+
+                    let import_button: Element<Interaction> = Button::new(
+                        &mut row.import_btn_state,
+                        Text::new("Import").size(DEFAULT_FONT_SIZE),
+                    )
+                    .on_press(Interaction::ImportSavedVariables(idx))
+                    .style(style::DefaultButton(color_palette))
+                    .into();
+
+                    let file_row = Row::new()
+                        .push(
+                            Column::new()
+                                .push(title)
+                                .push(variables_container)
+                                .width(Length::Fill),
+                        )
+                        .push(export_button.map(Message::Interaction))
+                        .push(Space::new(Length::Units(5), Length::Units(0)))
+                        .push(import_button.map(Message::Interaction))
+                        .align_items(Align::Center);
+
+                    saved_variables_column = saved_variables_column.push(file_row);
+                }
+
+                if let Some(status) = &state.status {
+                    saved_variables_column = saved_variables_column.push(
+                        Container::new(Text::new(status).size(DEFAULT_FONT_SIZE))
+                            .style(style::FadedBrightForegroundContainer(color_palette)),
+                    );
+                }
+            }
+
+            // Fingerprint of each folder per file, to tell why an addon doesn't
+            // match its release.
+            let fingerprints_title = Text::new("Fingerprints").size(DEFAULT_FONT_SIZE);
+            let fingerprints_title_container = Container::new(fingerprints_title)
+                .style(style::HoverableBrightForegroundContainer(color_palette));
+            let mut fingerprints_column = Column::new().spacing(3);
+
+            if let Some(state) = fingerprint_diagnostics_state {
+                let is_current = state.addon_id == addon.primary_folder_id;
+
+                let mut check_button = Button::new(
+                    &mut state.check_btn_state,
+                    Text::new("Check files").size(DEFAULT_FONT_SIZE),
+                )
+                .style(style::DefaultButton(color_palette));
+
+                if !(is_current && state.checking) {
+                    check_button = check_button.on_press(Interaction::DiagnoseFingerprints);
+                }
+
+                let check_button: Element<Interaction> = check_button.into();
+                fingerprints_column =
+                    fingerprints_column.push(check_button.map(Message::Interaction));
+
+                if is_current {
+                    for diagnostics in state.diagnostics.iter() {
+                        let expected = match diagnostics.expected {
+                            Some(expected) if expected == diagnostics.fingerprint => {
+                                "matches Curse".to_owned()
+                            }
+                            Some(expected) => format!("Curse expects {}", expected),
+                            None => "no fingerprint from Curse".to_owned(),
+                        };
+                        let title = Text::new(format!(
+                            "{}  -  {}  -  {}",
+                            diagnostics.folder, diagnostics.fingerprint, expected
+                        ))
+                        .size(DEFAULT_FONT_SIZE);
+
+                        let mut files_column = Column::new();
+
+                        if !diagnostics.has_baseline {
+                            files_column = files_column.push(
+                                Text::new("Files changed since installing are unknown")
+                                    .size(DEFAULT_FONT_SIZE),
+                            );
+                        }
+
+                        for file in diagnostics.files.iter() {
+                            let hash = file.hash.map(|h| h.to_string()).unwrap_or_default();
+                            let text = match file.change {
+                                Some(change) => format!("{:>10}  {} ({})", hash, file.path, change),
+                                None => format!("{:>10}  {}", hash, file.path),
+                            };
+
+                            files_column =
+                                files_column.push(Text::new(text).size(DEFAULT_FONT_SIZE));
+                        }
+
+                        let files_container = Container::new(files_column)
+                            .style(style::FadedBrightForegroundContainer(color_palette));
+
+                        fingerprints_column = fingerprints_column.push(title).push(files_container);
+                    }
+                }
+            }
+
             let button_row = Row::new()
                 .push(Space::new(Length::Fill, Length::Units(0)))
                 .push(website_button.map(Message::Interaction))
@@ -592,6 +793,8 @@ pub fn data_row_container<'a, 'b>(
                 .push(Space::new(Length::Units(5), Length::Units(0)))
                 .push(force_download_button.map(Message::Interaction))
                 .push(Space::new(Length::Units(5), Length::Units(0)))
+                .push(revert_button.map(Message::Interaction))
+                .push(Space::new(Length::Units(5), Length::Units(0)))
                 .push(ignore_button.map(Message::Interaction))
                 .push(Space::new(Length::Units(5), Length::Units(0)))
                 .push(delete_button.map(Message::Interaction))
@@ -608,6 +811,18 @@ pub fn data_row_container<'a, 'b>(
                 .push(release_channel_title_container)
                 .push(Space::new(Length::Units(0), Length::Units(3)))
                 .push(test_row)
+                .push(Space::new(Length::Units(0), Length::Units(15)))
+                .push(characters_title_container)
+                .push(Space::new(Length::Units(0), Length::Units(3)))
+                .push(characters_column)
+                .push(Space::new(Length::Units(0), Length::Units(15)))
+                .push(saved_variables_title_container)
+                .push(Space::new(Length::Units(0), Length::Units(3)))
+                .push(saved_variables_column)
+                .push(Space::new(Length::Units(0), Length::Units(15)))
+                .push(fingerprints_title_container)
+                .push(Space::new(Length::Units(0), Length::Units(3)))
+                .push(fingerprints_column)
                 .push(space)
                 .push(button_row)
                 .push(bottom_space);
@@ -648,6 +863,7 @@ pub fn menu_container<'a>(
     flavor: Flavor,
     update_all_button_state: &'a mut button::State,
     refresh_button_state: &'a mut button::State,
+    profile_state: &'a mut ProfileState,
     state: &HashMap<Mode, State>,
     addons: &[Addon],
     config: &Config,
@@ -731,8 +947,31 @@ pub fn menu_container<'a>(
         .push(Space::new(Length::Units(7), Length::Units(0)))
         .push(update_all_button.map(Message::Interaction))
         .push(Space::new(Length::Units(7), Length::Units(0)))
-        .push(status_container)
-        .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)));
+        .push(status_container);
+
+    // Profile selector, shown once a profile has been saved for the flavor.
+    if config
+        .addons
+        .profiles
+        .get(&flavor)
+        .map_or(false, |p| !p.is_empty())
+    {
+        let profile_pick_list = PickList::new(
+            &mut profile_state.pick_list_state,
+            ProfileChoice::all(config, flavor),
+            Some(ProfileChoice::active(config, flavor)),
+            |choice| Message::Interaction(Interaction::ProfileSelected(choice)),
+        )
+        .text_size(14)
+        .width(Length::Units(150))
+        .style(style::PickList(color_palette));
+
+        settings_row = settings_row
+            .push(Space::new(Length::Fill, Length::Units(0)))
+            .push(profile_pick_list);
+    }
+
+    settings_row = settings_row.push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)));
 
     // Add space above settings_row.
     let settings_column = Column::new()
@@ -3,14 +3,17 @@
 use {
     super::{DEFAULT_FONT_SIZE, DEFAULT_PADDING},
     crate::gui::{
-        style, BackupFolderKind, BackupState, CatalogColumnKey, CatalogColumnSettings, ColumnKey,
-        ColumnSettings, DirectoryType, Interaction, Message, ScaleState, SelfUpdateChannelState,
-        ThemeState,
+        style, BackupFolderKind, BackupState, CatalogColumnKey, CatalogColumnSettings,
+        CatalogSourcesState, ColumnKey, ColumnSettings, DirectoryType, Interaction, ManifestState,
+        Message, OrphansState, ProfileState, ScaleState, SelfUpdateChannelState, ThemeState,
+    },
+    ajour_core::{
+        config::{BackupFormat, Config},
+        theme::ColorPalette,
     },
-    ajour_core::{config::Config, theme::ColorPalette},
     iced::{
         button, scrollable, Align, Button, Checkbox, Column, Container, Element, Length, PickList,
-        Row, Scrollable, Space, Text, VerticalAlignment,
+        Row, Scrollable, Space, Text, TextInput, VerticalAlignment,
     },
 };
 
@@ -28,6 +31,10 @@ pub fn data_container<'a, 'b>(
     catalog_column_config: &'b [(CatalogColumnKey, Length, bool)],
     open_config_dir_button_state: &'a mut button::State,
     self_update_channel_state: &'a mut SelfUpdateChannelState,
+    catalog_sources_state: &'a mut CatalogSourcesState,
+    manifest_state: &'a mut ManifestState,
+    profile_state: &'a mut ProfileState,
+    orphans_state: &'a mut OrphansState,
 ) -> Container<'a, Message> {
     let mut scrollable = Scrollable::new(scrollable_state)
         .spacing(1)
@@ -151,7 +158,7 @@ pub fn data_container<'a, 'b>(
             .push(scale_buttons_row)
     };
 
-    let (backup_title_row, backup_directory_row, backup_now_row) = {
+    let (backup_title_row, backup_directory_row, backup_now_row, backup_archives_column) = {
         // Title for the Backup section.
         let backup_title_text = Text::new("Backup").size(DEFAULT_FONT_SIZE);
         let backup_title_text_container = Container::new(backup_title_text)
@@ -179,6 +186,20 @@ pub fn data_container<'a, 'b>(
         .style(style::BrightBackgroundContainer(color_palette))
         .into();
 
+        // Incremental backups only save files which changed since the last one.
+        let incremental_checkbox: Element<_> = Container::new(
+            Checkbox::new(
+                config.backup_format == BackupFormat::Incremental,
+                "Incremental",
+                Interaction::ToggleIncrementalBackup,
+            )
+            .text_size(DEFAULT_FONT_SIZE)
+            .spacing(5)
+            .style(style::DefaultCheckbox(color_palette)),
+        )
+        .style(style::BrightBackgroundContainer(color_palette))
+        .into();
+
         // Directory button for Backup directory selection.
         let directory_button_title_container =
             Container::new(Text::new("Select Directory").size(DEFAULT_FONT_SIZE))
@@ -217,6 +238,8 @@ pub fn data_container<'a, 'b>(
             .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
             .push(wtf_folder_checkbox.map(Message::Interaction))
             .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+            .push(incremental_checkbox.map(Message::Interaction))
+            .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
             .push(directory_button.map(Message::Interaction))
             .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
             .push(directory_data_text_container);
@@ -244,17 +267,41 @@ pub fn data_container<'a, 'b>(
             // Only show button as clickable if it's not currently backing up and
             // the wow folder is chosen and at least one of the folders is selected
             // for backup
-            if !backup_state.backing_up
+            let is_busy = backup_state.backing_up || backup_state.restoring;
+
+            if !is_busy
                 && config.wow.directory.is_some()
                 && (config.backup_addons || config.backup_wtf)
             {
                 backup_button = backup_button.on_press(Interaction::Backup);
             }
 
+            let restore_button_title_container =
+                Container::new(Text::new("Restore").size(DEFAULT_FONT_SIZE))
+                    .width(Length::FillPortion(1))
+                    .center_x()
+                    .align_x(Align::Center);
+            let mut restore_button = Button::new(
+                &mut backup_state.restore_btn_state,
+                restore_button_title_container,
+            )
+            .width(Length::Units(120))
+            .style(style::DefaultBoxedButton(color_palette));
+
+            // Restoring replaces the folders in the wow directory, so it has
+            // to be chosen
+            if !is_busy && config.wow.directory.is_some() {
+                restore_button = restore_button.on_press(Interaction::Restore);
+            }
+
             let backup_status_text = if backup_state.backing_up {
                 Text::new("Backing up...")
                     .size(DEFAULT_FONT_SIZE)
                     .vertical_alignment(VerticalAlignment::Center)
+            } else if backup_state.restoring {
+                Text::new("Restoring...")
+                    .size(DEFAULT_FONT_SIZE)
+                    .vertical_alignment(VerticalAlignment::Center)
             } else {
                 let as_of = backup_state
                     .last_backup
@@ -272,10 +319,13 @@ pub fn data_container<'a, 'b>(
                 .style(style::NormalBackgroundContainer(color_palette));
 
             let backup_button: Element<Interaction> = backup_button.into();
+            let restore_button: Element<Interaction> = restore_button.into();
 
             backup_now_row = backup_now_row
                 .push(backup_button.map(Message::Interaction))
                 .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+                .push(restore_button.map(Message::Interaction))
+                .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
                 .push(backup_status_text_container);
         } else {
             let backup_status_text =
@@ -291,10 +341,77 @@ pub fn data_container<'a, 'b>(
             backup_now_row = backup_now_row.push(backup_status_text_container);
         }
 
+        // Each archive in the backup directory, newest first, with its size,
+        // what it contains and a button to delete it.
+        let mut backup_archives_column = Column::new();
+
+        let is_busy = backup_state.backing_up || backup_state.restoring;
+
+        for row_state in backup_state.archives.iter_mut() {
+            let archive = &row_state.archive;
+
+            let contents = if row_state.sections.is_empty() {
+                "Empty".to_owned()
+            } else {
+                row_state
+                    .sections
+                    .iter()
+                    .map(|s| format!("{} {}", s.flavor, s.kind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            let archive_text = Text::new(format!(
+                "{}  -  {}  -  {:.1} MB  -  {}",
+                archive.as_of.format("%Y-%m-%d %H:%M:%S"),
+                archive.format,
+                archive.size as f64 / 1024.0 / 1024.0,
+                contents
+            ))
+            .size(DEFAULT_FONT_SIZE)
+            .vertical_alignment(VerticalAlignment::Center);
+            let archive_text_container = Container::new(archive_text)
+                .height(Length::Units(25))
+                .center_y()
+                .style(style::NormalBackgroundContainer(color_palette));
+
+            let mut delete_button = Button::new(
+                &mut row_state.delete_btn_state,
+                Text::new("Delete").size(DEFAULT_FONT_SIZE),
+            )
+            .style(style::DefaultDeleteButton(color_palette));
+
+            if !is_busy {
+                delete_button =
+                    delete_button.on_press(Interaction::DeleteBackup(archive.path.clone()));
+            }
+
+            let export_button: Element<Interaction> = Button::new(
+                &mut row_state.export_btn_state,
+                Text::new("Export").size(DEFAULT_FONT_SIZE),
+            )
+            .style(style::DefaultButton(color_palette))
+            .on_press(Interaction::ExportBackup(archive.path.clone()))
+            .into();
+
+            let delete_button: Element<Interaction> = delete_button.into();
+
+            let row = Row::new()
+                .align_items(Align::Center)
+                .height(Length::Units(26))
+                .push(delete_button.map(Message::Interaction))
+                .push(export_button.map(Message::Interaction))
+                .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+                .push(archive_text_container);
+
+            backup_archives_column = backup_archives_column.push(row);
+        }
+
         (
             backup_title_text_container,
             backup_directory_row,
             backup_now_row,
+            backup_archives_column,
         )
     };
 
@@ -312,6 +429,340 @@ pub fn data_container<'a, 'b>(
         Column::new().push(checkbox_container)
     };
 
+    let manifest_row = {
+        let export_button_title_container =
+            Container::new(Text::new("Export Addons").size(DEFAULT_FONT_SIZE))
+                .width(Length::FillPortion(1))
+                .center_x()
+                .align_x(Align::Center);
+        let mut export_button = Button::new(
+            &mut manifest_state.export_btn_state,
+            export_button_title_container,
+        )
+        .width(Length::Units(120))
+        .style(style::DefaultBoxedButton(color_palette));
+
+        let import_button_title_container =
+            Container::new(Text::new("Import Addons").size(DEFAULT_FONT_SIZE))
+                .width(Length::FillPortion(1))
+                .center_x()
+                .align_x(Align::Center);
+        let mut import_button = Button::new(
+            &mut manifest_state.import_btn_state,
+            import_button_title_container,
+        )
+        .width(Length::Units(120))
+        .style(style::DefaultBoxedButton(color_palette));
+
+        if config.wow.directory.is_some() {
+            export_button = export_button.on_press(Interaction::ExportAddons);
+            import_button = import_button.on_press(Interaction::ImportAddons);
+        }
+
+        let status = manifest_state.status.clone().unwrap_or_else(|| {
+            format!(
+                "Save the {} addons to a file, or install the addons from one",
+                config.wow.flavor
+            )
+        });
+        let status_text = Text::new(status)
+            .size(DEFAULT_FONT_SIZE)
+            .vertical_alignment(VerticalAlignment::Center);
+        let status_text_container = Container::new(status_text)
+            .height(Length::Units(25))
+            .center_y()
+            .style(style::NormalBackgroundContainer(color_palette));
+
+        let export_button: Element<Interaction> = export_button.into();
+        let import_button: Element<Interaction> = import_button.into();
+
+        Row::new()
+            .push(export_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+            .push(import_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+            .push(status_text_container)
+    };
+
+    let profile_row = {
+        let flavor = config.wow.flavor;
+        let name = profile_state.name.trim();
+        let profiles = config.addons.profiles.get(&flavor);
+
+        let name_input: Element<Interaction> = TextInput::new(
+            &mut profile_state.name_state,
+            "Profile name",
+            &profile_state.name,
+            Interaction::ProfileNameInput,
+        )
+        .size(DEFAULT_FONT_SIZE)
+        .padding(5)
+        .width(Length::Units(150))
+        .style(style::CatalogQueryInput(color_palette))
+        .into();
+
+        let mut save_button = Button::new(
+            &mut profile_state.save_btn_state,
+            Text::new("Save Profile").size(DEFAULT_FONT_SIZE),
+        )
+        .style(style::DefaultBoxedButton(color_palette));
+
+        let mut delete_button = Button::new(
+            &mut profile_state.delete_btn_state,
+            Text::new("Delete Profile").size(DEFAULT_FONT_SIZE),
+        )
+        .style(style::DefaultBoxedButton(color_palette));
+
+        if config.wow.directory.is_some() && !name.is_empty() {
+            save_button = save_button.on_press(Interaction::SaveProfile);
+        }
+
+        if profiles.map_or(false, |p| p.contains_key(name)) {
+            delete_button = delete_button.on_press(Interaction::DeleteProfile);
+        }
+
+        let status = profile_state.status.clone().unwrap_or_else(|| {
+            format!(
+                "Save the {} addons in the AddOns folder as a profile to switch to in My Addons",
+                flavor
+            )
+        });
+        let status_text = Text::new(status)
+            .size(DEFAULT_FONT_SIZE)
+            .vertical_alignment(VerticalAlignment::Center);
+        let status_text_container = Container::new(status_text)
+            .height(Length::Units(25))
+            .center_y()
+            .style(style::NormalBackgroundContainer(color_palette));
+
+        let save_button: Element<Interaction> = save_button.into();
+        let delete_button: Element<Interaction> = delete_button.into();
+
+        Row::new()
+            .align_items(Align::Center)
+            .push(name_input.map(Message::Interaction))
+            .push(Space::new(Length::Units(5), Length::Units(0)))
+            .push(save_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(5), Length::Units(0)))
+            .push(delete_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+            .push(status_text_container)
+    };
+
+    let orphans_column = {
+        let flavor = config.wow.flavor;
+
+        let mut find_button = Button::new(
+            &mut orphans_state.find_btn_state,
+            Text::new("Find Orphaned SavedVariables").size(DEFAULT_FONT_SIZE),
+        )
+        .style(style::DefaultBoxedButton(color_palette));
+
+        let mut delete_button = Button::new(
+            &mut orphans_state.delete_btn_state,
+            Text::new("Back Up and Delete").size(DEFAULT_FONT_SIZE),
+        )
+        .style(style::DefaultBoxedButton(color_palette));
+
+        if config.wow.directory.is_some() && !orphans_state.deleting {
+            find_button = find_button.on_press(Interaction::FindOrphanedSavedVariables);
+
+            // Only the orphans of the selected flavor are deleted.
+            if orphans_state.flavor == Some(flavor) && !orphans_state.orphans.is_empty() {
+                delete_button = delete_button.on_press(Interaction::DeleteOrphanedSavedVariables);
+            }
+        }
+
+        let status = orphans_state.status.clone().unwrap_or_else(|| {
+            format!(
+                "Find the SavedVariables of {} which no installed addon uses",
+                flavor
+            )
+        });
+        let status_text = Text::new(status)
+            .size(DEFAULT_FONT_SIZE)
+            .vertical_alignment(VerticalAlignment::Center);
+        let status_text_container = Container::new(status_text)
+            .height(Length::Units(25))
+            .center_y()
+            .style(style::NormalBackgroundContainer(color_palette));
+
+        let find_button: Element<Interaction> = find_button.into();
+        let delete_button: Element<Interaction> = delete_button.into();
+
+        let button_row = Row::new()
+            .align_items(Align::Center)
+            .push(find_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(5), Length::Units(0)))
+            .push(delete_button.map(Message::Interaction))
+            .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+            .push(status_text_container);
+
+        let mut column = Column::new().spacing(3).push(button_row);
+
+        if orphans_state.flavor == Some(flavor) {
+            for orphan in orphans_state.orphans.iter() {
+                let text = Text::new(format!(
+                    "{}  -  {}  -  {:.1} KB",
+                    orphan.folder,
+                    orphan.scope,
+                    orphan.size as f64 / 1024.0
+                ))
+                .size(DEFAULT_FONT_SIZE);
+
+                column = column.push(
+                    Container::new(text).style(style::NormalBackgroundContainer(color_palette)),
+                );
+            }
+        }
+
+        column
+    };
+
+    let catalog_sources_column = {
+        let title_container = Container::new(Text::new("Catalog Sources").size(DEFAULT_FONT_SIZE))
+            .style(style::BrightBackgroundContainer(color_palette));
+
+        let description = Text::new(
+            "Custom catalogs are added to the official catalogs. Sources higher in the list take priority.",
+        )
+        .size(DEFAULT_FONT_SIZE);
+        let description_container =
+            Container::new(description).style(style::NormalBackgroundContainer(color_palette));
+
+        let mut sources_column = Column::new()
+            .push(title_container)
+            .push(Space::new(Length::Units(0), Length::Units(5)))
+            .push(description_container)
+            .push(Space::new(Length::Units(0), Length::Units(5)));
+
+        // Make sure we have button states for every source.
+        let sources = &config.catalog_sources;
+        catalog_sources_state
+            .rows
+            .resize_with(sources.len(), Default::default);
+
+        // Add each source as up / down buttons + label + remove button
+        let sources_len = sources.len();
+        for (idx, (source, row_state)) in sources
+            .iter()
+            .zip(catalog_sources_state.rows.iter_mut())
+            .enumerate()
+        {
+            let is_first = idx == 0;
+            let is_last = idx == sources_len - 1;
+
+            let mut up_button = Button::new(
+                &mut row_state.up_btn_state,
+                Text::new(" ▲ ").size(11).color(if !is_first {
+                    color_palette.bright.primary
+                } else {
+                    color_palette.normal.primary
+                }),
+            )
+            .style(style::DefaultButton(color_palette));
+            if !is_first {
+                up_button =
+                    up_button.on_press(Interaction::MoveCatalogSourceUp(source.name.clone()));
+            }
+
+            let mut down_button = Button::new(
+                &mut row_state.down_btn_state,
+                Text::new(" ▼ ").size(11).color(if !is_last {
+                    color_palette.bright.primary
+                } else {
+                    color_palette.normal.primary
+                }),
+            )
+            .style(style::DefaultButton(color_palette));
+            if !is_last {
+                down_button =
+                    down_button.on_press(Interaction::MoveCatalogSourceDown(source.name.clone()));
+            }
+
+            let remove_button: Element<Interaction> = Button::new(
+                &mut row_state.remove_btn_state,
+                Text::new("Remove").size(DEFAULT_FONT_SIZE),
+            )
+            .style(style::DefaultDeleteButton(color_palette))
+            .on_press(Interaction::RemoveCatalogSource(source.name.clone()))
+            .into();
+
+            let up_button: Element<Interaction> = up_button.into();
+            let down_button: Element<Interaction> = down_button.into();
+
+            let source_text = Text::new(format!("{} - {}", source.name, source.location))
+                .size(DEFAULT_FONT_SIZE)
+                .vertical_alignment(VerticalAlignment::Center);
+            let source_text_container = Container::new(source_text)
+                .height(Length::Units(25))
+                .center_y()
+                .style(style::NormalBackgroundContainer(color_palette));
+
+            let row = Row::new()
+                .align_items(Align::Center)
+                .height(Length::Units(26))
+                .push(up_button.map(Message::Interaction))
+                .push(down_button.map(Message::Interaction))
+                .push(Space::new(Length::Units(5), Length::Units(0)))
+                .push(source_text_container)
+                .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
+                .push(remove_button.map(Message::Interaction));
+
+            sources_column = sources_column.push(row);
+        }
+
+        let name_input: Element<Interaction> = TextInput::new(
+            &mut catalog_sources_state.name_state,
+            "Name",
+            &catalog_sources_state.name,
+            Interaction::CatalogSourceNameInput,
+        )
+        .size(DEFAULT_FONT_SIZE)
+        .padding(5)
+        .width(Length::Units(150))
+        .style(style::CatalogQueryInput(color_palette))
+        .into();
+
+        let location_input: Element<Interaction> = TextInput::new(
+            &mut catalog_sources_state.location_state,
+            "URL or path to a catalog .json file",
+            &catalog_sources_state.location,
+            Interaction::CatalogSourceLocationInput,
+        )
+        .size(DEFAULT_FONT_SIZE)
+        .padding(5)
+        .width(Length::Units(350))
+        .style(style::CatalogQueryInput(color_palette))
+        .into();
+
+        let mut add_button = Button::new(
+            &mut catalog_sources_state.add_btn_state,
+            Text::new("Add").size(DEFAULT_FONT_SIZE),
+        )
+        .style(style::DefaultBoxedButton(color_palette));
+
+        if !catalog_sources_state.name.trim().is_empty()
+            && !catalog_sources_state.location.trim().is_empty()
+        {
+            add_button = add_button.on_press(Interaction::AddCatalogSource);
+        }
+
+        let add_button: Element<Interaction> = add_button.into();
+
+        let add_row = Row::new()
+            .align_items(Align::Center)
+            .push(name_input.map(Message::Interaction))
+            .push(Space::new(Length::Units(5), Length::Units(0)))
+            .push(location_input.map(Message::Interaction))
+            .push(Space::new(Length::Units(5), Length::Units(0)))
+            .push(add_button.map(Message::Interaction));
+
+        sources_column
+            .push(Space::new(Length::Units(0), Length::Units(5)))
+            .push(add_row)
+    };
+
     let config_column = {
         let config_dir = ajour_core::fs::config_dir();
         let config_dir_string = config_dir.as_path().display().to_string();
@@ -388,6 +839,8 @@ pub fn data_container<'a, 'b>(
         .push(backup_now_row)
         .push(Space::new(Length::Units(0), Length::Units(5)))
         .push(backup_directory_row)
+        .push(Space::new(Length::Units(0), Length::Units(5)))
+        .push(backup_archives_column)
         .push(Space::new(Length::Units(0), Length::Units(20)))
         .push(channel_title)
         .push(Space::new(Length::Units(0), Length::Units(5)))
@@ -396,6 +849,14 @@ pub fn data_container<'a, 'b>(
         .push(addon_title_container)
         .push(Space::new(Length::Units(0), Length::Units(5)))
         .push(hide_addons_column)
+        .push(Space::new(Length::Units(0), Length::Units(5)))
+        .push(manifest_row)
+        .push(Space::new(Length::Units(0), Length::Units(5)))
+        .push(profile_row)
+        .push(Space::new(Length::Units(0), Length::Units(5)))
+        .push(orphans_column)
+        .push(Space::new(Length::Units(0), Length::Units(20)))
+        .push(catalog_sources_column)
         .push(Space::new(Length::Units(0), Length::Units(20)))
         .push(ui_title_container)
         .push(Space::new(Length::Units(0), Length::Units(5)))
@@ -6,21 +6,27 @@ use crate::cli::Opts;
 use crate::Result;
 use ajour_core::{
     addon::{Addon, AddonFolder, AddonState},
+    backup::{ArchiveSection, BackupArchive, RestoreResult},
     cache::{
-        load_addon_cache, load_fingerprint_cache, AddonCache, AddonCacheEntry, FingerprintCache,
+        load_addon_cache, load_catalog_cache, load_fingerprint_cache, update_game_info_cache,
+        AddonCache, AddonCacheEntry, CatalogCache, FingerprintCache,
     },
-    catalog::get_catalog,
-    catalog::{self, Catalog, CatalogAddon},
+    catalog::{self, search::SearchIndex, Catalog, CatalogAddon},
     config::{ColumnConfig, ColumnConfigV2, Config, Flavor, SelfUpdateChannel},
     error::*,
     fs::PersistentData,
-    repository::ReleaseChannel,
+    manifest::AddonManifest,
+    parse::FingerprintDiagnostics,
+    profile::ProfileSwitch,
+    repository::{ReleaseChannel, RepositoryKind},
+    saved_variables::{OrphanedSavedVariables, SavedVariablesFile},
     theme::{load_user_themes, Theme},
     utility::{self, get_latest_release},
+    wtf::{AddOnsTxt, Character},
 };
 use ajour_widgets::header;
 use async_std::sync::{Arc, Mutex};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use iced::{
     button, pick_list, scrollable, text_input, Align, Application, Button, Column, Command,
     Container, Element, HorizontalAlignment, Length, PickList, Row, Scrollable, Settings, Space,
@@ -86,6 +92,7 @@ pub enum Interaction {
     Unignore(String),
     Update(String),
     UpdateAll,
+    Revert(String),
     SortColumn(ColumnKey),
     SortCatalogColumn(CatalogColumnKey),
     FlavorSelected(Flavor),
@@ -93,6 +100,21 @@ pub enum Interaction {
     ScaleUp,
     ScaleDown,
     Backup,
+    Restore,
+    DeleteBackup(PathBuf),
+    ExportBackup(PathBuf),
+    ExportAddons,
+    ImportAddons,
+    ProfileSelected(ProfileChoice),
+    ToggleAddonEnabled(usize, String, bool),
+    ExportSavedVariables(usize),
+    ImportSavedVariables(usize),
+    DiagnoseFingerprints,
+    FindOrphanedSavedVariables,
+    DeleteOrphanedSavedVariables,
+    ProfileNameInput(String),
+    SaveProfile,
+    DeleteProfile,
     ToggleColumn(bool, ColumnKey),
     ToggleCatalogColumn(bool, CatalogColumnKey),
     ToggleHideIgnoredAddons(bool),
@@ -110,7 +132,14 @@ pub enum Interaction {
     CatalogSourceSelected(CatalogSource),
     UpdateAjour,
     ToggleBackupFolder(bool, BackupFolderKind),
+    ToggleIncrementalBackup(bool),
     PickSelfUpdateChannel(SelfUpdateChannel),
+    CatalogSourceNameInput(String),
+    CatalogSourceLocationInput(String),
+    AddCatalogSource,
+    RemoveCatalogSource(String),
+    MoveCatalogSourceUp(String),
+    MoveCatalogSourceDown(String),
 }
 
 #[derive(Debug)]
@@ -139,13 +168,39 @@ pub enum Message {
     UpdateWowDirectory(Option<PathBuf>),
     UpdateBackupDirectory(Option<PathBuf>),
     RuntimeEvent(iced_native::Event),
-    LatestBackup(Option<NaiveDateTime>),
+    BackupsListed(Vec<(BackupArchive, Vec<ArchiveSection>)>),
     BackupFinished(Result<NaiveDateTime, FilesystemError>),
+    BackupDeleted(Result<(), FilesystemError>),
+    BackupExported(Option<Result<PathBuf, FilesystemError>>),
+    AddonsExported(Option<Result<PathBuf, FilesystemError>>),
+    ManifestLoaded(Option<Result<AddonManifest, FilesystemError>>),
+    ProfileSwitched(
+        (
+            Flavor,
+            ProfileChoice,
+            Result<ProfileSwitch, FilesystemError>,
+        ),
+    ),
+    CharactersLoaded((Flavor, Result<Vec<(Character, AddOnsTxt)>, FilesystemError>)),
+    SavedVariablesLoaded((String, Result<Vec<SavedVariablesFile>, FilesystemError>)),
+    SavedVariablesExported(Option<Result<PathBuf, ParseError>>),
+    SavedVariablesImported(Option<Result<Option<PathBuf>, ParseError>>),
+    FingerprintsDiagnosed((String, Result<Vec<FingerprintDiagnostics>, ParseError>)),
+    OrphanedSavedVariablesFound((Flavor, Result<Vec<OrphanedSavedVariables>, FilesystemError>)),
+    OrphanedSavedVariablesDeleted(Result<NaiveDateTime, FilesystemError>),
+    RestoreArchiveSelected(Option<PathBuf>),
+    RestoreFinished(Result<RestoreResult, FilesystemError>),
     CatalogDownloaded(Result<Catalog, DownloadError>),
+    CatalogCacheLoaded(Result<CatalogCache, CacheError>),
+    CatalogCacheSaved(Result<(), CacheError>),
+    GameInfoCacheUpdated(Result<(), CacheError>),
     InstallAddonFetched((Flavor, String, Result<Addon, RepositoryError>)),
     AjourUpdateDownloaded(Result<(PathBuf, PathBuf), DownloadError>),
     AddonCacheUpdated(Result<AddonCacheEntry, CacheError>),
     AddonCacheEntryRemoved(Result<Option<AddonCacheEntry>, CacheError>),
+    AddonReverted((Flavor, String, Result<NaiveDateTime, FilesystemError>)),
+    AddonLocked(Result<(), ParseError>),
+    AddonUnlocked(Result<(), FilesystemError>),
     RefreshCatalog(Instant),
     CheckLatestRelease(Instant),
 }
@@ -185,6 +240,7 @@ pub struct Ajour {
     catalog_column_settings: CatalogColumnSettings,
     onboarding_directory_btn_state: button::State,
     catalog: Option<Catalog>,
+    catalog_search_index: SearchIndex,
     install_addons: HashMap<Flavor, Vec<InstallAddon>>,
     catalog_last_updated: Option<DateTime<Utc>>,
     catalog_search_state: CatalogSearchState,
@@ -195,6 +251,15 @@ pub struct Ajour {
     open_config_dir_btn_state: button::State,
     install_from_scm_state: InstallFromSCMState,
     self_update_channel_state: SelfUpdateChannelState,
+    is_offline: bool,
+    catalog_sources_state: CatalogSourcesState,
+    manifest_state: ManifestState,
+    profile_state: ProfileState,
+    /// Characters of each flavor with their enabled addons.
+    characters: HashMap<Flavor, Vec<(Character, AddOnsTxt)>>,
+    saved_variables_state: SavedVariablesState,
+    fingerprint_diagnostics_state: FingerprintDiagnosticsState,
+    orphans_state: OrphansState,
 }
 
 impl Default for Ajour {
@@ -234,6 +299,7 @@ impl Default for Ajour {
             catalog_column_settings: Default::default(),
             onboarding_directory_btn_state: Default::default(),
             catalog: None,
+            catalog_search_index: SearchIndex::default(),
             install_addons: Default::default(),
             catalog_last_updated: None,
             catalog_search_state: Default::default(),
@@ -247,6 +313,14 @@ impl Default for Ajour {
                 picklist: Default::default(),
                 options: SelfUpdateChannel::all(),
             },
+            is_offline: false,
+            catalog_sources_state: Default::default(),
+            manifest_state: Default::default(),
+            profile_state: Default::default(),
+            characters: HashMap::new(),
+            saved_variables_state: Default::default(),
+            fingerprint_diagnostics_state: Default::default(),
+            orphans_state: Default::default(),
         }
     }
 }
@@ -264,7 +338,8 @@ impl Application for Ajour {
                 Message::LatestRelease,
             ),
             Command::perform(load_user_themes(), Message::ThemesLoaded),
-            Command::perform(get_catalog(), Message::CatalogDownloaded),
+            Command::perform(load_catalog_cache(), Message::CatalogCacheLoaded),
+            Command::perform(update_game_info_cache(), Message::GameInfoCacheUpdated),
         ];
 
         let mut ajour = Ajour::default();
@@ -337,6 +412,7 @@ impl Application for Ajour {
             &self.mode,
             &self.state,
             &self.error,
+            self.is_offline,
             &self.config,
             &self.valid_flavors,
             &mut self.settings_btn_state,
@@ -375,6 +451,7 @@ impl Application for Ajour {
                     flavor,
                     &mut self.update_all_btn_state,
                     &mut self.refresh_btn_state,
+                    &mut self.profile_state,
                     &self.state,
                     addons,
                     &self.config,
@@ -400,6 +477,11 @@ impl Application for Ajour {
                     .height(Length::FillPortion(1))
                     .style(style::Scrollable(color_palette));
 
+                // Only the expanded addon shows its SavedVariables and fingerprints.
+                let mut saved_variables_state = Some(&mut self.saved_variables_state);
+                let mut fingerprint_diagnostics_state =
+                    Some(&mut self.fingerprint_diagnostics_state);
+
                 // Loops though the addons.
                 for addon in addons {
                     // If hiding ignored addons, we will skip it.
@@ -421,6 +503,20 @@ impl Application for Ajour {
                         is_addon_expanded,
                         &self.expanded_type,
                         &column_config,
+                        self.characters
+                            .get(&flavor)
+                            .map(Vec::as_slice)
+                            .unwrap_or_default(),
+                        if is_addon_expanded {
+                            saved_variables_state.take()
+                        } else {
+                            None
+                        },
+                        if is_addon_expanded {
+                            fingerprint_diagnostics_state.take()
+                        } else {
+                            None
+                        },
                     );
 
                     // Adds the addon data cell to the scrollable.
@@ -605,7 +701,7 @@ impl Application for Ajour {
                     let source_picklist = PickList::new(
                         &mut self.catalog_search_state.sources_state,
                         &self.catalog_search_state.sources,
-                        Some(self.catalog_search_state.source),
+                        Some(self.catalog_search_state.source.clone()),
                         Interaction::CatalogSourceSelected,
                     )
                     .text_size(14)
@@ -656,12 +752,30 @@ impl Application for Ajour {
                             .height(Length::Fill)
                             .width(Length::FillPortion(1));
 
+                    // Shows how old the catalog data is.
+                    let last_updated_text = if let Some(last_updated) = self.catalog_last_updated {
+                        let f = timeago::Formatter::new();
+                        let now = Local::now();
+                        format!("Updated {}", f.convert_chrono(last_updated, now))
+                    } else {
+                        String::new()
+                    };
+                    let last_updated_container =
+                        Container::new(Text::new(last_updated_text).size(DEFAULT_FONT_SIZE))
+                            .center_y()
+                            .center_x()
+                            .padding(5)
+                            .height(Length::Fill)
+                            .width(Length::FillPortion(1))
+                            .style(style::NormalBackgroundContainer(color_palette));
+
                     let catalog_query_row = Row::new()
                         .push(Space::new(Length::Units(DEFAULT_PADDING), Length::Units(0)))
                         .push(catalog_query.map(Message::Interaction))
                         .push(source_picklist_container)
                         .push(category_picklist_container)
                         .push(result_size_picklist_container)
+                        .push(last_updated_container)
                         .push(Space::new(
                             Length::Units(DEFAULT_PADDING + 5),
                             Length::Units(0),
@@ -742,6 +856,10 @@ impl Application for Ajour {
                     &catalog_column_config,
                     &mut self.open_config_dir_btn_state,
                     &mut self.self_update_channel_state,
+                    &mut self.catalog_sources_state,
+                    &mut self.manifest_state,
+                    &mut self.profile_state,
+                    &mut self.orphans_state,
                 );
 
                 content = content.push(settings_container)
@@ -1425,7 +1543,7 @@ impl Default for CatalogSearchState {
             categories: Default::default(),
             categories_state: Default::default(),
             source: CatalogSource::Choice(catalog::Source::Curse),
-            sources: CatalogSource::all(),
+            sources: CatalogSource::all(&[]),
             sources_state: Default::default(),
         }
     }
@@ -1464,8 +1582,15 @@ pub enum InstallStatus {
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InstallKind {
-    Catalog { source: catalog::Source },
+    Catalog {
+        source: catalog::Source,
+    },
     Source,
+    /// Imported from an addon manifest
+    Manifest {
+        repository: RepositoryKind,
+        channel: ReleaseChannel,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -1529,18 +1654,27 @@ impl std::fmt::Display for CatalogResultSize {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum CatalogSource {
     Choice(catalog::Source),
+    Custom(String),
 }
 
 impl CatalogSource {
-    pub fn all() -> Vec<CatalogSource> {
-        vec![
+    pub fn all(custom_sources: &[catalog::CustomSource]) -> Vec<CatalogSource> {
+        let mut sources = vec![
             CatalogSource::Choice(catalog::Source::Curse),
             CatalogSource::Choice(catalog::Source::Tukui),
             CatalogSource::Choice(catalog::Source::WowI),
-        ]
+        ];
+
+        sources.extend(
+            custom_sources
+                .iter()
+                .map(|s| CatalogSource::Custom(s.name.clone())),
+        );
+
+        sources
     }
 }
 
@@ -1552,11 +1686,29 @@ impl std::fmt::Display for CatalogSource {
                 catalog::Source::Tukui => "Tukui",
                 catalog::Source::WowI => "WowInterface",
             },
+            CatalogSource::Custom(name) => name,
         };
         write!(f, "{}", s)
     }
 }
 
+#[derive(Default)]
+pub struct CatalogSourcesState {
+    pub rows: Vec<CatalogSourceRow>,
+    pub name: String,
+    pub name_state: text_input::State,
+    pub location: String,
+    pub location_state: text_input::State,
+    pub add_btn_state: button::State,
+}
+
+#[derive(Default)]
+pub struct CatalogSourceRow {
+    pub up_btn_state: button::State,
+    pub down_btn_state: button::State,
+    pub remove_btn_state: button::State,
+}
+
 pub struct ThemeState {
     themes: Vec<(String, Theme)>,
     current_theme_name: String,
@@ -1565,22 +1717,10 @@ pub struct ThemeState {
 
 impl Default for ThemeState {
     fn default() -> Self {
-        let mut themes = vec![];
-        themes.push(("Alliance".to_string(), Theme::alliance()));
-        themes.push(("Ayu".to_string(), Theme::ayu()));
-        themes.push(("Dark".to_string(), Theme::dark()));
-        themes.push(("Dracula".to_string(), Theme::dracula()));
-        themes.push(("Ferra".to_string(), Theme::ferra()));
-        themes.push(("Forest Night".to_string(), Theme::forest_night()));
-        themes.push(("Gruvbox".to_string(), Theme::gruvbox()));
-        themes.push(("Horde".to_string(), Theme::horde()));
-        themes.push(("Light".to_string(), Theme::light()));
-        themes.push(("Nord".to_string(), Theme::nord()));
-        themes.push(("One Dark".to_string(), Theme::one_dark()));
-        themes.push(("Outrun".to_string(), Theme::outrun()));
-        themes.push(("Solarized Dark".to_string(), Theme::solarized_dark()));
-        themes.push(("Solarized Light".to_string(), Theme::solarized_light()));
-        themes.push(("Sort".to_string(), Theme::sort()));
+        let themes = Theme::built_in()
+            .into_iter()
+            .map(|theme| (theme.name.clone(), theme))
+            .collect();
 
         ThemeState {
             themes,
@@ -1615,9 +1755,111 @@ pub enum BackupFolderKind {
 #[derive(Default)]
 pub struct BackupState {
     backing_up: bool,
+    restoring: bool,
     last_backup: Option<NaiveDateTime>,
     directory_btn_state: button::State,
     backup_now_btn_state: button::State,
+    restore_btn_state: button::State,
+    archives: Vec<BackupArchiveRow>,
+}
+
+/// Export and import of addon manifests in Settings.
+#[derive(Default)]
+pub struct ManifestState {
+    export_btn_state: button::State,
+    import_btn_state: button::State,
+    status: Option<String>,
+}
+
+/// Addon profiles, switched between in My Addons and managed in Settings.
+#[derive(Default)]
+pub struct ProfileState {
+    pick_list_state: pick_list::State<ProfileChoice>,
+    name: String,
+    name_state: text_input::State,
+    save_btn_state: button::State,
+    delete_btn_state: button::State,
+    status: Option<String>,
+}
+
+/// SavedVariables files of the addon whose details are shown.
+#[derive(Default)]
+pub struct SavedVariablesState {
+    /// Primary folder of the addon the files belong to.
+    addon_id: String,
+    files: Vec<SavedVariablesRow>,
+    status: Option<String>,
+}
+
+pub struct SavedVariablesRow {
+    file: SavedVariablesFile,
+    export_btn_state: button::State,
+    import_btn_state: button::State,
+}
+
+/// Fingerprint of each folder of the addon whose details are shown, per file.
+#[derive(Default)]
+pub struct FingerprintDiagnosticsState {
+    /// Primary folder of the addon the diagnostics belong to.
+    addon_id: String,
+    check_btn_state: button::State,
+    checking: bool,
+    diagnostics: Vec<FingerprintDiagnostics>,
+}
+
+/// Orphaned SavedVariables of a flavor, found and deleted in Settings.
+#[derive(Default)]
+pub struct OrphansState {
+    find_btn_state: button::State,
+    delete_btn_state: button::State,
+    flavor: Option<Flavor>,
+    orphans: Vec<OrphanedSavedVariables>,
+    deleting: bool,
+    status: Option<String>,
+}
+
+/// An entry of the profile selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileChoice {
+    /// All addons, nothing parked.
+    All,
+    Profile(String),
+}
+
+impl ProfileChoice {
+    pub fn all(config: &Config, flavor: Flavor) -> Vec<ProfileChoice> {
+        let mut choices = vec![ProfileChoice::All];
+
+        if let Some(profiles) = config.addons.profiles.get(&flavor) {
+            choices.extend(profiles.keys().cloned().map(ProfileChoice::Profile));
+        }
+
+        choices
+    }
+
+    pub fn active(config: &Config, flavor: Flavor) -> ProfileChoice {
+        match config.addons.active_profile.get(&flavor) {
+            Some(name) => ProfileChoice::Profile(name.clone()),
+            None => ProfileChoice::All,
+        }
+    }
+}
+
+impl std::fmt::Display for ProfileChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileChoice::All => write!(f, "All Addons"),
+            ProfileChoice::Profile(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// An archive in the backup directory, shown in Settings.
+pub struct BackupArchiveRow {
+    archive: BackupArchive,
+    sections: Vec<ArchiveSection>,
+    delete_btn_state: button::State,
+    export_btn_state: button::State,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -1835,5 +2077,8 @@ fn apply_config(ajour: &mut Ajour, config: Config) {
     // Set the inital mode flavor
     ajour.mode = Mode::MyAddons(config.wow.flavor);
 
+    // Add any custom catalog sources to the source filter
+    ajour.catalog_search_state.sources = CatalogSource::all(&config.catalog_sources);
+
     ajour.config = config;
 }
@@ -1,30 +1,48 @@
 use {
     super::{
-        Ajour, BackupFolderKind, CatalogCategory, CatalogColumnKey, CatalogRow, CatalogSource,
-        ColumnKey, DirectoryType, DownloadReason, ExpandType, InstallAddon, InstallKind,
-        InstallStatus, Interaction, Message, Mode, SelfUpdateStatus, SortDirection, State,
+        Ajour, BackupArchiveRow, BackupFolderKind, CatalogCategory, CatalogColumnKey, CatalogRow,
+        CatalogSource, ColumnKey, DirectoryType, DownloadReason, ExpandType, InstallAddon,
+        InstallKind, InstallStatus, Interaction, Message, Mode, ProfileChoice, SavedVariablesRow,
+        SavedVariablesState, SelfUpdateStatus, SortDirection, State,
     },
     crate::{log_error, Result},
     ajour_core::{
         addon::{Addon, AddonFolder, AddonState},
-        backup::{backup_folders, latest_backup, BackupFolder},
+        backup::{
+            addon::{revert_addon, snapshot_addon, SnapshotSettings},
+            archive_contents, backup_folders, delete_backup, export_backup, list_backups,
+            restore_backup, ArchiveSection, BackupArchive, BackupFolder, BackupOptions,
+            RestoreSelection,
+        },
         cache::{
-            remove_addon_cache_entry, update_addon_cache, AddonCache, AddonCacheEntry,
-            FingerprintCache,
+            cache_installed_addon, remove_addon_cache_entry, save_catalog_cache, AddonCache,
+            AddonCacheEntry, CatalogCache, FingerprintCache,
+        },
+        catalog::{
+            self,
+            search::{SearchIndex, SearchQuery},
+            Catalog, CatalogOrigin, CustomSource,
         },
-        catalog,
-        config::{ColumnConfig, ColumnConfigV2, Flavor},
+        config::{BackupCompression, BackupFormat, ColumnConfig, ColumnConfigV2, Flavor},
         error::{DownloadError, FilesystemError, ParseError, RepositoryError},
         fs::{delete_addons, install_addon, PersistentData},
+        lockfile::{lock_installed_addon, unlock_addon},
+        manifest::{load_manifest, save_manifest, AddonManifest, ManifestAddon},
         network::download_addon,
-        parse::{read_addon_directory, update_addon_fingerprint},
+        parse::{diagnose_addon_fingerprints, read_addon_directory, update_addon_fingerprint},
+        profile::{delete_profile, profile_folders, save_profile, switch_profile},
         repository::{RepositoryKind, RepositoryPackage},
+        saved_variables::{
+            delete_orphaned_saved_variables, export_saved_variables, find_orphaned_saved_variables,
+            import_saved_variables, load_saved_variables, SavedVariablesFile, Scope,
+        },
         utility::{download_update_to_temp_file, get_latest_release, wow_path_resolution},
+        wtf::{load_characters, save_addons_txt},
     },
     ajour_widgets::header::ResizeEvent,
     anyhow::Context,
     async_std::sync::{Arc, Mutex},
-    chrono::{NaiveTime, Utc},
+    chrono::NaiveDateTime,
     iced::{Command, Length},
     isahc::http::Uri,
     native_dialog::*,
@@ -52,11 +70,11 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
             // Begin to parse addon folder(s).
             let mut commands = vec![];
 
-            // If a backup directory is selected, find the latest backup
+            // If a backup directory is selected, list the backups in it
             if let Some(dir) = &ajour.config.backup_directory {
                 commands.push(Command::perform(
-                    latest_backup(dir.to_owned()),
-                    Message::LatestBackup,
+                    list_backups_with_contents(dir.to_owned()),
+                    Message::BackupsListed,
                 ));
             }
 
@@ -268,6 +286,26 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                         ajour.expanded_type = ExpandType::None;
                     } else {
                         ajour.expanded_type = expand_type.clone();
+                        ajour.saved_variables_state = SavedVariablesState::default();
+                        ajour.fingerprint_diagnostics_state = Default::default();
+
+                        // Reload the characters and SavedVariables, since WoW writes
+                        // AddOns.txt and SavedVariables on logout.
+                        let flavor = ajour.config.wow.flavor;
+                        if let Some(wtf_directory) =
+                            ajour.config.get_wtf_directory_for_flavor(&flavor)
+                        {
+                            return Ok(Command::batch(vec![
+                                Command::perform(
+                                    load_addon_saved_variables(a.clone(), wtf_directory.clone()),
+                                    Message::SavedVariablesLoaded,
+                                ),
+                                Command::perform(
+                                    async move { (flavor, load_characters(wtf_directory).await) },
+                                    Message::CharactersLoaded,
+                                ),
+                            ]));
+                        }
                     }
                 }
                 ExpandType::None => {
@@ -290,24 +328,75 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                 // Delete addon(s) from disk.
                 let _ = delete_addons(&addon.folders);
 
+                let mut commands = vec![];
+
                 // Remove addon from cache
                 if let Some(addon_cache) = &ajour.addon_cache {
+                    // Delete the entry for this cached addon
                     if let Ok(entry) = AddonCacheEntry::try_from(&addon) {
-                        match addon.repository_kind() {
-                            // Delete the entry for this cached addon
-                            Some(RepositoryKind::Tukui)
-                            | Some(RepositoryKind::WowI)
-                            | Some(RepositoryKind::Git(_)) => {
-                                return Ok(Command::perform(
-                                    remove_addon_cache_entry(addon_cache.clone(), entry, flavor),
-                                    Message::AddonCacheEntryRemoved,
-                                ));
-                            }
-                            _ => {}
-                        }
+                        commands.push(Command::perform(
+                            remove_addon_cache_entry(addon_cache.clone(), entry, flavor),
+                            Message::AddonCacheEntryRemoved,
+                        ));
                     }
                 }
+
+                // Remove addon from the lockfile, so it isn't installed again
+                commands.push(Command::perform(
+                    unlock_addon(flavor, addon),
+                    Message::AddonUnlocked,
+                ));
+
+                return Ok(Command::batch(commands));
+            }
+        }
+        Message::Interaction(Interaction::Revert(id)) => {
+            log::debug!("Interaction::Revert({})", &id);
+
+            // Close details if shown.
+            ajour.expanded_type = ExpandType::None;
+
+            let flavor = ajour.config.wow.flavor;
+            let addons = ajour.addons.entry(flavor).or_default();
+
+            if let (Some(addon), Some(settings)) = (
+                addons.iter().find(|a| a.primary_folder_id == id).cloned(),
+                SnapshotSettings::from_config(&ajour.config, flavor),
+            ) {
+                return Ok(Command::perform(
+                    perform_revert_addon(flavor, addon, settings),
+                    Message::AddonReverted,
+                ));
+            }
+        }
+        Message::AddonReverted((flavor, id, Ok(as_of))) => {
+            log::debug!("Message::AddonReverted(({}, {}, {}))", flavor, &id, as_of);
+
+            // Addon folders have changed on disk, so we parse them again.
+            let mut commands = vec![Command::perform(
+                async { Interaction::Refresh },
+                Message::Interaction,
+            )];
+
+            // The lockfile still has the release we reverted from, which
+            // `sync --locked` would install again.
+            let addons = ajour.addons.entry(flavor).or_default();
+            if let Some(addon) = addons.iter().find(|a| a.primary_folder_id == id).cloned() {
+                commands.push(Command::perform(
+                    unlock_addon(flavor, addon),
+                    Message::AddonUnlocked,
+                ));
             }
+
+            return Ok(Command::batch(commands));
+        }
+        Message::AddonReverted((_, id, error @ Err(_))) => {
+            let error = error
+                .context(format!("Failed to revert {}", id))
+                .unwrap_err();
+
+            log_error(&error);
+            ajour.error = Some(error);
         }
         Message::Interaction(Interaction::Update(id)) => {
             log::debug!("Interaction::Update({})", &id);
@@ -426,6 +515,12 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                         })
                         .collect::<Vec<Addon>>();
 
+                    // If remote info couldn't be fetched, addons were built from cached
+                    // data and we switch to offline mode until next successful parse.
+                    if flavor == ajour.config.wow.flavor {
+                        ajour.is_offline = addons.iter().any(|a| a.is_remote_stale());
+                    }
+
                     // Sort the addons.
                     sort_addons(&mut addons, SortDirection::Desc, ColumnKey::Status);
                     ajour.header_state.previous_sort_direction = Some(SortDirection::Desc);
@@ -509,6 +604,12 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                     .get_addon_directory_for_flavor(&flavor)
                     .expect("Expected a valid path");
 
+                // Updates are reverted from a snapshot of the current version
+                let snapshot = match reason {
+                    DownloadReason::Update => SnapshotSettings::from_config(&ajour.config, flavor),
+                    DownloadReason::Install => None,
+                };
+
                 if addon.state == AddonState::Downloading {
                     addon.state = AddonState::Unpacking;
 
@@ -519,6 +620,7 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                             addon.clone(),
                             from_directory,
                             to_directory,
+                            snapshot,
                         ),
                         Message::UnpackedAddon,
                     ));
@@ -608,29 +710,22 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                     addon.set_version(version);
                 }
 
-                // If we are updating / installing a Tukui / WowI
-                // addon, we want to update the cache. If we are installing a Curse
-                // addon, we want to make sure cache entry exists for those folders
+                // Update the cache entry for this addon with the installed release.
+                // Curse entries are only used to show the addon while offline.
                 if let Some(addon_cache) = &ajour.addon_cache {
-                    if let Ok(entry) = AddonCacheEntry::try_from(addon as &_) {
-                        match addon.repository_kind() {
-                            // Remove any entry related to this cached addon
-                            Some(RepositoryKind::Curse) => {
-                                commands.push(Command::perform(
-                                    remove_addon_cache_entry(addon_cache.clone(), entry, flavor),
-                                    Message::AddonCacheEntryRemoved,
-                                ));
-                            }
-                            // Update the entry for this cached addon
-                            Some(RepositoryKind::Tukui)
-                            | Some(RepositoryKind::WowI)
-                            | Some(RepositoryKind::Git(_)) => {
-                                commands.push(Command::perform(
-                                    update_addon_cache(addon_cache.clone(), entry, flavor),
-                                    Message::AddonCacheUpdated,
-                                ));
-                            }
-                            None => {}
+                    if addon.repository_kind().is_some() {
+                        if let Some(addon_directory) =
+                            ajour.config.get_addon_directory_for_flavor(&flavor)
+                        {
+                            commands.push(Command::perform(
+                                cache_installed_addon(
+                                    addon_cache.clone(),
+                                    addon.clone(),
+                                    addon_directory,
+                                    flavor,
+                                ),
+                                Message::AddonCacheUpdated,
+                            ));
                         }
                     }
                 }
@@ -652,6 +747,15 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                         ));
                     }
                 }
+
+                // Record the installed release in the lockfile
+                if let Some(addon_directory) = ajour.config.get_addon_directory_for_flavor(&flavor)
+                {
+                    commands.push(Command::perform(
+                        lock_installed_addon(flavor, addon.clone(), addon_directory),
+                        Message::AddonLocked,
+                    ));
+                }
             }
 
             if !commands.is_empty() {
@@ -910,8 +1014,11 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                 // Persist the newly updated config.
                 let _ = &ajour.config.save();
 
-                // Check if a latest backup exists in path
-                return Ok(Command::perform(latest_backup(path), Message::LatestBackup));
+                // List the backups which exist in path
+                return Ok(Command::perform(
+                    list_backups_with_contents(path),
+                    Message::BackupsListed,
+                ));
             }
         }
 
@@ -950,192 +1057,1007 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
             }
 
             return Ok(Command::perform(
-                backup_folders(src_folders, dest.to_owned()),
+                backup_folders(
+                    src_folders,
+                    dest.to_owned(),
+                    BackupOptions::from_config(&ajour.config),
+                ),
                 Message::BackupFinished,
             ));
         }
-        Message::Interaction(Interaction::ToggleBackupFolder(is_checked, folder)) => {
+        Message::Interaction(Interaction::Restore) => {
+            log::debug!("Interaction::Restore");
+
+            let backup_dir = ajour.config.backup_directory.clone();
+
+            return Ok(Command::perform(
+                select_backup_archive(backup_dir),
+                Message::RestoreArchiveSelected,
+            ));
+        }
+        Message::RestoreArchiveSelected(path) => {
+            log::debug!("Message::RestoreArchiveSelected({:?})", &path);
+
+            // Shouldn't panic since button is only clickable if wow directory is chosen
+            if let (Some(archive), Some(wow_dir)) = (path, ajour.config.wow.directory.clone()) {
+                // This will disable our backup and restore buttons and show a message
+                // that the app is restoring. We will unflag this on completion.
+                ajour.backup_state.restoring = true;
+
+                return Ok(Command::perform(
+                    restore_backup(
+                        archive,
+                        wow_dir,
+                        RestoreSelection::default(),
+                        ajour.config.get_snapshot_directory(),
+                    ),
+                    Message::RestoreFinished,
+                ));
+            }
+        }
+        Message::RestoreFinished(Ok(result)) => {
             log::debug!(
-                "Interaction::ToggleBackupFolder({:?}, checked: {})",
-                folder,
-                is_checked
+                "Message::RestoreFinished(restored: {}, snapshot: {:?})",
+                result.restored.len(),
+                &result.snapshot
             );
 
-            match folder {
-                BackupFolderKind::AddOns => {
-                    ajour.config.backup_addons = is_checked;
-                }
-                BackupFolderKind::WTF => {
-                    ajour.config.backup_wtf = is_checked;
-                }
+            ajour.backup_state.restoring = false;
+
+            // The snapshot is saved as a backup if it's in the backup directory.
+            if let Some(dir) = &ajour.config.backup_directory {
+                return Ok(Command::batch(vec![
+                    Command::perform(
+                        list_backups_with_contents(dir.to_owned()),
+                        Message::BackupsListed,
+                    ),
+                    Command::perform(async { Interaction::Refresh }, Message::Interaction),
+                ]));
             }
 
-            let _ = ajour.config.save();
+            // Addons have changed on disk, so we parse them again.
+            return Ok(Command::perform(
+                async { Interaction::Refresh },
+                Message::Interaction,
+            ));
         }
-        Message::LatestBackup(as_of) => {
-            log::debug!("Message::LatestBackup({:?})", &as_of);
+        Message::RestoreFinished(error @ Err(_)) => {
+            let error = error.context("Failed to restore backup").unwrap_err();
+
+            log_error(&error);
+            ajour.error = Some(error);
 
-            ajour.backup_state.last_backup = as_of;
+            ajour.backup_state.restoring = false;
         }
-        Message::BackupFinished(Ok(as_of)) => {
-            log::debug!("Message::BackupFinished({})", as_of.format("%H:%M:%S"));
+        Message::Interaction(Interaction::DeleteBackup(path)) => {
+            log::debug!("Interaction::DeleteBackup({:?})", &path);
 
-            ajour.backup_state.backing_up = false;
-            ajour.backup_state.last_backup = Some(as_of);
+            return Ok(Command::perform(
+                delete_backup(path),
+                Message::BackupDeleted,
+            ));
         }
-        Message::BackupFinished(error @ Err(_)) => {
-            let error = error.context("Failed to backup folders").unwrap_err();
+        Message::BackupDeleted(Ok(_)) => {
+            log::debug!("Message::BackupDeleted");
+
+            if let Some(dir) = &ajour.config.backup_directory {
+                return Ok(Command::perform(
+                    list_backups_with_contents(dir.to_owned()),
+                    Message::BackupsListed,
+                ));
+            }
+        }
+        Message::BackupDeleted(error @ Err(_)) => {
+            let error = error.context("Failed to delete backup").unwrap_err();
 
             log_error(&error);
             ajour.error = Some(error);
-
-            ajour.backup_state.backing_up = false;
         }
-        Message::Interaction(Interaction::ToggleColumn(is_checked, key)) => {
-            // We can't untoggle the addon title column
-            if key == ColumnKey::Title {
-                return Ok(Command::none());
-            }
+        Message::Interaction(Interaction::ExportBackup(path)) => {
+            log::debug!("Interaction::ExportBackup({:?})", &path);
 
-            log::debug!("Interaction::ToggleColumn({}, {:?})", is_checked, key);
+            return Ok(Command::perform(
+                select_export_directory(path, ajour.config.backup_compression),
+                Message::BackupExported,
+            ));
+        }
+        Message::BackupExported(Some(Ok(path))) => {
+            log::debug!("Message::BackupExported({:?})", &path);
 
-            if is_checked {
-                if let Some(column) = ajour.header_state.columns.iter_mut().find(|c| c.key == key) {
-                    column.hidden = false;
-                }
-            } else if let Some(column) =
-                ajour.header_state.columns.iter_mut().find(|c| c.key == key)
-            {
-                column.hidden = true;
+            // Exporting to the backup directory adds a backup to it.
+            if let Some(dir) = &ajour.config.backup_directory {
+                return Ok(Command::perform(
+                    list_backups_with_contents(dir.to_owned()),
+                    Message::BackupsListed,
+                ));
             }
+        }
+        Message::BackupExported(Some(error @ Err(_))) => {
+            let error = error.context("Failed to export backup").unwrap_err();
 
-            // Persist changes to config
-            save_column_configs(ajour);
+            log_error(&error);
+            ajour.error = Some(error);
         }
-        Message::Interaction(Interaction::MoveColumnLeft(key)) => {
-            log::debug!("Interaction::MoveColumnLeft({:?})", key);
+        Message::BackupExported(None) => {}
+        Message::Interaction(Interaction::ExportAddons) => {
+            log::debug!("Interaction::ExportAddons");
 
-            // Update header state ordering and save to config
-            if let Some(idx) = ajour.header_state.columns.iter().position(|c| c.key == key) {
-                ajour.header_state.columns.swap(idx, idx - 1);
+            let flavor = ajour.config.wow.flavor;
+            let addons = ajour
+                .addons
+                .get(&flavor)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
 
-                ajour
-                    .header_state
-                    .columns
-                    .iter_mut()
-                    .enumerate()
-                    .for_each(|(idx, column)| column.order = idx);
+            let manifest = AddonManifest::new(flavor, addons, &ajour.config);
 
-                // Persist changes to config
-                save_column_configs(ajour);
-            }
+            return Ok(Command::perform(
+                select_manifest_export_directory(manifest),
+                Message::AddonsExported,
+            ));
+        }
+        Message::AddonsExported(Some(Ok(path))) => {
+            log::debug!("Message::AddonsExported({:?})", &path);
 
-            // Update column ordering in settings
-            if let Some(idx) = ajour
-                .column_settings
-                .columns
-                .iter()
-                .position(|c| c.key == key)
-            {
-                ajour.column_settings.columns.swap(idx, idx - 1);
-            }
+            ajour.manifest_state.status = Some(format!("Exported to {}", path.display()));
         }
-        Message::Interaction(Interaction::MoveColumnRight(key)) => {
-            log::debug!("Interaction::MoveColumnRight({:?})", key);
+        Message::AddonsExported(Some(error @ Err(_))) => {
+            let error = error.context("Failed to export addons").unwrap_err();
 
-            // Update header state ordering and save to config
-            if let Some(idx) = ajour.header_state.columns.iter().position(|c| c.key == key) {
-                ajour.header_state.columns.swap(idx, idx + 1);
+            log_error(&error);
+            ajour.error = Some(error);
+        }
+        Message::AddonsExported(None) => {}
+        Message::Interaction(Interaction::ImportAddons) => {
+            log::debug!("Interaction::ImportAddons");
 
-                ajour
-                    .header_state
-                    .columns
-                    .iter_mut()
-                    .enumerate()
-                    .for_each(|(idx, column)| column.order = idx);
+            return Ok(Command::perform(select_manifest(), Message::ManifestLoaded));
+        }
+        Message::ManifestLoaded(Some(Ok(manifest))) => {
+            log::debug!("Message::ManifestLoaded({} addons)", manifest.addons.len());
 
-                // Persist changes to config
-                save_column_configs(ajour);
-            }
+            let flavor = manifest.flavor;
+
+            if !ajour.valid_flavors.contains(&flavor) {
+                ajour.manifest_state.status =
+                    Some(format!("No {} installation found to import to", flavor));
 
-            // Update column ordering in settings
-            if let Some(idx) = ajour
-                .column_settings
-                .columns
-                .iter()
-                .position(|c| c.key == key)
-            {
-                ajour.column_settings.columns.swap(idx, idx + 1);
-            }
-        }
-        Message::Interaction(Interaction::ToggleCatalogColumn(is_checked, key)) => {
-            // We can't untoggle the addon title column
-            if key == CatalogColumnKey::Title {
                 return Ok(Command::none());
             }
 
-            log::debug!(
-                "Interaction::ToggleCatalogColumn({}, {:?})",
-                is_checked,
-                key
-            );
+            let installed = ajour
+                .addons
+                .get(&flavor)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let missing: Vec<ManifestAddon> =
+                manifest.missing(installed).into_iter().cloned().collect();
+
+            ajour.manifest_state.status = Some(if missing.is_empty() {
+                "All addons in the manifest are installed".to_owned()
+            } else {
+                format!(
+                    "Installing {} of {} addons for {}",
+                    missing.len(),
+                    manifest.addons.len(),
+                    flavor
+                )
+            });
 
-            if is_checked {
-                if let Some(column) = ajour
-                    .catalog_header_state
-                    .columns
-                    .iter_mut()
-                    .find(|c| c.key == key)
-                {
-                    column.hidden = false;
-                }
-            } else if let Some(column) = ajour
-                .catalog_header_state
-                .columns
-                .iter_mut()
-                .find(|c| c.key == key)
-            {
-                column.hidden = true;
+            let mut commands = vec![];
+
+            for addon in missing {
+                // Release channel and ignored state apply once the addon is
+                // installed and parsed.
+                addon.apply_to_config(flavor, &mut ajour.config);
+
+                commands.push(handle_message(
+                    ajour,
+                    Message::Interaction(Interaction::InstallAddon(
+                        flavor,
+                        addon.id.clone(),
+                        InstallKind::Manifest {
+                            repository: addon.repository,
+                            channel: addon.channel,
+                        },
+                    )),
+                )?);
             }
 
-            // Persist changes to config
-            save_column_configs(ajour);
+            let _ = ajour.config.save();
+
+            return Ok(Command::batch(commands));
         }
-        Message::Interaction(Interaction::MoveCatalogColumnLeft(key)) => {
-            log::debug!("Interaction::MoveCatalogColumnLeft({:?})", key);
+        Message::ManifestLoaded(Some(error @ Err(_))) => {
+            let error = error.context("Failed to import addons").unwrap_err();
 
-            // Update header state ordering and save to config
-            if let Some(idx) = ajour
-                .catalog_header_state
-                .columns
-                .iter()
-                .position(|c| c.key == key)
-            {
-                ajour.catalog_header_state.columns.swap(idx, idx - 1);
+            log_error(&error);
+            ajour.error = Some(error);
+        }
+        Message::ManifestLoaded(None) => {}
+        Message::Interaction(Interaction::ProfileSelected(choice)) => {
+            log::debug!("Interaction::ProfileSelected({})", &choice);
 
-                ajour
-                    .catalog_header_state
-                    .columns
-                    .iter_mut()
-                    .enumerate()
-                    .for_each(|(idx, column)| column.order = idx);
+            let flavor = ajour.config.wow.flavor;
 
-                // Persist changes to config
-                save_column_configs(ajour);
+            if choice == ProfileChoice::active(&ajour.config, flavor) {
+                return Ok(Command::none());
             }
 
-            // Update column ordering in settings
-            if let Some(idx) = ajour
-                .catalog_column_settings
-                .columns
-                .iter()
-                .position(|c| c.key == key)
-            {
-                ajour.catalog_column_settings.columns.swap(idx, idx - 1);
+            // Don't move folders around while addons are being updated.
+            let addons_performing_actions = ajour.addons.get(&flavor).map_or(false, |addons| {
+                addons
+                    .iter()
+                    .any(|a| matches!(a.state, AddonState::Downloading | AddonState::Unpacking))
+            });
+
+            if addons_performing_actions {
+                return Ok(Command::none());
             }
-        }
-        Message::Interaction(Interaction::MoveCatalogColumnRight(key)) => {
-            log::debug!("Interaction::MoveCatalogColumnRight({:?})", key);
+
+            let folders = match &choice {
+                ProfileChoice::All => None,
+                ProfileChoice::Profile(name) => {
+                    match profile_folders(&ajour.config, flavor, name)
+                        .context("Failed to switch profile")
+                    {
+                        Ok(folders) => Some(folders),
+                        Err(error) => {
+                            log_error(&error);
+                            ajour.error = Some(error);
+
+                            return Ok(Command::none());
+                        }
+                    }
+                }
+            };
+
+            if let (Some(addon_directory), Some(parked_directory)) = (
+                ajour.config.get_addon_directory_for_flavor(&flavor),
+                ajour.config.get_parked_addon_directory_for_flavor(&flavor),
+            ) {
+                return Ok(Command::perform(
+                    async move {
+                        (
+                            flavor,
+                            choice,
+                            switch_profile(addon_directory, parked_directory, folders).await,
+                        )
+                    },
+                    Message::ProfileSwitched,
+                ));
+            }
+        }
+        Message::ProfileSwitched((flavor, choice, result)) => {
+            log::debug!(
+                "Message::ProfileSwitched(({}, {}, error: {}))",
+                flavor,
+                &choice,
+                result.is_err()
+            );
+
+            match result.context("Failed to switch profile") {
+                Ok(switch) => {
+                    match &choice {
+                        ProfileChoice::All => {
+                            ajour.config.addons.active_profile.remove(&flavor);
+                        }
+                        ProfileChoice::Profile(name) => {
+                            ajour
+                                .config
+                                .addons
+                                .active_profile
+                                .insert(flavor, name.clone());
+                        }
+                    }
+
+                    let _ = ajour.config.save();
+
+                    ajour.profile_state.status = Some(if switch.missing.is_empty() {
+                        format!("Switched to {}", choice)
+                    } else {
+                        format!(
+                            "Switched to {}, {} addon folders aren't installed: {}",
+                            choice,
+                            switch.missing.len(),
+                            switch.missing.join(", ")
+                        )
+                    });
+
+                    // Reparse the addons so only the active set is shown.
+                    if flavor == ajour.config.wow.flavor {
+                        return handle_message(ajour, Message::Interaction(Interaction::Refresh));
+                    }
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+
+                    // Some folders might have been moved before the error.
+                    return handle_message(ajour, Message::Interaction(Interaction::Refresh));
+                }
+            }
+        }
+        Message::CharactersLoaded((flavor, result)) => {
+            log::debug!(
+                "Message::CharactersLoaded(({}, error: {}))",
+                flavor,
+                result.is_err()
+            );
+
+            match result.context("Failed to load characters") {
+                Ok(characters) => {
+                    ajour.characters.insert(flavor, characters);
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::Interaction(Interaction::ToggleAddonEnabled(idx, id, is_checked)) => {
+            log::debug!(
+                "Interaction::ToggleAddonEnabled({}, {}, {})",
+                idx,
+                &id,
+                is_checked
+            );
+
+            let flavor = ajour.config.wow.flavor;
+
+            // Every folder of the addon is toggled together.
+            let folders: Vec<String> = ajour
+                .addons
+                .get(&flavor)
+                .and_then(|addons| addons.iter().find(|a| a.primary_folder_id == id))
+                .map(|addon| addon.folders.iter().map(|f| f.id.clone()).collect())
+                .unwrap_or_else(|| vec![id.clone()]);
+
+            if let Some((character, addons_txt)) = ajour
+                .characters
+                .get_mut(&flavor)
+                .and_then(|characters| characters.get_mut(idx))
+            {
+                for folder in &folders {
+                    addons_txt.set_enabled(folder, is_checked);
+                }
+
+                if let Err(error) = save_addons_txt(character, addons_txt)
+                    .context(format!("Failed to save AddOns.txt of {}", character))
+                {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::SavedVariablesLoaded((id, result)) => {
+            log::debug!(
+                "Message::SavedVariablesLoaded(({}, error: {}))",
+                &id,
+                result.is_err()
+            );
+
+            // Details might have been closed or moved to another addon meanwhile.
+            let is_expanded = match &ajour.expanded_type {
+                ExpandType::Details(a) => a.primary_folder_id == id,
+                ExpandType::None => false,
+            };
+
+            if !is_expanded {
+                return Ok(Command::none());
+            }
+
+            match result.context("Failed to load SavedVariables") {
+                Ok(files) => {
+                    ajour.saved_variables_state.addon_id = id;
+                    ajour.saved_variables_state.files = files
+                        .into_iter()
+                        .map(|file| SavedVariablesRow {
+                            file,
+                            export_btn_state: Default::default(),
+                            import_btn_state: Default::default(),
+                        })
+                        .collect();
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::Interaction(Interaction::ExportSavedVariables(idx)) => {
+            log::debug!("Interaction::ExportSavedVariables({})", idx);
+
+            if let Some(row) = ajour.saved_variables_state.files.get(idx) {
+                let file_name = format!("{}_{}.lua", &row.file.folder, row.file.scope.name());
+
+                return Ok(Command::perform(
+                    select_saved_variables_export_directory(row.file.path.clone(), file_name),
+                    Message::SavedVariablesExported,
+                ));
+            }
+        }
+        Message::SavedVariablesExported(Some(Ok(path))) => {
+            log::debug!("Message::SavedVariablesExported({:?})", &path);
+
+            ajour.saved_variables_state.status = Some(format!("Exported to {}", path.display()));
+        }
+        Message::SavedVariablesExported(Some(error @ Err(_))) => {
+            let error = error
+                .context("Failed to export SavedVariables")
+                .unwrap_err();
+
+            log_error(&error);
+            ajour.error = Some(error);
+        }
+        Message::SavedVariablesExported(None) => {}
+        Message::Interaction(Interaction::ImportSavedVariables(idx)) => {
+            log::debug!("Interaction::ImportSavedVariables({})", idx);
+
+            let flavor = ajour.config.wow.flavor;
+            let state = &ajour.saved_variables_state;
+
+            let row = match state.files.get(idx) {
+                Some(row) => row,
+                None => return Ok(Command::none()),
+            };
+
+            let folder = ajour
+                .addons
+                .get(&flavor)
+                .and_then(|addons| {
+                    addons
+                        .iter()
+                        .find(|a| a.primary_folder_id == state.addon_id)
+                })
+                .and_then(|addon| addon.folders.iter().find(|f| f.id == row.file.folder))
+                .cloned();
+
+            if let (Some(folder), Some(wtf_directory)) =
+                (folder, ajour.config.get_wtf_directory_for_flavor(&flavor))
+            {
+                return Ok(Command::perform(
+                    select_saved_variables_import_file(
+                        folder,
+                        row.file.scope.clone(),
+                        wtf_directory,
+                    ),
+                    Message::SavedVariablesImported,
+                ));
+            }
+        }
+        Message::SavedVariablesImported(Some(Ok(backup))) => {
+            log::debug!("Message::SavedVariablesImported({:?})", &backup);
+
+            ajour.saved_variables_state.status = Some(match backup {
+                Some(backup) => format!(
+                    "Imported, the previous file was kept as {}",
+                    backup.display()
+                ),
+                None => "Imported".to_owned(),
+            });
+
+            // Reload to show what was imported.
+            let flavor = ajour.config.wow.flavor;
+            if let (ExpandType::Details(addon), Some(wtf_directory)) = (
+                &ajour.expanded_type,
+                ajour.config.get_wtf_directory_for_flavor(&flavor),
+            ) {
+                return Ok(Command::perform(
+                    load_addon_saved_variables(addon.clone(), wtf_directory),
+                    Message::SavedVariablesLoaded,
+                ));
+            }
+        }
+        Message::SavedVariablesImported(Some(error @ Err(_))) => {
+            let error = error
+                .context("Failed to import SavedVariables")
+                .unwrap_err();
+
+            log_error(&error);
+            ajour.error = Some(error);
+        }
+        Message::SavedVariablesImported(None) => {}
+        Message::Interaction(Interaction::DiagnoseFingerprints) => {
+            log::debug!("Interaction::DiagnoseFingerprints");
+
+            let addon = match &ajour.expanded_type {
+                ExpandType::Details(addon) => addon.clone(),
+                ExpandType::None => return Ok(Command::none()),
+            };
+
+            let flavor = ajour.config.wow.flavor;
+            if let Some(addon_directory) = ajour.config.get_addon_directory_for_flavor(&flavor) {
+                let id = addon.primary_folder_id.clone();
+
+                ajour.fingerprint_diagnostics_state.addon_id = id.clone();
+                ajour.fingerprint_diagnostics_state.checking = true;
+
+                let fingerprint_cache = ajour.fingerprint_cache.clone();
+
+                return Ok(Command::perform(
+                    async move {
+                        (
+                            id,
+                            diagnose_addon_fingerprints(
+                                fingerprint_cache,
+                                flavor,
+                                addon,
+                                addon_directory,
+                            )
+                            .await,
+                        )
+                    },
+                    Message::FingerprintsDiagnosed,
+                ));
+            }
+        }
+        Message::FingerprintsDiagnosed((id, result)) => {
+            log::debug!(
+                "Message::FingerprintsDiagnosed(({}, error: {}))",
+                &id,
+                result.is_err()
+            );
+
+            let state = &mut ajour.fingerprint_diagnostics_state;
+
+            // Details might have been closed or moved to another addon meanwhile.
+            if state.addon_id != id {
+                return Ok(Command::none());
+            }
+
+            state.checking = false;
+
+            match result.context("Failed to check the files of the addon") {
+                Ok(diagnostics) => {
+                    state.diagnostics = diagnostics;
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::Interaction(Interaction::FindOrphanedSavedVariables) => {
+            log::debug!("Interaction::FindOrphanedSavedVariables");
+
+            let flavor = ajour.config.wow.flavor;
+
+            let directories = (
+                ajour.config.get_wtf_directory_for_flavor(&flavor),
+                ajour.config.get_addon_directory_for_flavor(&flavor),
+                ajour.config.get_parked_addon_directory_for_flavor(&flavor),
+            );
+
+            if let (Some(wtf_directory), Some(addon_directory), Some(parked_directory)) =
+                directories
+            {
+                // Folders which haven't been parsed yet are never orphaned, so
+                // this is safe before the addons have loaded.
+                let folders: Vec<AddonFolder> = ajour
+                    .addons
+                    .get(&flavor)
+                    .map(|addons| {
+                        addons
+                            .iter()
+                            .flat_map(|addon| addon.folders.iter().cloned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                return Ok(Command::perform(
+                    async move {
+                        (
+                            flavor,
+                            find_orphaned_saved_variables(
+                                wtf_directory,
+                                addon_directory,
+                                parked_directory,
+                                folders,
+                            )
+                            .await,
+                        )
+                    },
+                    Message::OrphanedSavedVariablesFound,
+                ));
+            }
+        }
+        Message::OrphanedSavedVariablesFound((flavor, result)) => {
+            log::debug!(
+                "Message::OrphanedSavedVariablesFound(({}, error: {}))",
+                flavor,
+                result.is_err()
+            );
+
+            match result.context("Failed to find orphaned SavedVariables") {
+                Ok(orphans) => {
+                    let size: u64 = orphans.iter().map(|o| o.size).sum();
+
+                    ajour.orphans_state.status = Some(if orphans.is_empty() {
+                        format!("No orphaned SavedVariables for {}", flavor)
+                    } else {
+                        format!(
+                            "{} orphaned SavedVariables for {}, {:.1} MB",
+                            orphans.len(),
+                            flavor,
+                            size as f64 / 1024.0 / 1024.0
+                        )
+                    });
+                    ajour.orphans_state.flavor = Some(flavor);
+                    ajour.orphans_state.orphans = orphans;
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::Interaction(Interaction::DeleteOrphanedSavedVariables) => {
+            log::debug!("Interaction::DeleteOrphanedSavedVariables");
+
+            let flavor = ajour
+                .orphans_state
+                .flavor
+                .unwrap_or(ajour.config.wow.flavor);
+
+            let directories = (
+                ajour.config.wow.directory.clone(),
+                ajour.config.get_wtf_directory_for_flavor(&flavor),
+                ajour.config.get_addon_directory_for_flavor(&flavor),
+                ajour.config.get_parked_addon_directory_for_flavor(&flavor),
+            );
+
+            if let (
+                Some(wow_directory),
+                Some(wtf_directory),
+                Some(addon_directory),
+                Some(parked_directory),
+            ) = directories
+            {
+                ajour.orphans_state.deleting = true;
+
+                let orphans = std::mem::take(&mut ajour.orphans_state.orphans);
+                let folders: Vec<AddonFolder> = ajour
+                    .addons
+                    .get(&flavor)
+                    .map(|addons| {
+                        addons
+                            .iter()
+                            .flat_map(|addon| addon.folders.iter().cloned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let backup_directory = ajour.config.get_saved_variables_backup_directory();
+                let compression = ajour.config.backup_compression;
+
+                return Ok(Command::perform(
+                    async move {
+                        // Addons might have been installed or parsed since the
+                        // orphans were found, so only files which are still
+                        // orphaned are deleted.
+                        let current = find_orphaned_saved_variables(
+                            wtf_directory,
+                            addon_directory,
+                            parked_directory,
+                            folders,
+                        )
+                        .await?;
+
+                        let orphans = orphans
+                            .into_iter()
+                            .filter_map(|mut orphan| {
+                                orphan
+                                    .paths
+                                    .retain(|path| current.iter().any(|o| o.paths.contains(path)));
+
+                                if orphan.paths.is_empty() {
+                                    None
+                                } else {
+                                    Some(orphan)
+                                }
+                            })
+                            .collect();
+
+                        delete_orphaned_saved_variables(
+                            orphans,
+                            wow_directory,
+                            backup_directory,
+                            compression,
+                        )
+                        .await
+                    },
+                    Message::OrphanedSavedVariablesDeleted,
+                ));
+            }
+        }
+        Message::OrphanedSavedVariablesDeleted(result) => {
+            log::debug!(
+                "Message::OrphanedSavedVariablesDeleted(error: {})",
+                result.is_err()
+            );
+
+            ajour.orphans_state.deleting = false;
+
+            match result.context("Failed to delete orphaned SavedVariables") {
+                Ok(_) => {
+                    ajour.orphans_state.status = Some(format!(
+                        "Deleted, a backup was saved to {}",
+                        ajour
+                            .config
+                            .get_saved_variables_backup_directory()
+                            .display()
+                    ));
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+
+                    // Some files might have been deleted before the error.
+                    return handle_message(
+                        ajour,
+                        Message::Interaction(Interaction::FindOrphanedSavedVariables),
+                    );
+                }
+            }
+        }
+        Message::Interaction(Interaction::ProfileNameInput(name)) => {
+            ajour.profile_state.name = name;
+        }
+        Message::Interaction(Interaction::SaveProfile) => {
+            let name = ajour.profile_state.name.trim().to_string();
+            let flavor = ajour.config.wow.flavor;
+
+            log::debug!("Interaction::SaveProfile({})", &name);
+
+            if let Some(addon_directory) = ajour.config.get_addon_directory_for_flavor(&flavor) {
+                match save_profile(&mut ajour.config, flavor, name.clone(), &addon_directory)
+                    .context("Failed to save profile")
+                {
+                    Ok(count) => {
+                        let _ = ajour.config.save();
+
+                        ajour.profile_state.name = String::new();
+                        ajour.profile_state.status =
+                            Some(format!("Saved {} addon folders as {}", count, name));
+                    }
+                    Err(error) => {
+                        log_error(&error);
+                        ajour.error = Some(error);
+                    }
+                }
+            }
+        }
+        Message::Interaction(Interaction::DeleteProfile) => {
+            let name = ajour.profile_state.name.trim().to_string();
+            let flavor = ajour.config.wow.flavor;
+
+            log::debug!("Interaction::DeleteProfile({})", &name);
+
+            match delete_profile(&mut ajour.config, flavor, &name)
+                .context("Failed to delete profile")
+            {
+                Ok(_) => {
+                    let _ = ajour.config.save();
+
+                    ajour.profile_state.name = String::new();
+                    ajour.profile_state.status = Some(format!("Deleted {}", name));
+                }
+                Err(error) => {
+                    log_error(&error);
+                    ajour.error = Some(error);
+                }
+            }
+        }
+        Message::Interaction(Interaction::ToggleIncrementalBackup(is_checked)) => {
+            log::debug!("Interaction::ToggleIncrementalBackup({})", is_checked);
+
+            ajour.config.backup_format = if is_checked {
+                BackupFormat::Incremental
+            } else {
+                BackupFormat::Zip
+            };
+
+            let _ = ajour.config.save();
+        }
+        Message::Interaction(Interaction::ToggleBackupFolder(is_checked, folder)) => {
+            log::debug!(
+                "Interaction::ToggleBackupFolder({:?}, checked: {})",
+                folder,
+                is_checked
+            );
+
+            match folder {
+                BackupFolderKind::AddOns => {
+                    ajour.config.backup_addons = is_checked;
+                }
+                BackupFolderKind::WTF => {
+                    ajour.config.backup_wtf = is_checked;
+                }
+            }
+
+            let _ = ajour.config.save();
+        }
+        Message::BackupsListed(archives) => {
+            log::debug!("Message::BackupsListed({} archives)", archives.len());
+
+            ajour.backup_state.last_backup = archives.first().map(|(a, _)| a.as_of);
+            ajour.backup_state.archives = archives
+                .into_iter()
+                .map(|(archive, sections)| BackupArchiveRow {
+                    archive,
+                    sections,
+                    delete_btn_state: Default::default(),
+                    export_btn_state: Default::default(),
+                })
+                .collect();
+        }
+        Message::BackupFinished(Ok(as_of)) => {
+            log::debug!("Message::BackupFinished({})", as_of.format("%H:%M:%S"));
+
+            ajour.backup_state.backing_up = false;
+            ajour.backup_state.last_backup = Some(as_of);
+
+            // Retention might have deleted older backups, so we list them again.
+            if let Some(dir) = &ajour.config.backup_directory {
+                return Ok(Command::perform(
+                    list_backups_with_contents(dir.to_owned()),
+                    Message::BackupsListed,
+                ));
+            }
+        }
+        Message::BackupFinished(error @ Err(_)) => {
+            let error = error.context("Failed to backup folders").unwrap_err();
+
+            log_error(&error);
+            ajour.error = Some(error);
+
+            ajour.backup_state.backing_up = false;
+        }
+        Message::Interaction(Interaction::ToggleColumn(is_checked, key)) => {
+            // We can't untoggle the addon title column
+            if key == ColumnKey::Title {
+                return Ok(Command::none());
+            }
+
+            log::debug!("Interaction::ToggleColumn({}, {:?})", is_checked, key);
+
+            if is_checked {
+                if let Some(column) = ajour.header_state.columns.iter_mut().find(|c| c.key == key) {
+                    column.hidden = false;
+                }
+            } else if let Some(column) =
+                ajour.header_state.columns.iter_mut().find(|c| c.key == key)
+            {
+                column.hidden = true;
+            }
+
+            // Persist changes to config
+            save_column_configs(ajour);
+        }
+        Message::Interaction(Interaction::MoveColumnLeft(key)) => {
+            log::debug!("Interaction::MoveColumnLeft({:?})", key);
+
+            // Update header state ordering and save to config
+            if let Some(idx) = ajour.header_state.columns.iter().position(|c| c.key == key) {
+                ajour.header_state.columns.swap(idx, idx - 1);
+
+                ajour
+                    .header_state
+                    .columns
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(idx, column)| column.order = idx);
+
+                // Persist changes to config
+                save_column_configs(ajour);
+            }
+
+            // Update column ordering in settings
+            if let Some(idx) = ajour
+                .column_settings
+                .columns
+                .iter()
+                .position(|c| c.key == key)
+            {
+                ajour.column_settings.columns.swap(idx, idx - 1);
+            }
+        }
+        Message::Interaction(Interaction::MoveColumnRight(key)) => {
+            log::debug!("Interaction::MoveColumnRight({:?})", key);
+
+            // Update header state ordering and save to config
+            if let Some(idx) = ajour.header_state.columns.iter().position(|c| c.key == key) {
+                ajour.header_state.columns.swap(idx, idx + 1);
+
+                ajour
+                    .header_state
+                    .columns
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(idx, column)| column.order = idx);
+
+                // Persist changes to config
+                save_column_configs(ajour);
+            }
+
+            // Update column ordering in settings
+            if let Some(idx) = ajour
+                .column_settings
+                .columns
+                .iter()
+                .position(|c| c.key == key)
+            {
+                ajour.column_settings.columns.swap(idx, idx + 1);
+            }
+        }
+        Message::Interaction(Interaction::ToggleCatalogColumn(is_checked, key)) => {
+            // We can't untoggle the addon title column
+            if key == CatalogColumnKey::Title {
+                return Ok(Command::none());
+            }
+
+            log::debug!(
+                "Interaction::ToggleCatalogColumn({}, {:?})",
+                is_checked,
+                key
+            );
+
+            if is_checked {
+                if let Some(column) = ajour
+                    .catalog_header_state
+                    .columns
+                    .iter_mut()
+                    .find(|c| c.key == key)
+                {
+                    column.hidden = false;
+                }
+            } else if let Some(column) = ajour
+                .catalog_header_state
+                .columns
+                .iter_mut()
+                .find(|c| c.key == key)
+            {
+                column.hidden = true;
+            }
+
+            // Persist changes to config
+            save_column_configs(ajour);
+        }
+        Message::Interaction(Interaction::MoveCatalogColumnLeft(key)) => {
+            log::debug!("Interaction::MoveCatalogColumnLeft({:?})", key);
+
+            // Update header state ordering and save to config
+            if let Some(idx) = ajour
+                .catalog_header_state
+                .columns
+                .iter()
+                .position(|c| c.key == key)
+            {
+                ajour.catalog_header_state.columns.swap(idx, idx - 1);
+
+                ajour
+                    .catalog_header_state
+                    .columns
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(idx, column)| column.order = idx);
+
+                // Persist changes to config
+                save_column_configs(ajour);
+            }
+
+            // Update column ordering in settings
+            if let Some(idx) = ajour
+                .catalog_column_settings
+                .columns
+                .iter()
+                .position(|c| c.key == key)
+            {
+                ajour.catalog_column_settings.columns.swap(idx, idx - 1);
+            }
+        }
+        Message::Interaction(Interaction::MoveCatalogColumnRight(key)) => {
+            log::debug!("Interaction::MoveCatalogColumnRight({:?})", key);
 
             // Update header state ordering and save to config
             if let Some(idx) = ajour
@@ -1167,50 +2089,59 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                 ajour.catalog_column_settings.columns.swap(idx, idx + 1);
             }
         }
-        Message::CatalogDownloaded(Ok(catalog)) => {
+        Message::CatalogCacheLoaded(Ok(catalog_cache)) => {
             log::debug!(
-                "Message::CatalogDownloaded({} addons in catalog)",
-                catalog.addons.len()
+                "Message::CatalogCacheLoaded({} addons in catalog, fetched at {})",
+                catalog_cache.catalog.addons.len(),
+                catalog_cache.fetched_at
             );
 
-            ajour.catalog_last_updated = Some(Utc::now());
+            let is_stale = catalog_cache.is_stale();
 
-            let mut categories_per_source =
-                catalog
-                    .addons
-                    .iter()
-                    .fold(HashMap::new(), |mut map, addon| {
-                        map.entry(addon.source.to_string())
-                            .or_insert_with(Vec::new)
-                            .append(
-                                &mut addon
-                                    .categories
-                                    .clone()
-                                    .iter()
-                                    .map(|c| CatalogCategory::Choice(c.to_string()))
-                                    .collect(),
-                            );
-                        map
-                    });
-            categories_per_source.iter_mut().for_each(move |s| {
-                s.1.sort();
-                s.1.dedup();
-                s.1.insert(0, CatalogCategory::All);
-            });
+            ajour.catalog_last_updated = Some(catalog_cache.fetched_at);
 
-            ajour.catalog_categories_per_source_cache = categories_per_source;
+            set_catalog(ajour, catalog_cache.catalog);
 
-            ajour.catalog_search_state.categories = ajour
-                .catalog_categories_per_source_cache
-                .get(&ajour.catalog_search_state.source.to_string())
-                .cloned()
-                .unwrap_or_default();
+            // Refresh the cached catalog in the background if a newer one is available.
+            if is_stale {
+                log::debug!("Message::CatalogCacheLoaded: cached catalog is stale, refreshing");
+
+                return Ok(Command::perform(
+                    catalog::get_catalog(ajour.config.catalog_sources.clone()),
+                    Message::CatalogDownloaded,
+                ));
+            }
+        }
+        Message::CatalogCacheLoaded(Err(error)) => {
+            log::debug!("Message::CatalogCacheLoaded: no cached catalog: {}", error);
 
-            ajour.catalog = Some(catalog);
+            return Ok(Command::perform(
+                catalog::get_catalog(ajour.config.catalog_sources.clone()),
+                Message::CatalogDownloaded,
+            ));
+        }
+        Message::CatalogDownloaded(Ok(catalog)) => {
+            log::debug!(
+                "Message::CatalogDownloaded({} addons in catalog)",
+                catalog.addons.len()
+            );
 
-            ajour.state.insert(Mode::Catalog, State::Ready);
+            let catalog_cache = CatalogCache::new(catalog.clone());
 
-            query_and_sort_catalog(ajour);
+            ajour.catalog_last_updated = Some(catalog_cache.fetched_at);
+
+            set_catalog(ajour, catalog);
+
+            return Ok(Command::perform(
+                save_catalog_cache(catalog_cache),
+                Message::CatalogCacheSaved,
+            ));
+        }
+        Message::CatalogCacheSaved(Ok(_)) => {
+            log::debug!("Message::CatalogCacheSaved");
+        }
+        Message::GameInfoCacheUpdated(Ok(_)) => {
+            log::debug!("Message::GameInfoCacheUpdated");
         }
         Message::Interaction(Interaction::CatalogQuery(query)) => {
             // Catalog search query
@@ -1227,7 +2158,9 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
             // to try and download it again. For InstallKind::Source, we should only
             // ever have one entry here so we just remove it
             install_addons.retain(|a| match kind {
-                InstallKind::Catalog { .. } => !(id == a.id && a.kind == kind),
+                InstallKind::Catalog { .. } | InstallKind::Manifest { .. } => {
+                    !(id == a.id && a.kind == kind)
+                }
                 InstallKind::Source => a.kind != kind,
             });
 
@@ -1264,7 +2197,7 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
             log::debug!("Interaction::CatalogResultSizeSelected({:?})", source);
 
             // Catalog source
-            ajour.catalog_search_state.source = source;
+            ajour.catalog_search_state.source = source.clone();
 
             ajour.catalog_search_state.categories = ajour
                 .catalog_categories_per_source_cache
@@ -1318,7 +2251,7 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                             InstallKind::Catalog { .. } => {
                                 install_addon.status = InstallStatus::Unavilable;
                             }
-                            InstallKind::Source => {
+                            InstallKind::Source | InstallKind::Manifest { .. } => {
                                 install_addon.status = InstallStatus::Error(error.to_string());
                             }
                         }
@@ -1394,6 +2327,16 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
                 }
             }
         }
+        Message::AddonLocked(result) => {
+            if let Err(e) = result.context("Failed to update lockfile") {
+                log_error(&e);
+            }
+        }
+        Message::AddonUnlocked(result) => {
+            if let Err(e) = result.context("Failed to update lockfile") {
+                log_error(&e);
+            }
+        }
         Message::Interaction(Interaction::InstallSCMQuery(query)) => {
             // install from scm search query
             ajour.install_from_scm_state.query = Some(query);
@@ -1433,15 +2376,11 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
         }
         Message::RefreshCatalog(_) => {
             if let Some(last_updated) = &ajour.catalog_last_updated {
-                let now = Utc::now();
-                let now_time = now.time();
-                let refresh_time = NaiveTime::from_hms(0, 40, 0);
-
-                if last_updated.date() < now.date() && now_time > refresh_time {
+                if catalog::is_stale(last_updated) {
                     log::debug!("Message::RefreshCatalog: catalog needs to be refreshed");
 
                     return Ok(Command::perform(
-                        catalog::get_catalog(),
+                        catalog::get_catalog(ajour.config.catalog_sources.clone()),
                         Message::CatalogDownloaded,
                     ));
                 }
@@ -1458,11 +2397,89 @@ pub fn handle_message(ajour: &mut Ajour, message: Message) -> Result<Command<Mes
             log_error(&error);
             ajour.error = Some(error);
         }
+        Message::CatalogCacheSaved(error @ Err(_)) => {
+            let error = error.context("Failed to save catalog cache").unwrap_err();
+            log_error(&error);
+        }
+        Message::GameInfoCacheUpdated(error @ Err(_)) => {
+            // Fingerprinting falls back to the cached or built-in parsing rules
+            let error = error.context("Failed to update game info").unwrap_err();
+            log_error(&error);
+        }
         Message::AddonCacheUpdated(error @ Err(_)) => {
             let error = error.context("Failed to update addon cache").unwrap_err();
             log_error(&error);
             ajour.error = Some(error);
         }
+        Message::Interaction(Interaction::CatalogSourceNameInput(name)) => {
+            ajour.catalog_sources_state.name = name;
+        }
+        Message::Interaction(Interaction::CatalogSourceLocationInput(location)) => {
+            ajour.catalog_sources_state.location = location;
+        }
+        Message::Interaction(Interaction::AddCatalogSource) => {
+            let name = ajour.catalog_sources_state.name.trim().to_string();
+            let location = ajour.catalog_sources_state.location.trim().to_string();
+
+            log::debug!("Interaction::AddCatalogSource({}, {})", &name, &location);
+
+            // Names must be unique, and can't shadow one of the official sources.
+            let name_taken = CatalogSource::all(&ajour.config.catalog_sources)
+                .iter()
+                .any(|s| s.to_string().eq_ignore_ascii_case(&name));
+
+            if name.is_empty() || location.is_empty() || name_taken {
+                return Ok(Command::none());
+            }
+
+            let source = CustomSource {
+                name,
+                // Parsing the location is infallible.
+                location: location.parse().unwrap(),
+            };
+
+            ajour.config.catalog_sources.push(source);
+            ajour.catalog_sources_state.name = String::new();
+            ajour.catalog_sources_state.location = String::new();
+
+            return Ok(catalog_sources_changed(ajour));
+        }
+        Message::Interaction(Interaction::RemoveCatalogSource(name)) => {
+            log::debug!("Interaction::RemoveCatalogSource({})", &name);
+
+            ajour.config.catalog_sources.retain(|s| s.name != name);
+
+            // Reset the source filter if we removed the selected source.
+            if ajour.catalog_search_state.source == CatalogSource::Custom(name) {
+                ajour.catalog_search_state.source = CatalogSource::Choice(catalog::Source::Curse);
+            }
+
+            return Ok(catalog_sources_changed(ajour));
+        }
+        Message::Interaction(Interaction::MoveCatalogSourceUp(name)) => {
+            log::debug!("Interaction::MoveCatalogSourceUp({})", &name);
+
+            let sources = &mut ajour.config.catalog_sources;
+            if let Some(idx) = sources.iter().position(|s| s.name == name) {
+                if idx > 0 {
+                    sources.swap(idx, idx - 1);
+
+                    return Ok(catalog_sources_changed(ajour));
+                }
+            }
+        }
+        Message::Interaction(Interaction::MoveCatalogSourceDown(name)) => {
+            log::debug!("Interaction::MoveCatalogSourceDown({})", &name);
+
+            let sources = &mut ajour.config.catalog_sources;
+            if let Some(idx) = sources.iter().position(|s| s.name == name) {
+                if idx < sources.len() - 1 {
+                    sources.swap(idx, idx + 1);
+
+                    return Ok(catalog_sources_changed(ajour));
+                }
+            }
+        }
         Message::Interaction(Interaction::PickSelfUpdateChannel(channel)) => {
             log::debug!("Interaction::PickSelfUpdateChannel({:?})", channel);
 
@@ -1525,6 +2542,112 @@ async fn select_directory() -> Option<PathBuf> {
     None
 }
 
+async fn select_backup_archive(backup_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = backup_dir.as_ref().and_then(|d| d.to_str());
+
+    let dialog = OpenSingleFile {
+        dir,
+        filter: Some(&["zip", "json"]),
+    };
+    if let Ok(show) = dialog.show() {
+        return show;
+    }
+
+    None
+}
+
+/// Asks for a folder and exports the backup at `archive` to it as a .zip
+/// archive. Returns `None` if no folder is chosen.
+async fn select_export_directory(
+    archive: PathBuf,
+    compression: BackupCompression,
+) -> Option<Result<PathBuf, FilesystemError>> {
+    let dest = select_directory().await?;
+
+    Some(export_backup(archive, dest, compression).await)
+}
+
+/// Asks for a folder and saves `manifest` to it. Returns `None` if no folder
+/// is chosen.
+async fn select_manifest_export_directory(
+    manifest: AddonManifest,
+) -> Option<Result<PathBuf, FilesystemError>> {
+    let dir = select_directory().await?;
+
+    let path = dir.join(format!(
+        "ajour_addons_{}_{}.yml",
+        manifest.flavor.to_string().to_lowercase().replace(' ', "_"),
+        manifest.exported_at.format("%Y-%m-%d_%H-%M-%S")
+    ));
+
+    Some(save_manifest(manifest, path.clone()).await.map(|_| path))
+}
+
+/// Loads the SavedVariables files of `addon`, along with its primary folder.
+async fn load_addon_saved_variables(
+    addon: Addon,
+    wtf_directory: PathBuf,
+) -> (String, Result<Vec<SavedVariablesFile>, FilesystemError>) {
+    let id = addon.primary_folder_id.clone();
+
+    (id, load_saved_variables(addon, wtf_directory).await)
+}
+
+/// Asks for a folder and exports the SavedVariables file at `path` to it as
+/// `file_name`. Returns `None` if no folder is chosen.
+async fn select_saved_variables_export_directory(
+    path: PathBuf,
+    file_name: String,
+) -> Option<Result<PathBuf, ParseError>> {
+    let dir = select_directory().await?;
+
+    Some(export_saved_variables(path, dir.join(file_name)).await)
+}
+
+/// Asks for an exported SavedVariables file and imports it as the file of
+/// `folder` in `scope`. Returns `None` if no file is chosen.
+async fn select_saved_variables_import_file(
+    folder: AddonFolder,
+    scope: Scope,
+    wtf_directory: PathBuf,
+) -> Option<Result<Option<PathBuf>, ParseError>> {
+    let dialog = OpenSingleFile {
+        dir: None,
+        filter: Some(&["lua"]),
+    };
+    let path = dialog.show().ok().flatten()?;
+
+    Some(import_saved_variables(path, folder, scope, wtf_directory).await)
+}
+
+/// Asks for a manifest and loads it. Returns `None` if no file is chosen.
+async fn select_manifest() -> Option<Result<AddonManifest, FilesystemError>> {
+    let dialog = OpenSingleFile {
+        dir: None,
+        filter: Some(&["yml", "yaml", "json"]),
+    };
+    let path = dialog.show().ok().flatten()?;
+
+    Some(load_manifest(path).await)
+}
+
+/// Lists the backups in `backup_dir` along with what each of them contains.
+async fn list_backups_with_contents(
+    backup_dir: PathBuf,
+) -> Vec<(BackupArchive, Vec<ArchiveSection>)> {
+    let mut backups = vec![];
+
+    for archive in list_backups(backup_dir).await {
+        let sections = archive_contents(archive.path.clone())
+            .await
+            .unwrap_or_default();
+
+        backups.push((archive, sections));
+    }
+
+    backups
+}
+
 async fn perform_read_addon_directory(
     addon_cache: Option<Arc<Mutex<AddonCache>>>,
     fingerprint_cache: Option<Arc<Mutex<FingerprintCache>>>,
@@ -1567,19 +2690,30 @@ async fn perform_hash_addon(
     )
 }
 
-/// Unzips `Addon` at given `from_directory` and moves it `to_directory`.
+/// Unzips `Addon` at given `from_directory` and moves it `to_directory`. If
+/// `snapshot` is set, the current version of the addon is snapshotted first.
 async fn perform_unpack_addon(
     reason: DownloadReason,
     flavor: Flavor,
     addon: Addon,
     from_directory: PathBuf,
     to_directory: PathBuf,
+    snapshot: Option<SnapshotSettings>,
 ) -> (
     DownloadReason,
     Flavor,
     String,
     Result<Vec<AddonFolder>, FilesystemError>,
 ) {
+    if let Some(settings) = snapshot {
+        if let Err(e) = snapshot_addon(addon.clone(), flavor, settings)
+            .await
+            .context(format!("Failed to snapshot {}", addon.title()))
+        {
+            log_error(&e);
+        }
+    }
+
     (
         reason,
         flavor,
@@ -1588,6 +2722,19 @@ async fn perform_unpack_addon(
     )
 }
 
+/// Reverts `Addon` to its newest snapshot.
+async fn perform_revert_addon(
+    flavor: Flavor,
+    addon: Addon,
+    settings: SnapshotSettings,
+) -> (Flavor, String, Result<NaiveDateTime, FilesystemError>) {
+    (
+        flavor,
+        addon.primary_folder_id.clone(),
+        revert_addon(addon, flavor, settings).await,
+    )
+}
+
 async fn perform_fetch_latest_addon(
     install_kind: InstallKind,
     id: String,
@@ -1623,11 +2770,18 @@ async fn perform_fetch_latest_addon(
 
                 RepositoryPackage::from_source_url(flavor, url)?
             }
+            InstallKind::Manifest { repository, .. } => {
+                RepositoryPackage::from_kind_and_id(flavor, repository, id)?
+            }
         };
         repo_package.resolve_metadata().await?;
 
         addon.set_repository(repo_package);
 
+        if let InstallKind::Manifest { channel, .. } = install_kind {
+            addon.release_channel = channel;
+        }
+
         Ok(addon)
     }
 
@@ -1799,39 +2953,99 @@ fn sort_catalog_addons(
     }
 }
 
+/// Saves the catalog sources to the config and downloads the catalog again
+/// with the new sources.
+fn catalog_sources_changed(ajour: &mut Ajour) -> Command<Message> {
+    let _ = ajour.config.save();
+
+    ajour.catalog_search_state.sources = CatalogSource::all(&ajour.config.catalog_sources);
+
+    Command::perform(
+        catalog::get_catalog(ajour.config.catalog_sources.clone()),
+        Message::CatalogDownloaded,
+    )
+}
+
+/// Sets the catalog and rebuilds the categories and search results from it.
+fn set_catalog(ajour: &mut Ajour, catalog: Catalog) {
+    let mut categories_per_source = catalog
+        .addons
+        .iter()
+        .fold(HashMap::new(), |mut map, addon| {
+            let categories: Vec<_> = addon
+                .categories
+                .iter()
+                .map(|c| CatalogCategory::Choice(c.to_string()))
+                .collect();
+
+            // Addons from a custom catalog are also listed under that catalog.
+            if let CatalogOrigin::Custom(name) = &addon.origin {
+                map.entry(name.clone())
+                    .or_insert_with(Vec::new)
+                    .extend(categories.clone());
+            }
+
+            map.entry(addon.source.to_string())
+                .or_insert_with(Vec::new)
+                .extend(categories);
+            map
+        });
+    categories_per_source.iter_mut().for_each(move |s| {
+        s.1.sort();
+        s.1.dedup();
+        s.1.insert(0, CatalogCategory::All);
+    });
+
+    ajour.catalog_categories_per_source_cache = categories_per_source;
+
+    ajour.catalog_search_state.categories = ajour
+        .catalog_categories_per_source_cache
+        .get(&ajour.catalog_search_state.source.to_string())
+        .cloned()
+        .unwrap_or_default();
+
+    ajour.catalog_search_index = SearchIndex::new(&catalog.addons);
+    ajour.catalog = Some(catalog);
+
+    ajour.state.insert(Mode::Catalog, State::Ready);
+
+    query_and_sort_catalog(ajour);
+}
+
 fn query_and_sort_catalog(ajour: &mut Ajour) {
     if let Some(catalog) = &ajour.catalog {
-        let query = ajour
-            .catalog_search_state
-            .query
-            .as_ref()
-            .map(|s| s.to_lowercase());
+        let query = SearchQuery::parse(
+            ajour
+                .catalog_search_state
+                .query
+                .as_deref()
+                .unwrap_or_default(),
+        );
         let flavor = &ajour.config.wow.flavor;
         let source = &ajour.catalog_search_state.source;
         let category = &ajour.catalog_search_state.category;
         let result_size = ajour.catalog_search_state.result_size.as_usize();
 
-        let mut catalog_rows: Vec<_> = catalog
-            .addons
-            .iter()
+        let mut catalog_rows: Vec<_> = ajour
+            .catalog_search_index
+            .search(&query)
+            .into_iter()
+            .filter_map(|result| catalog.addons.get(result.index))
             .filter(|a| !a.game_versions.is_empty())
-            .filter(|a| {
-                let cleaned_text =
-                    format!("{} {}", a.name.to_lowercase(), a.summary.to_lowercase());
-
-                if let Some(query) = &query {
-                    cleaned_text.contains(query)
-                } else {
-                    true
-                }
-            })
             .filter(|a| {
                 a.game_versions
                     .iter()
                     .any(|gc| gc.flavor == flavor.base_flavor())
             })
-            .filter(|a| match source {
-                CatalogSource::Choice(source) => a.source == *source,
+            // A source typed in the query overrides the source picker.
+            .filter(|a| {
+                !query.sources.is_empty()
+                    || match source {
+                        CatalogSource::Choice(source) => a.source == *source,
+                        CatalogSource::Custom(name) => {
+                            matches!(&a.origin, CatalogOrigin::Custom(origin) if origin == name)
+                        }
+                    }
             })
             .filter(|a| match category {
                 CatalogCategory::All => true,
@@ -1841,22 +3055,22 @@ fn query_and_sort_catalog(ajour: &mut Ajour) {
             .map(CatalogRow::from)
             .collect();
 
-        let sort_direction = ajour
-            .catalog_header_state
-            .previous_sort_direction
-            .unwrap_or(SortDirection::Desc);
-        let column_key = ajour
-            .catalog_header_state
-            .previous_column_key
-            .unwrap_or(CatalogColumnKey::NumDownloads);
+        // Keep the relevance order of the search unless a column has been
+        // sorted by the user.
+        if !query.has_terms() || ajour.catalog_header_state.previous_column_key.is_some() {
+            let sort_direction = ajour
+                .catalog_header_state
+                .previous_sort_direction
+                .unwrap_or(SortDirection::Desc);
+            let column_key = ajour
+                .catalog_header_state
+                .previous_column_key
+                .unwrap_or(CatalogColumnKey::NumDownloads);
 
-        sort_catalog_addons(&mut catalog_rows, sort_direction, column_key, flavor);
+            sort_catalog_addons(&mut catalog_rows, sort_direction, column_key, flavor);
+        }
 
-        catalog_rows = catalog_rows
-            .into_iter()
-            .enumerate()
-            .filter_map(|(idx, row)| if idx < result_size { Some(row) } else { None })
-            .collect();
+        catalog_rows.truncate(result_size);
 
         ajour.catalog_search_state.catalog_rows = catalog_rows;
     }
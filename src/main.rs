@@ -66,18 +66,101 @@ pub fn main() {
 
     match opts.command {
         Some(command) => {
+            // Exit code is 1 if the command failed. `update` uses its own
+            // codes to tell if anything was updated.
+            let mut exit_code = 0;
+
             // Process the command and exit
             if let Err(e) = match command {
                 cli::Command::Backup {
                     backup_folder,
                     destination,
                     flavors,
-                } => command::backup(backup_folder, destination, flavors),
-                cli::Command::Update => command::update_all_addons(),
-                cli::Command::Install { url, flavor } => command::install_from_source(url, flavor),
+                    format,
+                    include,
+                    exclude,
+                    compression,
+                } => command::backup(
+                    backup_folder,
+                    destination,
+                    flavors,
+                    format,
+                    include,
+                    exclude,
+                    compression,
+                ),
+                cli::Command::ExportBackup {
+                    archive,
+                    destination,
+                } => command::export_backup(archive, destination),
+                cli::Command::Export { flavor, path } => command::export(flavor, path),
+                cli::Command::Import {
+                    flavor,
+                    dry_run,
+                    path,
+                } => command::import(flavor, path, dry_run),
+                cli::Command::Lock { flavor, path } => command::lock(flavor, path),
+                cli::Command::Sync {
+                    flavor,
+                    locked,
+                    dry_run,
+                    path,
+                } => command::sync(flavor, path, locked, dry_run),
+                cli::Command::Update {
+                    flavor,
+                    addons,
+                    exclude,
+                    dry_run,
+                    report,
+                } => command::update(flavor, addons, exclude, dry_run, report)
+                    .map(|outcome| exit_code = outcome.exit_code()),
+                cli::Command::Install {
+                    addon,
+                    flavor,
+                    channel,
+                } => command::install(addon, flavor, channel),
+                cli::Command::List {
+                    flavor,
+                    outdated,
+                    unknown,
+                    ignored,
+                    format,
+                } => command::list(flavor, outdated, unknown, ignored, format),
+                cli::Command::Remove { flavor, addons } => command::remove(flavor, addons),
+                cli::Command::Ignore { flavor, addons } => command::ignore(flavor, addons),
+                cli::Command::Unignore { flavor, addons } => command::unignore(flavor, addons),
+                cli::Command::Channel {
+                    flavor,
+                    channel,
+                    addons,
+                } => command::channel(flavor, channel, addons),
+                cli::Command::Revert { flavor, addons } => command::revert(flavor, addons),
+                cli::Command::Profile { flavor, command } => command::profile(flavor, command),
+                cli::Command::SavedVariables { flavor, command } => {
+                    command::saved_variables(flavor, command)
+                }
+                cli::Command::Config { command } => command::config(command),
+                cli::Command::Restore {
+                    backup_folder,
+                    flavors,
+                    list,
+                    archive,
+                } => command::restore(archive, backup_folder, flavors, list),
+                cli::Command::Search {
+                    query,
+                    flavor,
+                    source,
+                    category,
+                    limit,
+                    json,
+                } => command::search(query, flavor, source, category, limit, json),
             } {
                 log_error(&e);
+
+                exit_code = 1;
             }
+
+            std::process::exit(exit_code);
         }
         None => {
             // Start the GUI
@@ -119,7 +202,11 @@ fn setup_logger(is_cli: bool, is_debug: bool) -> Result<()> {
         logger = logger.level_for("ajour_core", log::LevelFilter::Trace);
     }
 
-    if is_cli || is_debug {
+    // Command output such as `--format json` is printed to stdout, so the
+    // CLI logs to stderr to keep it parseable.
+    if is_cli {
+        logger = logger.chain(std::io::stderr());
+    } else if is_debug {
         logger = logger.chain(std::io::stdout());
     }
 